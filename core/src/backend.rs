@@ -0,0 +1,18 @@
+//! What turns audio into text: whisper.cpp with a loaded model, or a server speaking the OpenAI API.
+//! Requests run on either, see `TranscribeRequest::run`.
+
+use crate::transcribe::{self, TranscribeRequest, WhisperContext};
+use crate::transcript::Transcript;
+use eyre::Result;
+
+pub trait TranscriptionBackend {
+    /// Transcribe the request and call its handlers. Options the backend has no use for are ignored.
+    fn transcribe(&self, request: TranscribeRequest) -> Result<Transcript>;
+}
+
+/// whisper.cpp, decoding with the request's state when it has one
+impl TranscriptionBackend for WhisperContext {
+    fn transcribe(&self, request: TranscribeRequest) -> Result<Transcript> {
+        transcribe::transcribe(self, request)
+    }
+}
@@ -0,0 +1,175 @@
+//! On disk cache of transcripts, keyed by the normalized audio, the model and the options.
+//! Entries are evicted least recently used first once the cache grows over its size limit.
+
+use crate::config::TranscribeOptions;
+use crate::transcribe::DiarizeOptions;
+use crate::transcript::Transcript;
+use eyre::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub const DEFAULT_MAX_BYTES: u64 = 100 * 1024 * 1024;
+/// Model hashes, so multi gigabyte models are hashed once
+const MODEL_HASHES_FILENAME: &str = "models.json";
+const ENTRY_EXTENSION: &str = "json";
+
+/// Options that don't change the transcript
+const IGNORED_OPTIONS: [&str; 4] = ["path", "verbose", "n_threads", "no_cache"];
+
+#[derive(Debug, Clone)]
+pub struct TranscriptCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    model_hash: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ModelHash {
+    len: u64,
+    modified: SystemTime,
+    hash: String,
+}
+
+fn model_hash(dir: &Path, model_path: &Path) -> Result<String> {
+    let metadata = fs::metadata(model_path).with_context(|| format!("model not found at {}", model_path.display()))?;
+    let modified = metadata.modified()?;
+    let hashes_path = dir.join(MODEL_HASHES_FILENAME);
+    let mut hashes: HashMap<String, ModelHash> = fs::read_to_string(&hashes_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let key = model_path.to_string_lossy().to_string();
+    if let Some(known) = hashes.get(&key) {
+        if known.len == metadata.len() && known.modified == modified {
+            return Ok(known.hash.clone());
+        }
+    }
+
+    tracing::debug!("hashing model {}", model_path.display());
+    let hash = sha256::try_digest(model_path).context("failed to hash model")?;
+    hashes.insert(
+        key,
+        ModelHash {
+            len: metadata.len(),
+            modified,
+            hash: hash.clone(),
+        },
+    );
+    fs::write(&hashes_path, serde_json::to_string(&hashes)?)?;
+    Ok(hash)
+}
+
+fn is_entry(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(ENTRY_EXTENSION)
+        && path.file_name().and_then(|n| n.to_str()) != Some(MODEL_HASHES_FILENAME)
+}
+
+impl TranscriptCache {
+    /// Cache in `dir` for transcripts made with the model at `model_path`
+    pub fn open(dir: PathBuf, model_path: &Path) -> Result<Self> {
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create cache folder {}", dir.display()))?;
+        let model_hash = model_hash(&dir, model_path)?;
+        Ok(Self {
+            dir,
+            max_bytes: DEFAULT_MAX_BYTES,
+            model_hash,
+        })
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Key of a transcription of `samples`, the normalized audio
+    pub fn key(&self, samples: &[i16], options: &TranscribeOptions, diarize_options: Option<&DiarizeOptions>) -> Result<String> {
+        let audio: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+        let mut options = serde_json::to_value(options)?;
+        if let Some(options) = options.as_object_mut() {
+            for ignored in IGNORED_OPTIONS {
+                options.remove(ignored);
+            }
+        }
+        // Debug output covers the models, thresholds and speaker profiles
+        let diarize = diarize_options.map(|d| format!("{:?}", d)).unwrap_or_default();
+        Ok(sha256::digest(format!(
+            "{}:{}:{}:{}",
+            sha256::digest(&audio),
+            self.model_hash,
+            options,
+            sha256::digest(diarize)
+        )))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension(ENTRY_EXTENSION)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Transcript> {
+        let path = self.entry_path(key);
+        let content = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(transcript) => {
+                // Mark it as recently used
+                if let Ok(file) = fs::File::options().append(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Some(transcript)
+            }
+            Err(error) => {
+                tracing::warn!("removing unreadable cache entry {}: {:?}", path.display(), error);
+                let _ = fs::remove_file(path);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, transcript: &Transcript) -> Result<()> {
+        let path = self.entry_path(key);
+        // Written aside then renamed, so a crash never leaves half an entry
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string(transcript)?)?;
+        fs::rename(&temp_path, &path)?;
+        self.evict()
+    }
+
+    /// Remove the least recently used entries until the cache fits its size limit
+    fn evict(&self) -> Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !is_entry(&path) {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            entries.push((metadata.modified()?, metadata.len(), path));
+        }
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            tracing::debug!("evicting cached transcript {}", path.display());
+            fs::remove_file(&path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    /// Remove every cached transcript in `dir`, whatever the model
+    pub fn clear(dir: &Path) -> Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if is_entry(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Long recordings transcribed in chunks cut at silences, overlapping so words at the cuts aren't lost.
+//! Chunks are decoded in parallel when the request has spare whisper states, and stitched back in order,
+//! keeping each overlap's words once.
+
+use crate::config::TranscribeOptions;
+use crate::hallucination;
+use crate::pool::StatePool;
+use crate::transcribe::{decode, Decoded};
+use crate::transcript::Segment;
+use crate::vad;
+use eyre::Result;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use whisper_rs::{FullParams, WhisperContext, WhisperState};
+
+pub const DEFAULT_OVERLAP_SECS: u32 = 5;
+const SAMPLE_RATE: usize = 16000;
+const SAMPLES_PER_CENTISECOND: usize = 160;
+/// A cut moves back at most this much from the chunk length to land on a silence
+const MAX_CUT_SHIFT_SECS: usize = 10;
+/// Emitted segments checked for words the next chunk transcribed again
+const SEAM_SEGMENTS: usize = 3;
+/// How often the abort handler is polled and progress reported while chunks are decoded
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Ranges of `samples` to transcribe separately, a single one when chunking is off or the audio is short enough
+pub fn plan_chunks(samples: &[i16], options: &TranscribeOptions) -> Vec<Range<usize>> {
+    let chunk = match options.chunk_secs {
+        Some(chunk_secs) => chunk_secs as usize * SAMPLE_RATE,
+        None => {
+            return vec![Range {
+                start: 0,
+                end: samples.len(),
+            }]
+        }
+    };
+    let overlap = options.chunk_overlap_secs.unwrap_or(DEFAULT_OVERLAP_SECS) as usize * SAMPLE_RATE;
+    let max_shift = (MAX_CUT_SHIFT_SECS * SAMPLE_RATE).min(chunk / 4);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    // The last chunk takes whatever is left rather than leaving a sliver
+    while samples.len() - start > chunk + overlap {
+        let target = start + chunk;
+        let cut = target - max_shift + vad::quietest_point(&samples[target - max_shift..target]);
+        chunks.push(start..cut + overlap / 2);
+        start = cut - overlap / 2;
+    }
+    chunks.push(start..samples.len());
+    chunks
+}
+
+/// Middles of the overlaps, where each chunk hands over to the next, in centiseconds moved by `offset`
+pub fn cuts(chunks: &[Range<usize>], offset: i64) -> Vec<i64> {
+    chunks
+        .windows(2)
+        .map(|pair| ((pair[0].end + pair[1].start) / 2 / SAMPLES_PER_CENTISECOND) as i64 + offset)
+        .collect()
+}
+
+/// Whether `new` is words of `kept` transcribed again by the next chunk, maybe split or timed a little differently
+fn is_duplicate(kept: &Segment, new: &Segment) -> bool {
+    let overlaps = new.start < kept.stop && kept.start < new.stop;
+    let (kept, new) = (hallucination::normalize(&kept.text), hallucination::normalize(&new.text));
+    overlaps && !kept.is_empty() && !new.is_empty() && (kept.contains(&new) || new.contains(&kept))
+}
+
+/// Joins chunk transcripts in order. Each overlap is split at its middle, every chunk keeping its side,
+/// and words both chunks put across the middle are kept once.
+pub struct Stitcher {
+    cuts: Vec<i64>,
+    segments: Vec<Segment>,
+}
+
+impl Stitcher {
+    /// `cuts[i]` is where chunk `i` hands over to chunk `i + 1`, in centiseconds
+    pub fn new(cuts: Vec<i64>) -> Self {
+        Self {
+            cuts,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Add the segments of the next chunk in order, returns the ones kept
+    pub fn push(&mut self, index: usize, segments: Vec<Segment>) -> Vec<Segment> {
+        let from = index.checked_sub(1).map(|i| self.cuts[i]).unwrap_or(i64::MIN);
+        let to = self.cuts.get(index).copied().unwrap_or(i64::MAX);
+        let seam = self.segments.len().saturating_sub(SEAM_SEGMENTS);
+        let mut kept = Vec::new();
+        for segment in segments {
+            let middle = (segment.start + segment.stop) / 2;
+            if middle < from || middle >= to {
+                continue;
+            }
+            if self.segments[seam..].iter().any(|previous| is_duplicate(previous, &segment)) {
+                tracing::debug!("dropping {:?} transcribed by both chunks", segment.text);
+                continue;
+            }
+            kept.push(segment);
+        }
+        self.segments.extend(kept.iter().cloned());
+        kept
+    }
+
+    pub fn finish(self) -> Vec<Segment> {
+        self.segments
+    }
+}
+
+/// Where the chunks run. The request's state always takes part, free slots of the pool add workers up to `max`.
+pub struct Workers<'a> {
+    pub state: &'a mut WhisperState,
+    pub pool: Option<&'a Arc<StatePool>>,
+    pub max: usize,
+}
+
+/// Request handlers, only called from the calling thread
+pub struct Handlers<'a> {
+    pub segment: Option<&'a dyn Fn(Segment)>,
+    pub progress: Option<&'a dyn Fn(i32)>,
+    pub abort: Option<&'a dyn Fn() -> bool>,
+}
+
+/// Decode `chunks` of `samples` on the workers and stitch them.
+/// Segments are emitted as soon as every chunk before theirs is done, progress is weighted by chunk length.
+/// When aborted, the segments of the chunks finished in order are kept, up to the first unfinished one.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn transcribe_chunks(
+    ctx: &WhisperContext,
+    workers: Workers,
+    params: FullParams,
+    options: &TranscribeOptions,
+    samples: &[i16],
+    chunks: &[Range<usize>],
+    offset: i64,
+    speech_threshold: f32,
+    handlers: Handlers,
+) -> Result<Decoded> {
+    // Only slots free right now, so chunks never wait behind other jobs
+    let mut extra = Vec::new();
+    if let Some(pool) = workers.pool {
+        while extra.len() + 1 < workers.max.min(chunks.len()) {
+            match pool.try_acquire() {
+                Some(state) => extra.push(state),
+                None => break,
+            }
+        }
+    }
+    tracing::debug!("transcribing {} chunks on {} states", chunks.len(), extra.len() + 1);
+
+    let cuts = cuts(chunks, offset);
+    // Where the part of the audio each chunk keeps begins
+    let starts: Vec<i64> = std::iter::once(offset).chain(cuts.iter().copied()).collect();
+    let mut stitcher = Stitcher::new(cuts);
+    let next_chunk = AtomicUsize::new(0);
+    let aborted = AtomicBool::new(false);
+    let progress: Vec<AtomicI32> = chunks.iter().map(|_| AtomicI32::new(0)).collect();
+    let total_len: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+    let (sender, receiver) = mpsc::channel();
+
+    let mut states: Vec<&mut WhisperState> = vec![workers.state];
+    states.extend(extra.iter_mut().map(|state| &mut **state));
+    let resume_from = thread::scope(|scope| {
+        for state in states {
+            let sender = sender.clone();
+            let (next_chunk, aborted, progress, params) = (&next_chunk, &aborted, &progress, params.clone());
+            scope.spawn(move || loop {
+                let index = next_chunk.fetch_add(1, Ordering::SeqCst);
+                if index >= chunks.len() || aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+                let chunk = chunks[index].clone();
+                let on_progress = |value: i32| progress[index].store(value, Ordering::Relaxed);
+                let abort = || aborted.load(Ordering::Relaxed);
+                let result = decode(
+                    ctx,
+                    state,
+                    params.clone(),
+                    options,
+                    &samples[chunk.clone()],
+                    offset + (chunk.start / SAMPLES_PER_CENTISECOND) as i64,
+                    speech_threshold,
+                    None,
+                    Some(&on_progress),
+                    Some(&abort),
+                );
+                progress[index].store(100, Ordering::Relaxed);
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        // Only the workers hold senders now, the channel closes once they're all done
+        drop(sender);
+
+        let mut decoded: Vec<Option<Decoded>> = chunks.iter().map(|_| None).collect();
+        let mut stitched = 0;
+        let mut resume_from = None;
+        let mut reported = -1;
+        loop {
+            if handlers.abort.map(|abort| abort()).unwrap_or(false) {
+                aborted.store(true, Ordering::Relaxed);
+            }
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok((index, Ok(chunk))) => {
+                    decoded[index] = Some(chunk);
+                    // Nothing after an aborted chunk is kept, its audio would be missing in between
+                    while resume_from.is_none() {
+                        let Some(chunk) = decoded.get_mut(stitched).and_then(Option::take) else {
+                            break;
+                        };
+                        for segment in stitcher.push(stitched, chunk.segments) {
+                            if let Some(on_segment) = handlers.segment {
+                                on_segment(segment);
+                            }
+                        }
+                        match chunk.resume_from {
+                            Some(at) => resume_from = Some(at.max(starts[stitched])),
+                            None => stitched += 1,
+                        }
+                    }
+                }
+                Ok((index, Err(error))) => {
+                    aborted.store(true, Ordering::Relaxed);
+                    return Err(error.wrap_err(format!("failed to transcribe chunk {}", index)));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if let Some(on_progress) = handlers.progress {
+                let done: usize = chunks
+                    .iter()
+                    .zip(&progress)
+                    .map(|(chunk, value)| chunk.len() * value.load(Ordering::Relaxed).clamp(0, 100) as usize)
+                    .sum();
+                let value = (done / total_len.max(1)) as i32;
+                if value != reported {
+                    on_progress(value);
+                    reported = value;
+                }
+            }
+        }
+        if resume_from.is_none() && stitched < chunks.len() {
+            // Workers stopped before starting the chunk
+            resume_from = Some(starts[stitched]);
+        }
+        if resume_from.is_some() {
+            tracing::debug!("transcription aborted after {} of {} chunks", stitched, chunks.len());
+        }
+        Ok(resume_from)
+    })?;
+    Ok(Decoded {
+        segments: stitcher.finish(),
+        resume_from,
+    })
+}
@@ -1,8 +1,9 @@
 use core::fmt;
+use eyre::{bail, Result};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[derive(Deserialize, Serialize, ToSchema)]
+#[derive(Deserialize, Serialize, ToSchema, Default, Clone)]
 pub struct TranscribeOptions {
     pub path: String,
     pub lang: Option<String>,
@@ -10,11 +11,201 @@ pub struct TranscribeOptions {
 
     pub n_threads: Option<i32>,
     pub init_prompt: Option<String>,
+    /// Names and terms to spell this way, put before the initial prompt as long as they fit in its token budget
+    #[serde(default)]
+    pub vocabulary: Option<Vec<String>>,
     pub temperature: Option<f32>,
     pub translate: Option<bool>,
     pub max_text_ctx: Option<i32>,
     pub word_timestamps: Option<bool>,
     pub max_sentence_len: Option<i32>,
+    /// Skip silence before transcribing. Timestamps stay on the original timeline.
+    #[serde(default)]
+    pub vad: Option<bool>,
+    /// What to do with segments that look made up: loops of the same phrase or text over silence
+    #[serde(default)]
+    pub hallucination_filter: Option<HallucinationFilter>,
+    /// Decode suspect segments again at higher temperatures before filtering them
+    #[serde(default)]
+    pub temperature_fallback: Option<bool>,
+
+    /// Beam search with this many beams instead of greedy decoding. Slower, usually more accurate.
+    #[schema(minimum = 1, maximum = 8)]
+    pub beam_size: Option<i32>,
+    /// Candidates sampled with greedy decoding when whisper.cpp raises the temperature, the best one is kept
+    #[schema(minimum = 1, maximum = 8)]
+    pub best_of: Option<i32>,
+    /// How much whisper.cpp raises the temperature when a window fails the thresholds below, 0 disables it
+    #[schema(minimum = 0.0, maximum = 1.0)]
+    pub temperature_increment: Option<f32>,
+    /// Decode the window again at a higher temperature when its entropy is above this
+    #[schema(minimum = 0.0)]
+    pub entropy_threshold: Option<f32>,
+    /// Decode the window again at a higher temperature when its average token log probability is below this
+    #[schema(maximum = 0.0)]
+    pub logprob_threshold: Option<f32>,
+    /// Probability of the no speech token above which a window is considered silent
+    #[schema(minimum = 0.0, maximum = 1.0)]
+    pub no_speech_threshold: Option<f32>,
+    /// Don't start segments with a blank. Enabled by default.
+    pub suppress_blank: Option<bool>,
+    /// Never output non speech tokens such as music notes and sound effect brackets
+    pub suppress_non_speech_tokens: Option<bool>,
+    /// Token ids never to output, for words or symbols the model keeps making up. Ids past the vocabulary are ignored.
+    #[serde(default)]
+    pub suppress_tokens: Option<Vec<i32>>,
+    /// Transcribe from this position of the audio, in milliseconds. Timestamps stay relative to the file start.
+    #[schema(minimum = 0)]
+    pub offset_ms: Option<i32>,
+    /// Transcribe only this much audio after `offset_ms`, in milliseconds
+    #[schema(minimum = 1)]
+    pub duration_ms: Option<i32>,
+    /// Don't give the text of previous windows as context. Helps with repetition loops, hurts consistency.
+    pub no_context: Option<bool>,
+    /// Transcribe again even when the same audio was transcribed with the same model and options
+    #[serde(default)]
+    pub no_cache: Option<bool>,
+    /// Split audio longer than this, in seconds, into chunks cut at silences and transcribed separately.
+    /// Uses less memory on long recordings, and chunks run in parallel when the request has extra workers.
+    #[serde(default)]
+    #[schema(minimum = 30)]
+    pub chunk_secs: Option<u32>,
+    /// Audio shared by consecutive chunks, in seconds, so words at the cut aren't lost
+    #[serde(default)]
+    pub chunk_overlap_secs: Option<u32>,
+    /// What transcribes the audio, the loaded model unless set
+    #[serde(default, skip_serializing_if = "BackendOptions::is_local")]
+    pub backend: BackendOptions,
+}
+
+impl TranscribeOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.beam_size.is_some() && self.best_of.is_some() {
+            bail!("beam_size and best_of can't be used together, best_of only applies to greedy decoding")
+        }
+        if let Some(beam_size) = self.beam_size {
+            if !(1..=8).contains(&beam_size) {
+                bail!("beam_size must be between 1 and 8, got {beam_size}")
+            }
+        }
+        if let Some(best_of) = self.best_of {
+            if !(1..=8).contains(&best_of) {
+                bail!("best_of must be between 1 and 8, got {best_of}")
+            }
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=1.0).contains(&temperature) {
+                bail!("temperature must be between 0 and 1, got {temperature}")
+            }
+        }
+        if let Some(increment) = self.temperature_increment {
+            if !(0.0..=1.0).contains(&increment) {
+                bail!("temperature_increment must be between 0 and 1, got {increment}")
+            }
+        }
+        if let Some(entropy) = self.entropy_threshold {
+            if entropy < 0.0 {
+                bail!("entropy_threshold can't be negative, got {entropy}")
+            }
+        }
+        if let Some(logprob) = self.logprob_threshold {
+            if logprob > 0.0 {
+                bail!("logprob_threshold can't be positive, got {logprob}")
+            }
+        }
+        if let Some(no_speech) = self.no_speech_threshold {
+            if !(0.0..=1.0).contains(&no_speech) {
+                bail!("no_speech_threshold must be between 0 and 1, got {no_speech}")
+            }
+        }
+        if let Some(token) = self.suppress_tokens.iter().flatten().find(|token| **token < 0) {
+            bail!("suppress_tokens can't have negative token ids, got {token}")
+        }
+        if let Some(offset) = self.offset_ms {
+            if offset < 0 {
+                bail!("offset_ms can't be negative, got {offset}")
+            }
+        }
+        if let Some(duration) = self.duration_ms {
+            if duration <= 0 {
+                bail!("duration_ms must be positive, got {duration}")
+            }
+        }
+        if let Some(chunk) = self.chunk_secs {
+            // Shorter chunks than whisper's 30 second window only lose context
+            if chunk < 30 {
+                bail!("chunk_secs must be at least 30, got {chunk}")
+            }
+            if let Some(overlap) = self.chunk_overlap_secs {
+                if overlap * 2 >= chunk {
+                    bail!("chunk_overlap_secs must be less than half of chunk_secs, got {overlap}")
+                }
+            }
+        }
+        if let BackendOptions::Remote(remote) = &self.backend {
+            if !remote.url.starts_with("http://") && !remote.url.starts_with("https://") {
+                bail!("remote url must start with http:// or https://, got {}", remote.url)
+            }
+            if remote.model.is_empty() {
+                bail!("remote model can't be empty")
+            }
+            if remote.max_upload_mb == Some(0) {
+                bail!("max_upload_mb must be positive")
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackendOptions {
+    /// whisper.cpp with the loaded model
+    #[default]
+    Local,
+    /// A server with the OpenAI `/audio/transcriptions` API.
+    /// Only the language, prompt, vocabulary, temperature, translation, range and chunk options apply.
+    Remote(RemoteOptions),
+}
+
+impl BackendOptions {
+    pub fn is_local(&self) -> bool {
+        matches!(self, BackendOptions::Local)
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Clone, Default, PartialEq)]
+pub struct RemoteOptions {
+    /// API base, the endpoints are appended to it. For example `https://api.openai.com/v1`
+    pub url: String,
+    /// Model name on the server, `whisper-1` for OpenAI
+    pub model: String,
+    /// Sent as a bearer token. Never serialized, so it stays out of logs and cache keys.
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<String>,
+    /// Largest file the server accepts, longer audio is uploaded in chunks. 25MB by default, OpenAI's limit.
+    #[serde(default)]
+    #[schema(minimum = 1)]
+    pub max_upload_mb: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HallucinationFilter {
+    /// Keep them with `suspect` set
+    Flag,
+    Drop,
+}
+
+impl fmt::Debug for RemoteOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteOptions")
+            .field("url", &self.url)
+            .field("model", &self.model)
+            .field("api_key", &self.api_key.as_ref().map(|_| "***"))
+            .field("max_upload_mb", &self.max_upload_mb)
+            .finish()
+    }
 }
 
 impl fmt::Debug for TranscribeOptions {
@@ -0,0 +1,399 @@
+use crate::audio;
+use crate::transcribe::{create_normalized_audio, DiarizeOptions};
+use crate::transcript::Segment;
+use eyre::{bail, eyre, Result};
+pub use samwise_vector::cosine_similarity;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Voice of a known person, enrolled from sample clips
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakerProfile {
+    pub name: String,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeakerMatch {
+    pub speaker: String,
+    /// Cosine similarity between the segment and the matched speaker, from -1 to 1
+    pub confidence: f32,
+}
+
+/// Element wise mean of embeddings, used to combine several clips of the same speaker
+pub fn average_embedding(embeddings: &[Vec<f32>]) -> Option<Vec<f32>> {
+    let first = embeddings.first()?;
+    let mut sum = vec![0.0f32; first.len()];
+    for embedding in embeddings.iter().filter(|e| e.len() == first.len()) {
+        for (total, value) in sum.iter_mut().zip(embedding) {
+            *total += value;
+        }
+    }
+    let count = embeddings.iter().filter(|e| e.len() == first.len()).count() as f32;
+    Some(sum.into_iter().map(|v| v / count).collect())
+}
+
+/// Cosine similarity above which two clusters are considered the same voice, for callers without a preference.
+/// Tuned on meeting recordings with the wespeaker CAM++ model.
+pub const DEFAULT_CLUSTER_THRESHOLD: f32 = 0.5;
+
+/// Lowest similarity for a cluster to be named after an enrolled profile, whatever the clustering threshold.
+/// A permissive clustering threshold merges voices, it shouldn't put a known name on strangers.
+pub const MIN_PROFILE_MATCH: f32 = 0.5;
+
+/// Agglomerative clustering of embeddings with average linkage on cosine similarity.
+/// Clusters are merged while their similarity is above `threshold`, or while there are more than `max_clusters`,
+/// and never below `min_clusters`. Returns the cluster of every embedding, numbered by first appearance.
+pub fn cluster_embeddings(
+    embeddings: &[Vec<f32>],
+    threshold: f32,
+    min_clusters: Option<usize>,
+    max_clusters: Option<usize>,
+) -> Vec<usize> {
+    let n = embeddings.len();
+    let mut similarity = vec![vec![f32::NEG_INFINITY; n]; n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let value = cosine_similarity(&embeddings[i], &embeddings[j]);
+            similarity[i][j] = value;
+            similarity[j][i] = value;
+        }
+    }
+    let mut active = vec![true; n];
+    let mut size = vec![1usize; n];
+    let mut parent: Vec<usize> = (0..n).collect();
+
+    // Most similar active neighbour of every cluster, so a merge only rescans the rows it touched
+    let nearest = |row: &[f32], active: &[bool], own: usize| -> (usize, f32) {
+        row.iter()
+            .enumerate()
+            .filter(|(j, _)| *j != own && active[*j])
+            .map(|(j, v)| (j, *v))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((own, f32::NEG_INFINITY))
+    };
+    let mut best: Vec<(usize, f32)> = (0..n).map(|i| nearest(&similarity[i], &active, i)).collect();
+
+    let min_clusters = min_clusters.unwrap_or(1).max(1);
+    let mut count = n;
+    while count > min_clusters {
+        let Some((i, (j, value))) = (0..n)
+            .filter(|i| active[*i])
+            .map(|i| (i, best[i]))
+            .max_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+        else {
+            break;
+        };
+        if value < threshold && count <= max_clusters.unwrap_or(usize::MAX) {
+            break;
+        }
+
+        // Merge j into i, average linkage keeps the mean similarity of all pairs across clusters
+        for k in 0..n {
+            if active[k] && k != i && k != j {
+                let merged = (similarity[i][k] * size[i] as f32 + similarity[j][k] * size[j] as f32) / (size[i] + size[j]) as f32;
+                similarity[i][k] = merged;
+                similarity[k][i] = merged;
+            }
+        }
+        active[j] = false;
+        size[i] += size[j];
+        parent[j] = i;
+        count -= 1;
+
+        for k in 0..n {
+            if !active[k] {
+                continue;
+            }
+            if k == i || best[k].0 == i || best[k].0 == j {
+                best[k] = nearest(&similarity[k], &active, k);
+            } else if similarity[k][i] > best[k].1 {
+                best[k] = (i, similarity[k][i]);
+            }
+        }
+    }
+
+    let root = |mut i: usize| {
+        while parent[i] != i {
+            i = parent[i];
+        }
+        i
+    };
+    let mut roots: Vec<usize> = Vec::new();
+    (0..n)
+        .map(|i| {
+            let r = root(i);
+            match roots.iter().position(|known| *known == r) {
+                Some(label) => label,
+                None => {
+                    roots.push(r);
+                    roots.len() - 1
+                }
+            }
+        })
+        .collect()
+}
+
+/// Cluster the embeddings of a whole recording into speakers.
+/// Clusters matching an enrolled profile are labeled by name, the others are numbered from 0.
+/// Profiles are assigned one to one, the most similar pairs first, so two clusters never share a name.
+/// The confidence of a segment is its similarity to the profile it was named after, or to the centroid of its cluster.
+pub fn identify_speakers(embeddings: &[Vec<f32>], options: &DiarizeOptions) -> Vec<SpeakerMatch> {
+    let match_threshold = options.threshold.max(MIN_PROFILE_MATCH);
+    let labels = cluster_embeddings(embeddings, options.threshold, options.min_speakers, options.max_speakers);
+    let cluster_count = labels.iter().max().map_or(0, |max| max + 1);
+    tracing::debug!("estimated {} speakers from {} segments", cluster_count, embeddings.len());
+
+    let centroids: Vec<Vec<f32>> = (0..cluster_count)
+        .map(|cluster| {
+            let members: Vec<Vec<f32>> = labels
+                .iter()
+                .zip(embeddings)
+                .filter(|(label, _)| **label == cluster)
+                .map(|(_, embedding)| embedding.clone())
+                .collect();
+            average_embedding(&members).unwrap_or_default()
+        })
+        .collect();
+
+    let mut candidates: Vec<(usize, usize, f32)> = Vec::new();
+    for (cluster, centroid) in centroids.iter().enumerate() {
+        for (index, profile) in options.speaker_profiles.iter().enumerate() {
+            let similarity = cosine_similarity(&profile.embedding, centroid);
+            if similarity >= match_threshold {
+                candidates.push((cluster, index, similarity));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let mut assigned: Vec<Option<usize>> = vec![None; cluster_count];
+    let mut used = vec![false; options.speaker_profiles.len()];
+    for (cluster, index, _) in candidates {
+        if assigned[cluster].is_none() && !used[index] {
+            assigned[cluster] = Some(index);
+            used[index] = true;
+        }
+    }
+
+    let mut next_number = 0;
+    let speakers: Vec<(String, Vec<f32>)> = centroids
+        .into_iter()
+        .zip(assigned)
+        .map(|(centroid, profile)| match profile {
+            Some(index) => {
+                let profile = &options.speaker_profiles[index];
+                (profile.name.clone(), profile.embedding.clone())
+            }
+            None => {
+                next_number += 1;
+                ((next_number - 1).to_string(), centroid)
+            }
+        })
+        .collect();
+
+    labels
+        .iter()
+        .zip(embeddings)
+        .map(|(label, embedding)| {
+            let (speaker, reference) = &speakers[*label];
+            SpeakerMatch {
+                speaker: speaker.clone(),
+                confidence: cosine_similarity(reference, embedding),
+            }
+        })
+        .collect()
+}
+
+/// Speech segments found by pyannote along with their voice embedding.
+/// Segments too short to embed are left out.
+pub fn embed_segments(
+    samples: &[i16],
+    options: &DiarizeOptions,
+    abort_callback: Option<&dyn Fn() -> bool>,
+) -> Result<Vec<(pyannote_rs::Segment, Vec<f32>)>> {
+    let diarize_segments = pyannote_rs::segment(samples, 16000, &options.segment_model_path).map_err(|e| eyre!("{:?}", e))?;
+    let mut extractor = pyannote_rs::EmbeddingExtractor::new(&options.embedding_model_path).map_err(|e| eyre!("{:?}", e))?;
+    let mut embedded = Vec::new();
+    for segment in diarize_segments {
+        if abort_callback.is_some_and(|abort| abort()) {
+            break;
+        }
+        match extractor.compute(&segment.samples) {
+            Ok(embedding) => {
+                let embedding = embedding.collect();
+                embedded.push((segment, embedding));
+            }
+            Err(error) => tracing::debug!("no embedding for {:.2} - {:.2}: {:?}", segment.start, segment.end, error),
+        }
+    }
+    Ok(embedded)
+}
+
+/// Compute the voice embedding of a sample clip.
+/// The clip is split into speech segments and their embeddings are averaged, so silence doesn't weigh in.
+pub fn compute_speaker_embedding(audio_path: &Path, segment_model_path: &str, embedding_model_path: &str) -> Result<Vec<f32>> {
+    if !audio_path.exists() {
+        bail!("audio file doesn't exist")
+    }
+    let out_path = create_normalized_audio(audio_path.to_path_buf())?;
+    let samples = audio::parse_wav_file(&out_path)?;
+    std::fs::remove_file(out_path)?;
+
+    let mut extractor = pyannote_rs::EmbeddingExtractor::new(embedding_model_path).map_err(|e| eyre!("{:?}", e))?;
+    let segments = pyannote_rs::segment(&samples, 16000, segment_model_path).map_err(|e| eyre!("{:?}", e))?;
+    let mut embeddings = Vec::new();
+    for segment in segments {
+        match extractor.compute(&segment.samples) {
+            Ok(embedding) => embeddings.push(embedding.collect()),
+            Err(error) => tracing::debug!("skip enrollment segment {} - {}: {:?}", segment.start, segment.end, error),
+        }
+    }
+    if embeddings.is_empty() {
+        bail!("no speech found in {}", audio_path.display())
+    }
+    average_embedding(&embeddings).ok_or_else(|| eyre!("failed to average embeddings"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiarizePipeline {
+    /// Run whisper on every pyannote segment, one text segment per speech segment
+    #[default]
+    PerSegment,
+    /// Run whisper once on the whole file with word timestamps, then give words speakers by time overlap
+    Overlap,
+}
+
+/// Speech segments of the audio with the speaker of each, without transcribing anything
+pub fn speaker_turns(
+    samples: &[i16],
+    options: &DiarizeOptions,
+    abort_callback: Option<&dyn Fn() -> bool>,
+) -> Result<Vec<SpeakerTurn>> {
+    let embedded = embed_segments(samples, options, abort_callback)?;
+    let embeddings: Vec<Vec<f32>> = embedded.iter().map(|(_, embedding)| embedding.clone()).collect();
+    let speakers = identify_speakers(&embeddings, options);
+    Ok(embedded
+        .iter()
+        .zip(speakers)
+        .map(|((segment, _), speaker_match)| SpeakerTurn {
+            start: (segment.start * 100.0) as i64,
+            stop: (segment.end * 100.0) as i64,
+            speaker: speaker_match.speaker,
+            confidence: speaker_match.confidence,
+        })
+        .collect())
+}
+
+/// How the overlap pipeline groups words before giving them a speaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeakerAssignment {
+    /// Every word gets the speaker it overlaps the most, turns can change mid sentence
+    Word,
+    /// Whole sentences get the speaker overlapping most of them, which is steadier on short interjections
+    #[default]
+    Sentence,
+}
+
+/// Time range in which diarization heard a speaker, in centiseconds like whisper timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeakerTurn {
+    pub start: i64,
+    pub stop: i64,
+    pub speaker: String,
+    pub confidence: f32,
+}
+
+fn overlap(a_start: i64, a_stop: i64, b_start: i64, b_stop: i64) -> i64 {
+    (a_stop.min(b_stop) - a_start.max(b_start)).max(0)
+}
+
+/// Speaker overlapping the range the most, or the closest turn when nothing overlaps
+fn speaker_for_range(start: i64, stop: i64, turns: &[SpeakerTurn]) -> Option<(String, f32)> {
+    let mut totals: Vec<(&str, i64, f32)> = Vec::new();
+    for turn in turns {
+        let overlap = overlap(start, stop, turn.start, turn.stop);
+        if overlap == 0 {
+            continue;
+        }
+        match totals.iter_mut().find(|(speaker, _, _)| *speaker == turn.speaker) {
+            Some(total) => {
+                total.2 = (total.2 * total.1 as f32 + turn.confidence * overlap as f32) / (total.1 + overlap) as f32;
+                total.1 += overlap;
+            }
+            None => totals.push((&turn.speaker, overlap, turn.confidence)),
+        }
+    }
+    if let Some((speaker, _, confidence)) = totals.into_iter().max_by_key(|(_, overlap, _)| *overlap) {
+        return Some((speaker.to_string(), confidence));
+    }
+    turns
+        .iter()
+        .min_by_key(|turn| (turn.start - stop).max(start - turn.stop).max(0))
+        .map(|turn| (turn.speaker.clone(), turn.confidence))
+}
+
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end().ends_with(['.', '?', '!', '。', '？', '！', '…'])
+}
+
+/// Give speakers to word level whisper segments by time overlap with diarization turns,
+/// then merge consecutive segments of the same speaker so each turn keeps its full text.
+pub fn assign_speakers(words: &[Segment], turns: &[SpeakerTurn], assignment: SpeakerAssignment) -> Vec<Segment> {
+    // Units are the ranges of words which share a speaker
+    let mut units: Vec<&[Segment]> = Vec::new();
+    match assignment {
+        SpeakerAssignment::Word => units.extend(words.chunks(1)),
+        SpeakerAssignment::Sentence => {
+            let mut begin = 0;
+            for (i, word) in words.iter().enumerate() {
+                if ends_sentence(&word.text) || i + 1 == words.len() {
+                    units.push(&words[begin..=i]);
+                    begin = i + 1;
+                }
+            }
+        }
+    }
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut durations: Vec<i64> = Vec::new();
+    for unit in units {
+        let (Some(first), Some(last)) = (unit.first(), unit.last()) else {
+            continue;
+        };
+        let text: String = unit.iter().map(|w| w.text.as_str()).collect();
+        let assigned = speaker_for_range(first.start, last.stop, turns);
+        // Without any turn the unit still gets a placeholder, so unassigned units merge like the others
+        let speaker = Some(assigned.as_ref().map_or_else(|| "?".to_string(), |a| a.0.clone()));
+        let duration = (last.stop - first.start).max(1);
+        let suspect = unit.iter().any(|w| w.suspect == Some(true)).then_some(true);
+        match (segments.last_mut(), durations.last_mut()) {
+            (Some(previous), Some(previous_duration)) if previous.speaker == speaker => {
+                previous.stop = last.stop;
+                previous.text.push_str(&text);
+                // Confidence of a turn is the duration weighted mean of its parts
+                if let (Some(previous_confidence), Some((_, confidence))) = (previous.speaker_confidence, &assigned) {
+                    previous.speaker_confidence = Some(
+                        (previous_confidence * *previous_duration as f32 + confidence * duration as f32)
+                            / (*previous_duration + duration) as f32,
+                    );
+                }
+                *previous_duration += duration;
+                previous.suspect = previous.suspect.or(suspect);
+            }
+            _ => {
+                segments.push(Segment {
+                    start: first.start,
+                    stop: last.stop,
+                    text,
+                    speaker,
+                    speaker_confidence: assigned.map(|a| a.1),
+                    suspect,
+                });
+                durations.push(duration);
+            }
+        }
+    }
+    segments
+}
@@ -0,0 +1,111 @@
+//! Two pass transcription: a small model transcribes a quick draft that can be read right away,
+//! then the selected model transcribes again and its segments replace the draft by time range.
+
+use crate::backend::TranscriptionBackend;
+use crate::transcribe::TranscribeRequest;
+use crate::transcript::{Segment, Transcript};
+use eyre::Result;
+use serde::Serialize;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Accurate segments that supersede the draft segments of a time range, in centiseconds
+#[derive(Debug, Clone, Serialize)]
+pub struct Replacement {
+    pub start: i64,
+    pub stop: i64,
+    pub segments: Vec<Segment>,
+}
+
+pub type ReplaceCallback = Box<dyn Fn(Replacement)>;
+
+impl Replacement {
+    /// Whether a draft segment is superseded, by where most of it lies
+    pub fn covers(&self, segment: &Segment) -> bool {
+        let middle = (segment.start + segment.stop) / 2;
+        middle >= self.start && middle < self.stop
+    }
+
+    /// Replace the covered draft segments of `segments`, which are sorted by time
+    pub fn apply(&self, segments: &mut Vec<Segment>) {
+        segments.retain(|segment| !self.covers(segment));
+        let index = segments.partition_point(|segment| segment.start < self.start);
+        segments.splice(index..index, self.segments.iter().cloned());
+    }
+}
+
+/// Transcribe with `draft` first, giving its segments to the request's segment callback,
+/// then with `backend`, giving every accurate segment to `on_replace` along with the time range it supersedes.
+/// The draft is skipped when it fails, the accurate transcript is returned.
+pub(crate) fn run<B: TranscriptionBackend + ?Sized>(
+    draft: &dyn TranscriptionBackend,
+    on_replace: Option<ReplaceCallback>,
+    request: TranscribeRequest,
+    backend: &B,
+) -> Result<Transcript> {
+    let TranscribeRequest {
+        options,
+        progress_callback,
+        new_segment_callback,
+        abort_callback,
+        diarize_options,
+        cache,
+        state,
+        pool,
+        workers,
+        ..
+    } = request;
+
+    // Speakers and the cache are only for the accurate transcript
+    let mut draft_request = TranscribeRequest::new(options);
+    draft_request.new_segment_callback = new_segment_callback;
+    draft_request.abort_callback = abort_callback.clone();
+    let draft_stop = match draft.transcribe(draft_request) {
+        Ok(transcript) => {
+            tracing::debug!("draft took {}s", transcript.processing_time_sec);
+            transcript.segments.last().map(|segment| segment.stop)
+        }
+        Err(error) => {
+            tracing::warn!("draft transcription failed: {:?}", error);
+            None
+        }
+    };
+
+    let on_replace = on_replace.map(Rc::new);
+    let offset = options.offset_ms.unwrap_or(0) as i64 / 10;
+    // Start of the draft not replaced yet
+    let replaced_until = Rc::new(Cell::new(offset));
+    let mut request = TranscribeRequest::new(options).diarize(diarize_options).cache(cache);
+    request.progress_callback = progress_callback;
+    request.abort_callback = abort_callback;
+    request.state = state;
+    request.pool = pool;
+    request.workers = workers;
+    if let Some(ref on_replace) = on_replace {
+        let on_replace = on_replace.clone();
+        let replaced_until = replaced_until.clone();
+        request = request.on_segment(move |segment| {
+            let start = replaced_until.get();
+            let stop = segment.stop.max(start);
+            replaced_until.set(stop);
+            on_replace(Replacement {
+                start,
+                stop,
+                segments: vec![segment],
+            });
+        });
+    }
+    let transcript = request.run(backend)?;
+
+    // Draft segments past the last accurate one, heard only by the small model
+    if let (Some(on_replace), Some(draft_stop), false) = (on_replace, draft_stop, transcript.is_partial()) {
+        if draft_stop > replaced_until.get() {
+            on_replace(Replacement {
+                start: replaced_until.get(),
+                stop: draft_stop,
+                segments: Vec::new(),
+            });
+        }
+    }
+    Ok(transcript)
+}
@@ -0,0 +1,230 @@
+//! Detects segments whisper likely made up: loops of the same phrase, and text over silence.
+//! Uses the heuristics of the reference whisper implementation. Suspect segments can be decoded
+//! again at higher temperatures, and are dropped or flagged when they still fail the checks.
+
+use crate::config::{HallucinationFilter, TranscribeOptions};
+use crate::transcribe::setup_params;
+use crate::transcript::Segment;
+use crate::vad;
+use eyre::{Context, Result};
+use std::ffi::c_int;
+use std::ops::Range;
+use whisper_rs::{WhisperContext, WhisperState};
+
+/// Average token log probability below which whisper wasn't confident about the text
+pub const LOGPROB_THRESHOLD: f32 = -1.0;
+/// Text compressing better than this is mostly repeated
+pub const COMPRESSION_RATIO_THRESHOLD: f32 = 2.4;
+/// Share of silent audio above which the segment is considered to have no speech.
+/// Stands in for the no speech probability threshold of the reference implementation.
+pub const SILENCE_THRESHOLD: f32 = 0.6;
+/// Temperatures tried in order when decoding a suspect segment again
+pub const FALLBACK_TEMPERATURES: [f32; 5] = [0.2, 0.4, 0.6, 0.8, 1.0];
+/// Consecutive identical segments allowed before the next ones count as a loop
+const MAX_REPEATS: usize = 2;
+/// Shorter segments don't have enough context for whisper to decode them on their own
+const MIN_FALLBACK_MS: i64 = 1000;
+const SAMPLES_PER_CENTISECOND: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentQuality {
+    pub avg_logprob: f32,
+    pub compression_ratio: f32,
+    /// Share of the segment's audio quieter than the speech threshold, from the audio energy and not a model score.
+    /// It approximates whisper's no speech probability, which the bundled whisper.cpp doesn't expose per segment.
+    pub silence: f32,
+}
+
+impl SegmentQuality {
+    /// Measure the whisper segments in `segments` of the last decoding, as a whole
+    pub fn measure(ctx: &WhisperContext, state: &WhisperState, segments: Range<c_int>, silence: f32) -> Result<Self> {
+        let mut text = String::new();
+        let mut logprob_sum = 0.0;
+        let mut tokens = 0;
+        for segment in segments {
+            text += &state.full_get_segment_text_lossy(segment).context("failed to get segment")?;
+            let n_tokens = state.full_n_tokens(segment).context("failed to get number of tokens")?;
+            for token in 0..n_tokens {
+                let data = state
+                    .full_get_token_data(segment, token)
+                    .context("failed to get token data")?;
+                // Timestamps and other special tokens come after the text ones
+                if data.id >= ctx.token_eot() {
+                    continue;
+                }
+                logprob_sum += data.plog;
+                tokens += 1;
+            }
+        }
+        Ok(Self {
+            avg_logprob: if tokens > 0 { logprob_sum / tokens as f32 } else { 0.0 },
+            compression_ratio: compression_ratio(&text),
+            silence,
+        })
+    }
+
+    /// Worth decoding again at a higher temperature
+    pub fn needs_fallback(&self) -> bool {
+        self.compression_ratio > COMPRESSION_RATIO_THRESHOLD || self.avg_logprob < LOGPROB_THRESHOLD
+    }
+
+    /// Repeated text, or unconfident text over silence
+    pub fn is_hallucination(&self) -> bool {
+        self.compression_ratio > COMPRESSION_RATIO_THRESHOLD
+            || (self.silence > SILENCE_THRESHOLD && self.avg_logprob < LOGPROB_THRESHOLD)
+    }
+}
+
+/// Estimate of the gzip compression ratio the reference implementation uses:
+/// the text length over its size once repeated substrings are replaced by back references.
+pub fn compression_ratio(text: &str) -> f32 {
+    // Back references shorter than this cost more than the bytes they replace
+    const MIN_MATCH: usize = 3;
+    const REFERENCE_SIZE: usize = 2;
+
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        return 1.0;
+    }
+    let mut compressed = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let longest = (0..i)
+            .map(|j| bytes[i..].iter().zip(&bytes[j..]).take_while(|(a, b)| a == b).count())
+            .max()
+            .unwrap_or(0);
+        if longest >= MIN_MATCH {
+            compressed += REFERENCE_SIZE;
+            i += longest;
+        } else {
+            compressed += 1;
+            i += 1;
+        }
+    }
+    bytes.len() as f32 / compressed as f32
+}
+
+pub(crate) fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Which segments repeat the ones before them more than `MAX_REPEATS` times.
+/// Single words are skipped since word timestamps split every sentence into them.
+pub fn find_repeats(segments: &[Segment]) -> Vec<bool> {
+    let mut repeats = Vec::with_capacity(segments.len());
+    let mut previous = String::new();
+    let mut count = 0;
+    for segment in segments {
+        let text = normalize(&segment.text);
+        if !text.is_empty() && text == previous {
+            count += 1;
+        } else {
+            count = 1;
+        }
+        repeats.push(count > MAX_REPEATS && text.contains(' '));
+        previous = text;
+    }
+    repeats
+}
+
+/// Decode `samples` alone at increasing temperatures until the result passes the checks.
+/// Returns the first passing text, or the most confident one when none does.
+fn decode_with_fallback(
+    ctx: &WhisperContext,
+    state: &mut WhisperState,
+    options: &TranscribeOptions,
+    samples: &[i16],
+    silence: f32,
+) -> Result<Option<(String, SegmentQuality)>> {
+    let mut input = vec![0.0f32; samples.len()];
+    whisper_rs::convert_integer_to_float_audio(samples, &mut input)?;
+
+    let mut best: Option<(String, SegmentQuality)> = None;
+    for temperature in FALLBACK_TEMPERATURES {
+        let mut params = setup_params(ctx, options);
+        params.set_single_segment(true);
+        params.set_no_context(true);
+        params.set_temperature(temperature);
+        // Fallback is done here, don't let whisper.cpp raise the temperature on its own
+        params.set_temperature_inc(0.0);
+        state.full(params, &input).context("failed to transcribe")?;
+
+        let num_segments = state.full_n_segments().context("failed to get number of segments")?;
+        let mut text = String::new();
+        for s in 0..num_segments {
+            text += &state.full_get_segment_text_lossy(s).context("failed to get segment")?;
+        }
+        let quality = SegmentQuality::measure(ctx, state, 0..num_segments, silence)?;
+        tracing::debug!("fallback at temperature {temperature}: {:?} {:?}", text, quality);
+        if !quality.needs_fallback() {
+            return Ok(Some((text, quality)));
+        }
+        if best
+            .as_ref()
+            .map(|(_, b)| quality.avg_logprob > b.avg_logprob)
+            .unwrap_or(true)
+        {
+            best = Some((text, quality));
+        }
+    }
+    Ok(best)
+}
+
+/// Decode suspect segments again and drop or flag the ones still failing, as set in the options.
+/// `samples` is the audio whisper decoded, segment timestamps are relative to it.
+pub fn filter_segments(
+    ctx: &WhisperContext,
+    state: &mut WhisperState,
+    options: &TranscribeOptions,
+    samples: &[i16],
+    speech_threshold: f32,
+    decoded: Vec<(Segment, SegmentQuality)>,
+) -> Result<Vec<Segment>> {
+    let fallback = options.temperature_fallback.unwrap_or(false);
+    let mut checked = Vec::with_capacity(decoded.len());
+    for (mut segment, mut quality) in decoded {
+        if fallback && quality.needs_fallback() && segment.stop - segment.start >= MIN_FALLBACK_MS / 10 {
+            let start = (segment.start.max(0) as usize * SAMPLES_PER_CENTISECOND).min(samples.len());
+            let stop = (segment.stop.max(0) as usize * SAMPLES_PER_CENTISECOND).clamp(start, samples.len());
+            let silence = vad::silence_ratio(&samples[start..stop], speech_threshold);
+            if let Some((text, retry)) = decode_with_fallback(ctx, state, options, &samples[start..stop], silence)? {
+                if retry.avg_logprob > quality.avg_logprob || retry.compression_ratio < quality.compression_ratio {
+                    segment.text = text;
+                    quality = retry;
+                }
+            }
+        }
+        checked.push((segment, quality));
+    }
+
+    let segments: Vec<Segment> = checked.iter().map(|(segment, _)| segment.clone()).collect();
+    let repeats = find_repeats(&segments);
+    let mut filtered = Vec::with_capacity(checked.len());
+    for ((mut segment, quality), repeat) in checked.into_iter().zip(repeats) {
+        if !(repeat || quality.is_hallucination()) {
+            filtered.push(segment);
+            continue;
+        }
+        tracing::debug!("suspect segment {:?} {:?} repeat: {}", segment.text, quality, repeat);
+        match options.hallucination_filter {
+            Some(HallucinationFilter::Drop) => {}
+            Some(HallucinationFilter::Flag) => {
+                segment.suspect = Some(true);
+                filtered.push(segment);
+            }
+            None => filtered.push(segment),
+        }
+    }
+    Ok(filtered)
+}
+
+/// Whether the options ask for any checking at all
+pub fn enabled(options: &TranscribeOptions) -> bool {
+    options.hallucination_filter.is_some() || options.temperature_fallback.unwrap_or(false)
+}
@@ -1,8 +1,19 @@
 pub mod audio;
+pub mod backend;
+pub mod cache;
+pub mod chunk;
 pub mod config;
+pub mod diarize;
 pub mod downloader;
+pub mod draft;
+pub mod hallucination;
+pub mod model;
+pub mod pool;
+pub mod remote;
 pub mod transcribe;
 pub mod transcript;
+pub mod vad;
+pub mod vocabulary;
 
 #[cfg(test)]
 mod test;
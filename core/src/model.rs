@@ -0,0 +1,275 @@
+//! Checks of whisper model files before whisper.cpp loads them.
+//! whisper.cpp aborts the process on some malformed files, which `catch_unwind` can't recover from,
+//! so the header, the vocabulary and the tensor table are read here first and the file is rejected with a clear error.
+
+use eyre::{bail, Context, ContextCompat, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use utoipa::ToSchema;
+
+/// "ggml" as a little endian u32
+const GGML_MAGIC: u32 = 0x67676d6c;
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const MAX_TOKEN_BYTES: u32 = 1024;
+const MAX_TENSOR_NAME_BYTES: i32 = 256;
+const MAX_TENSOR_DIMS: i32 = 4;
+
+/// What the header of a model file tells about it
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ModelInfo {
+    /// tiny, base, small, medium or large, by the number of encoder layers
+    pub model_type: String,
+    pub multilingual: bool,
+    /// Weights type, such as f16 or q5_0
+    pub ftype: String,
+    pub n_vocab: i32,
+    pub n_audio_ctx: i32,
+    pub n_audio_state: i32,
+    pub n_audio_head: i32,
+    pub n_audio_layer: i32,
+    pub n_text_ctx: i32,
+    pub n_text_state: i32,
+    pub n_text_head: i32,
+    pub n_text_layer: i32,
+    pub n_mels: i32,
+    /// Tokens stored in the file, the special tokens are added by whisper.cpp
+    pub n_tokens: i32,
+    pub n_tensors: usize,
+    pub file_size: u64,
+}
+
+/// Read and check a whisper model file without loading its weights
+pub fn inspect(path: &Path) -> Result<ModelInfo> {
+    let file = File::open(path).with_context(|| format!("failed to open model {}", path.display()))?;
+    let file_size = file.metadata()?.len();
+    let mut reader = ModelReader {
+        inner: BufReader::new(file),
+        position: 0,
+        file_size,
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic, "magic")?;
+    if u32::from_le_bytes(magic) != GGML_MAGIC {
+        bail!("{} isn't a whisper model: {}", name, describe_format(&magic))
+    }
+
+    let n_vocab = reader.i32("n_vocab")?;
+    let n_audio_ctx = reader.i32("n_audio_ctx")?;
+    let n_audio_state = reader.i32("n_audio_state")?;
+    let n_audio_head = reader.i32("n_audio_head")?;
+    let n_audio_layer = reader.i32("n_audio_layer")?;
+    let n_text_ctx = reader.i32("n_text_ctx")?;
+    let n_text_state = reader.i32("n_text_state")?;
+    let n_text_head = reader.i32("n_text_head")?;
+    let n_text_layer = reader.i32("n_text_layer")?;
+    let n_mels = reader.i32("n_mels")?;
+    let ftype = reader.i32("ftype")?;
+
+    let dimensions = [
+        n_vocab,
+        n_audio_ctx,
+        n_audio_state,
+        n_audio_head,
+        n_audio_layer,
+        n_text_ctx,
+        n_text_state,
+        n_text_head,
+        n_text_layer,
+    ];
+    if dimensions.iter().any(|dimension| *dimension <= 0) || n_audio_state % n_audio_head != 0 || n_text_state % n_text_head != 0
+    {
+        bail!("{} has invalid model dimensions {:?}", name, dimensions)
+    }
+    if n_mels != 80 && n_mels != 128 {
+        bail!("{} has {} mel bands, whisper models have 80 or 128", name, n_mels)
+    }
+    let ftype_name = ftype_name(ftype % 1000).with_context(|| format!("{} has unknown weights type {}", name, ftype))?;
+
+    // Mel filters
+    let filters_mels = reader.i32("mel filters")?;
+    let filters_fft = reader.i32("mel filters")?;
+    if filters_mels != n_mels || filters_fft <= 0 {
+        bail!(
+            "{} has mel filters of {}x{} for {} mel bands",
+            name,
+            filters_mels,
+            filters_fft,
+            n_mels
+        )
+    }
+    reader.skip(filters_mels as u64 * filters_fft as u64 * 4, "mel filters")?;
+
+    // Vocabulary
+    let n_tokens = reader.i32("vocabulary")?;
+    if n_tokens <= 0 || n_tokens > n_vocab {
+        bail!("{} has {} tokens for a vocabulary of {}", name, n_tokens, n_vocab)
+    }
+    for _ in 0..n_tokens {
+        let len = reader.u32("vocabulary")?;
+        if len > MAX_TOKEN_BYTES {
+            bail!("{} has a corrupted vocabulary", name)
+        }
+        reader.skip(len as u64, "vocabulary")?;
+    }
+
+    // Tensors, whose data must end exactly at the end of the file
+    let mut n_tensors = 0;
+    while reader.position < file_size {
+        let n_dims = reader.i32("tensor")?;
+        let name_len = reader.i32("tensor")?;
+        let ttype = reader.i32("tensor")?;
+        if !(1..=MAX_TENSOR_DIMS).contains(&n_dims) || !(1..=MAX_TENSOR_NAME_BYTES).contains(&name_len) {
+            bail!("{} has a corrupted tensor table at tensor {}", name, n_tensors)
+        }
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            let ne = reader.i32("tensor")?;
+            if ne <= 0 {
+                bail!("{} has a corrupted tensor table at tensor {}", name, n_tensors)
+            }
+            elements *= ne as u64;
+        }
+        reader.skip(name_len as u64, "tensor")?;
+        let (type_size, block_size) =
+            type_size(ttype).with_context(|| format!("{} has tensor {} of unknown type {}", name, n_tensors, ttype))?;
+        let blocks = elements / block_size;
+        if blocks * block_size != elements {
+            bail!("{} has tensor {} that doesn't fit its type", name, n_tensors)
+        }
+        reader.skip(blocks * type_size, "tensor data")?;
+        n_tensors += 1;
+    }
+    if n_tensors == 0 {
+        bail!("{} has no weights, the download may have been cut", name)
+    }
+
+    Ok(ModelInfo {
+        model_type: model_type(n_audio_layer).to_string(),
+        // The english only models have one token less
+        multilingual: n_vocab >= 51865,
+        ftype: ftype_name.to_string(),
+        n_vocab,
+        n_audio_ctx,
+        n_audio_state,
+        n_audio_head,
+        n_audio_layer,
+        n_text_ctx,
+        n_text_state,
+        n_text_head,
+        n_text_layer,
+        n_mels,
+        n_tokens,
+        n_tensors,
+        file_size,
+    })
+}
+
+struct ModelReader {
+    inner: BufReader<File>,
+    position: u64,
+    file_size: u64,
+}
+
+impl ModelReader {
+    fn read_exact(&mut self, buf: &mut [u8], what: &str) -> Result<()> {
+        self.inner
+            .read_exact(buf)
+            .with_context(|| format!("model file is truncated, it ends in the {}. Download it again.", what))?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    fn i32(&mut self, what: &str) -> Result<i32> {
+        let mut buf = [0u8; 4];
+        self.read_exact(&mut buf, what)?;
+        Ok(i32::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self, what: &str) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        self.read_exact(&mut buf, what)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    /// Seeking doesn't fail past the end, so the length is checked against the file size.
+    /// Short skips such as tokens stay in the read buffer.
+    fn skip(&mut self, len: u64, what: &str) -> Result<()> {
+        let end = self.position.saturating_add(len);
+        if end > self.file_size {
+            bail!(
+                "model file is truncated, it ends in the {} ({} bytes missing). Download it again.",
+                what,
+                end - self.file_size
+            )
+        }
+        self.inner.seek_relative(len as i64)?;
+        self.position = end;
+        Ok(())
+    }
+}
+
+/// Name the format of a file that isn't a ggml model, for files picked by mistake
+fn describe_format(magic: &[u8; 4]) -> String {
+    match magic {
+        GGUF_MAGIC => "it's a GGUF file, such as a language model".to_string(),
+        [b'P', b'K', 3, 4] => "it's a zip file, such as a PyTorch checkpoint".to_string(),
+        // Protobuf starting with field 1 (ir_version) as a varint
+        [0x08, ..] => "it's an ONNX model, such as a diarization model".to_string(),
+        [b'<', ..] => "it's a web page, the download link may be wrong".to_string(),
+        _ => format!("unknown format (starts with {:02x?})", magic),
+    }
+}
+
+fn model_type(n_audio_layer: i32) -> &'static str {
+    match n_audio_layer {
+        4 => "tiny",
+        6 => "base",
+        12 => "small",
+        24 => "medium",
+        32 => "large",
+        _ => "unknown",
+    }
+}
+
+/// ggml file types
+fn ftype_name(ftype: i32) -> Option<&'static str> {
+    Some(match ftype {
+        0 => "f32",
+        1 => "f16",
+        2 => "q4_0",
+        3 => "q4_1",
+        7 => "q8_0",
+        8 => "q5_0",
+        9 => "q5_1",
+        10 => "q2_k",
+        11 => "q3_k",
+        12 => "q4_k",
+        13 => "q5_k",
+        14 => "q6_k",
+        _ => return None,
+    })
+}
+
+/// Bytes per block and elements per block of ggml tensor types
+fn type_size(ttype: i32) -> Option<(u64, u64)> {
+    Some(match ttype {
+        0 => (4, 1),
+        1 => (2, 1),
+        2 => (18, 32),
+        3 => (20, 32),
+        6 => (22, 32),
+        7 => (24, 32),
+        8 => (34, 32),
+        10 => (84, 256),
+        11 => (110, 256),
+        12 => (144, 256),
+        13 => (176, 256),
+        14 => (210, 256),
+        30 => (2, 1),
+        _ => return None,
+    })
+}
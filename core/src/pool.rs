@@ -0,0 +1,209 @@
+//! Whisper states shared by concurrent transcriptions of one loaded model.
+//! The model weights are loaded once, every running job gets its own state (decoder and KV cache),
+//! and jobs over the concurrency limit wait for a state to be given back.
+
+use eyre::{bail, eyre, Result};
+use serde::Serialize;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+use whisper_rs::{WhisperContext, WhisperState};
+
+/// How often a waiting job checks whether it was aborted
+const ABORT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct PoolStatus {
+    pub max_jobs: usize,
+    pub running: usize,
+    pub waiting: usize,
+}
+
+struct Slots {
+    max_jobs: usize,
+    running: usize,
+    waiting: usize,
+    /// States given back by finished jobs, reused so their buffers aren't allocated again
+    idle: Vec<WhisperState>,
+    /// When the last job finished, or when the pool was created
+    idle_since: Instant,
+}
+
+pub struct StatePool {
+    ctx: WhisperContext,
+    slots: Mutex<Slots>,
+    released: Condvar,
+}
+
+impl StatePool {
+    pub fn new(ctx: WhisperContext, max_jobs: usize) -> Self {
+        Self {
+            ctx,
+            slots: Mutex::new(Slots {
+                max_jobs: max_jobs.max(1),
+                running: 0,
+                waiting: 0,
+                idle: Vec::new(),
+                idle_since: Instant::now(),
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn context(&self) -> &WhisperContext {
+        &self.ctx
+    }
+
+    /// Change the concurrency limit, running jobs above a lower limit finish normally
+    pub fn set_max_jobs(&self, max_jobs: usize) {
+        if let Ok(mut slots) = self.slots.lock() {
+            let max_jobs = max_jobs.max(1);
+            slots.max_jobs = max_jobs;
+            slots.idle.truncate(max_jobs);
+        }
+        self.released.notify_all();
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        PoolStatus {
+            max_jobs: slots.max_jobs,
+            running: slots.running,
+            waiting: slots.waiting,
+        }
+    }
+
+    /// How long no job has been running or waiting, `None` while the pool is in use
+    pub fn idle_for(&self) -> Option<Duration> {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        if slots.running > 0 || slots.waiting > 0 {
+            return None;
+        }
+        Some(slots.idle_since.elapsed())
+    }
+
+    /// States kept for reuse, each holds its own buffers on top of the model weights
+    pub fn idle_states(&self) -> usize {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner()).idle.len()
+    }
+
+    /// Threads each job gets so that jobs running together share the cores instead of oversubscribing them
+    pub fn threads_per_job(&self) -> i32 {
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let max_jobs = self.status().max_jobs;
+        (cores / max_jobs).max(1) as i32
+    }
+
+    /// Take a state, blocking while the concurrency limit is reached.
+    /// `on_wait` is called once with the number of jobs ahead when the job has to wait,
+    /// and the job leaves the queue with an error once `abort` returns true.
+    pub fn acquire(self: &Arc<Self>, on_wait: impl FnOnce(usize), abort: impl Fn() -> bool) -> Result<PooledState> {
+        let mut slots = self.slots.lock().map_err(|e| eyre!("{:?}", e))?;
+        if slots.running >= slots.max_jobs {
+            let ahead = slots.waiting + slots.running + 1 - slots.max_jobs;
+            tracing::debug!("transcription waiting for a free state, {} jobs ahead", ahead);
+            on_wait(ahead);
+            slots.waiting += 1;
+            while slots.running >= slots.max_jobs {
+                if abort() {
+                    slots.waiting -= 1;
+                    bail!("transcription aborted while waiting for the model")
+                }
+                slots = self
+                    .released
+                    .wait_timeout(slots, ABORT_CHECK_INTERVAL)
+                    .map_err(|e| eyre!("{:?}", e))?
+                    .0;
+            }
+            slots.waiting -= 1;
+        }
+        slots.running += 1;
+        let idle = slots.idle.pop();
+        drop(slots);
+        self.lend(idle)
+    }
+
+    /// Take a state only if a slot is free right away, for work that can run with fewer states
+    pub fn try_acquire(self: &Arc<Self>) -> Option<PooledState> {
+        let mut slots = self.slots.lock().ok()?;
+        // Jobs already waiting go first
+        if slots.running >= slots.max_jobs || slots.waiting > 0 {
+            return None;
+        }
+        slots.running += 1;
+        let idle = slots.idle.pop();
+        drop(slots);
+        self.lend(idle)
+            .map_err(|error| tracing::warn!("no extra state: {:?}", error))
+            .ok()
+    }
+
+    /// Wrap a state for a slot already counted as running
+    fn lend(self: &Arc<Self>, idle: Option<WhisperState>) -> Result<PooledState> {
+        let state = match idle {
+            Some(state) => state,
+            None => match self.ctx.create_state() {
+                Ok(state) => state,
+                Err(error) => {
+                    self.release(None);
+                    bail!("failed to create state: {:?}", error)
+                }
+            },
+        };
+        Ok(PooledState {
+            pool: self.clone(),
+            state: Some(state),
+        })
+    }
+
+    fn release(&self, state: Option<WhisperState>) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.running -= 1;
+        slots.idle_since = Instant::now();
+        if let Some(state) = state {
+            if slots.idle.len() < slots.max_jobs {
+                slots.idle.push(state);
+            }
+        }
+        drop(slots);
+        self.released.notify_one();
+    }
+}
+
+/// State lent to one job, given back to the pool when dropped
+pub struct PooledState {
+    pool: Arc<StatePool>,
+    state: Option<WhisperState>,
+}
+
+impl PooledState {
+    /// Free the slot without giving the state back, for states left unusable by a crash
+    pub fn discard(mut self) {
+        self.state = None;
+    }
+
+    pub fn context(&self) -> &WhisperContext {
+        self.pool.context()
+    }
+}
+
+impl Deref for PooledState {
+    type Target = WhisperState;
+
+    fn deref(&self) -> &WhisperState {
+        self.state.as_ref().expect("state is only taken on drop")
+    }
+}
+
+impl DerefMut for PooledState {
+    fn deref_mut(&mut self) -> &mut WhisperState {
+        self.state.as_mut().expect("state is only taken on drop")
+    }
+}
+
+impl Drop for PooledState {
+    fn drop(&mut self) {
+        self.pool.release(self.state.take());
+    }
+}
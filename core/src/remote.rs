@@ -0,0 +1,271 @@
+//! Transcription by a server with the OpenAI audio API (`POST {url}/audio/transcriptions`),
+//! such as OpenAI itself, faster-whisper-server or LocalAI.
+//! Audio is uploaded as 16kHz WAV, in chunks cut at silences when it's over the server's size limit.
+
+use crate::audio;
+use crate::backend::TranscriptionBackend;
+use crate::chunk::{self, Stitcher};
+use crate::config::{RemoteOptions, TranscribeOptions};
+use crate::diarize;
+use crate::transcribe::{create_normalized_audio, select_range, TranscribeRequest};
+use crate::transcript::{Segment, Transcript};
+use crate::vocabulary;
+use eyre::{bail, Context, Result};
+use reqwest::blocking::{multipart, Client};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// OpenAI's upload limit
+pub const DEFAULT_MAX_UPLOAD_MB: u32 = 25;
+/// Room left in an upload for the WAV header and the other form fields
+const UPLOAD_OVERHEAD_BYTES: usize = 64 * 1024;
+const BYTES_PER_SECOND: usize = 16000 * 2;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Servers without a GPU take a while on long chunks
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Deserialize)]
+struct VerboseTranscription {
+    text: String,
+    /// Missing when the server ignores `verbose_json`
+    #[serde(default)]
+    segments: Vec<RemoteSegment>,
+}
+
+/// Timestamps in seconds
+#[derive(Deserialize)]
+struct RemoteSegment {
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+pub struct RemoteBackend {
+    options: RemoteOptions,
+    client: Client,
+}
+
+impl RemoteBackend {
+    /// The client blocks, so the backend is created, used and dropped outside of async tasks (e.g. in `spawn_blocking`)
+    pub fn new(options: RemoteOptions) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("failed to create http client")?;
+        Ok(Self { options, client })
+    }
+
+    /// Longest chunk in seconds that fits in one upload
+    fn chunk_secs(&self, options: &TranscribeOptions) -> Result<u32> {
+        let max_upload_mb = self.options.max_upload_mb.unwrap_or(DEFAULT_MAX_UPLOAD_MB);
+        let max_bytes = max_upload_mb as usize * 1024 * 1024;
+        let max_secs = (max_bytes.saturating_sub(UPLOAD_OVERHEAD_BYTES) / BYTES_PER_SECOND) as u32;
+        // The last chunk runs up to a whole overlap past the chunk length
+        let overlap = options.chunk_overlap_secs.unwrap_or(chunk::DEFAULT_OVERLAP_SECS);
+        let fits = max_secs.saturating_sub(overlap);
+        if fits <= overlap * 2 {
+            bail!(
+                "upload limit of {}MB is too small for chunks overlapping by {}s",
+                max_upload_mb,
+                overlap
+            )
+        }
+        Ok(options.chunk_secs.map_or(fits, |chunk_secs| chunk_secs.min(fits)))
+    }
+
+    /// Send one piece of audio, returns its segments with timestamps relative to it
+    pub(crate) fn upload(&self, samples: &[i16], options: &TranscribeOptions, prompt: Option<&str>) -> Result<Vec<Segment>> {
+        let translate = options.translate.unwrap_or(false);
+        let endpoint = if translate { "translations" } else { "transcriptions" };
+        let url = format!("{}/audio/{}", self.options.url.trim_end_matches('/'), endpoint);
+
+        let file = multipart::Part::bytes(wav_bytes(samples)?)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+        let mut form = multipart::Form::new()
+            .part("file", file)
+            .text("model", self.options.model.clone())
+            .text("response_format", "verbose_json")
+            .text("timestamp_granularities[]", "segment");
+        // Translations take no language, the source is detected
+        if let Some(lang) = options.lang.as_deref().filter(|lang| *lang != "auto" && !translate) {
+            form = form.text("language", lang.to_string());
+        }
+        if let Some(prompt) = prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        if let Some(temperature) = options.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+        let mut request = self.client.post(&url).multipart(form);
+        if let Some(api_key) = &self.options.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        tracing::debug!("uploading {}s of audio to {}", samples.len() / 16000, url);
+        let response = request.send().with_context(|| format!("failed to reach {}", url))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .with_context(|| format!("failed to read response of {}", url))?;
+        if !status.is_success() {
+            bail!("{} responded with {}: {}", url, status, error_message(&body))
+        }
+        let transcription: VerboseTranscription =
+            serde_json::from_str(&body).with_context(|| format!("unexpected response from {}", url))?;
+
+        let segments = transcription
+            .segments
+            .into_iter()
+            .map(|segment| Segment {
+                start: (segment.start * 100.0).round() as i64,
+                stop: (segment.end * 100.0).round() as i64,
+                text: segment.text,
+                speaker: None,
+                speaker_confidence: None,
+                suspect: None,
+            })
+            .collect::<Vec<_>>();
+        if segments.is_empty() && !transcription.text.trim().is_empty() {
+            // Text only, it spans the whole piece
+            return Ok(vec![Segment {
+                start: 0,
+                stop: (samples.len() / 160) as i64,
+                text: transcription.text,
+                speaker: None,
+                speaker_confidence: None,
+                suspect: None,
+            }]);
+        }
+        Ok(segments)
+    }
+}
+
+/// Chunks are uploaded one after the other. The abort handler is checked between uploads.
+/// Results aren't cached, what the server runs may change.
+impl TranscriptionBackend for RemoteBackend {
+    fn transcribe(&self, request: TranscribeRequest) -> Result<Transcript> {
+        let TranscribeRequest {
+            options,
+            progress_callback,
+            new_segment_callback,
+            abort_callback,
+            diarize_options,
+            ..
+        } = request;
+        tracing::debug!("remote transcribe on {:?} with {:?}", self.options, options);
+        options.validate()?;
+
+        if !PathBuf::from(options.path.clone()).exists() {
+            bail!("audio file doesn't exist")
+        }
+        let out_path = create_normalized_audio(options.path.clone().into())?;
+        let samples = audio::parse_wav_file(&out_path);
+        std::fs::remove_file(out_path)?;
+        let samples = select_range(samples?, options)?;
+        let offset = options.offset_ms.unwrap_or(0) as i64 / 10;
+
+        let st = Instant::now();
+        let chunk_options = TranscribeOptions {
+            chunk_secs: Some(self.chunk_secs(options)?),
+            ..options.clone()
+        };
+        let chunks = chunk::plan_chunks(&samples, &chunk_options);
+        let cuts = chunk::cuts(&chunks, offset);
+        let starts: Vec<i64> = std::iter::once(offset).chain(cuts.iter().copied()).collect();
+        let mut stitcher = Stitcher::new(cuts);
+
+        // Speakers are found locally, then given to the server's sentences by time overlap
+        let speaker_turns = match diarize_options {
+            Some(ref diarize_options) => {
+                let mut turns = diarize::speaker_turns(&samples, diarize_options, abort_callback.as_deref())?;
+                for turn in &mut turns {
+                    turn.start += offset;
+                    turn.stop += offset;
+                }
+                Some(turns)
+            }
+            None => None,
+        };
+
+        // No tokenizer for the server's model, words are counted as two tokens to stay in budget
+        let vocabulary = options.vocabulary.as_deref().unwrap_or_default();
+        let prompt = vocabulary::build_prompt(
+            options.init_prompt.as_deref(),
+            vocabulary,
+            vocabulary::PROMPT_TOKEN_BUDGET,
+            |text| text.split_whitespace().count() * 2,
+        );
+
+        let mut resume_from = None;
+        for (index, range) in chunks.iter().enumerate() {
+            if abort_callback.as_ref().is_some_and(|abort| abort()) {
+                resume_from = Some(starts[index]);
+                break;
+            }
+            let chunk_offset = offset + (range.start / 160) as i64;
+            let segments = self
+                .upload(&samples[range.clone()], options, prompt.as_deref())
+                .with_context(|| format!("failed to transcribe chunk {} of {}", index + 1, chunks.len()))?
+                .into_iter()
+                .map(|mut segment| {
+                    segment.start += chunk_offset;
+                    segment.stop += chunk_offset;
+                    segment
+                })
+                .collect();
+            for segment in stitcher.push(index, segments) {
+                if let (Some(new_segment_callback), None) = (&new_segment_callback, &speaker_turns) {
+                    new_segment_callback(segment);
+                }
+            }
+            if let Some(ref progress_callback) = progress_callback {
+                progress_callback(((index + 1) * 100 / chunks.len()) as i32);
+            }
+        }
+
+        let mut segments = stitcher.finish();
+        if let (Some(turns), Some(diarize_options)) = (speaker_turns, diarize_options) {
+            segments = diarize::assign_speakers(&segments, &turns, diarize_options.speaker_assignment);
+            if let Some(ref new_segment_callback) = new_segment_callback {
+                for segment in &segments {
+                    new_segment_callback(segment.clone());
+                }
+            }
+        }
+        Ok(Transcript {
+            segments,
+            processing_time_sec: Instant::now().duration_since(st).as_secs(),
+            resume_from,
+        })
+    }
+}
+
+/// 16kHz mono 16 bit WAV, what every server accepts
+fn wav_bytes(samples: &[i16]) -> Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::with_capacity(samples.len() * 2 + 44));
+    let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+    for sample in samples {
+        writer.write_sample(*sample)?;
+    }
+    writer.finalize()?;
+    Ok(cursor.into_inner())
+}
+
+/// The message of OpenAI style error bodies, or the start of the body
+pub fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|body| body["error"]["message"].as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| body.chars().take(200).collect())
+}
@@ -25,12 +25,645 @@ fn test_transcribe() {
         temperature: None,
         translate: None,
         word_timestamps: None,
+        vad: None,
+        hallucination_filter: None,
+        temperature_fallback: None,
+        ..Default::default()
     };
     let start = Instant::now();
-    let result = crate::transcribe::transcribe(&ctx, options, None, None, None, None);
+    let result = crate::transcribe::TranscribeRequest::new(options).run(&ctx);
     println!("{:?}", result);
     println!(
         "Elapsed time: {:.2} seconds",
         Instant::now().duration_since(start).as_secs_f64()
     );
 }
+
+#[test]
+#[serial]
+#[traced_test]
+fn test_state_pool() {
+    use crate::pool::StatePool;
+    use std::sync::Arc;
+
+    let ctx = create_context(&PathBuf::from("../ggml-tiny.bin"), None).unwrap();
+    let pool = Arc::new(StatePool::new(ctx, 2));
+    let options = TranscribeOptions {
+        path: "../samples/short.wav".into(),
+        lang: Some("en".into()),
+        ..Default::default()
+    };
+    let handles: Vec<_> = (0..3)
+        .map(|_| {
+            let pool = pool.clone();
+            let options = options.clone();
+            std::thread::spawn(move || {
+                let mut state = pool.acquire(|_| {}, || false).unwrap();
+                crate::transcribe::TranscribeRequest::new(&options)
+                    .state(&mut state)
+                    .run(pool.context())
+                    .unwrap()
+            })
+        })
+        .collect();
+    let transcripts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert!(transcripts.iter().all(|t| t.segments.len() == transcripts[0].segments.len()));
+    let status = pool.status();
+    assert_eq!((status.running, status.waiting), (0, 0));
+
+    // An aborted job leaves the queue without taking the slot
+    pool.set_max_jobs(1);
+    let held = pool.acquire(|_| {}, || false).unwrap();
+    assert!(pool.acquire(|_| {}, || true).is_err());
+    assert_eq!((pool.status().running, pool.status().waiting), (1, 0));
+    drop(held);
+}
+
+#[test]
+fn test_cluster_speakers() {
+    use crate::diarize::{
+        cluster_embeddings, identify_speakers, DiarizePipeline, SpeakerAssignment, SpeakerProfile, DEFAULT_CLUSTER_THRESHOLD,
+    };
+    use crate::transcribe::DiarizeOptions;
+
+    // Two voices around distinct directions, with some noise
+    let embeddings = vec![
+        vec![1.0, 0.1, 0.0],
+        vec![0.1, 1.0, 0.0],
+        vec![0.9, 0.0, 0.1],
+        vec![0.0, 0.9, 0.2],
+        vec![1.0, 0.0, 0.2],
+    ];
+    assert_eq!(cluster_embeddings(&embeddings, 0.5, None, None), vec![0, 1, 0, 1, 0]);
+    // Bounds win over the threshold
+    assert_eq!(cluster_embeddings(&embeddings, 0.5, None, Some(1)), vec![0; 5]);
+    assert_eq!(cluster_embeddings(&embeddings, 0.5, Some(3), None).iter().max(), Some(&2));
+
+    let options = DiarizeOptions {
+        segment_model_path: String::new(),
+        embedding_model_path: String::new(),
+        threshold: DEFAULT_CLUSTER_THRESHOLD,
+        min_speakers: None,
+        max_speakers: None,
+        speaker_profiles: vec![SpeakerProfile {
+            name: "Bob".into(),
+            embedding: vec![0.0, 1.0, 0.1],
+        }],
+        pipeline: DiarizePipeline::PerSegment,
+        speaker_assignment: SpeakerAssignment::Sentence,
+    };
+    assert!(options.validate().is_ok());
+    let inverted = DiarizeOptions {
+        min_speakers: Some(3),
+        max_speakers: Some(2),
+        ..options.clone()
+    };
+    assert!(inverted.validate().is_err());
+    let unset = DiarizeOptions {
+        threshold: 0.0,
+        ..options.clone()
+    };
+    assert!(unset.validate().is_err());
+
+    let speakers = identify_speakers(&embeddings, &options);
+    let names: Vec<_> = speakers.iter().map(|s| s.speaker.as_str()).collect();
+    assert_eq!(names, vec!["0", "Bob", "0", "Bob", "0"]);
+    assert!(speakers.iter().all(|s| s.confidence > 0.9));
+
+    // A profile close to both voices only names the closest one
+    let shared = DiarizeOptions {
+        speaker_profiles: vec![SpeakerProfile {
+            name: "Bob".into(),
+            embedding: vec![0.6, 0.8, 0.1],
+        }],
+        ..options.clone()
+    };
+    let speakers = identify_speakers(&embeddings, &shared);
+    let names: Vec<_> = speakers.iter().map(|s| s.speaker.as_str()).collect();
+    assert_eq!(names, vec!["0", "Bob", "0", "Bob", "0"]);
+
+    // A loose clustering threshold doesn't name a voice after a distant profile, and confidences stay real similarities
+    let options = DiarizeOptions {
+        threshold: 0.05,
+        speaker_profiles: vec![SpeakerProfile {
+            name: "Carol".into(),
+            embedding: vec![0.0, 0.0, 1.0],
+        }],
+        ..options
+    };
+    let speakers = identify_speakers(&embeddings, &options);
+    assert!(speakers.iter().all(|s| s.speaker == "0"), "{:?}", speakers);
+    assert!(speakers.iter().all(|s| s.confidence < 0.9), "{:?}", speakers);
+}
+
+#[test]
+fn test_assign_speakers_by_overlap() {
+    use crate::diarize::{assign_speakers, SpeakerAssignment, SpeakerTurn};
+    use crate::transcript::Segment;
+
+    let word = |start, stop, text: &str| Segment {
+        start,
+        stop,
+        text: text.into(),
+        speaker: None,
+        speaker_confidence: None,
+        suspect: None,
+    };
+    let words = vec![
+        word(0, 50, " Hello"),
+        word(50, 100, " there."),
+        word(110, 150, " Hi,"),
+        word(150, 190, " how"),
+        word(190, 230, " are"),
+        word(230, 300, " you?"),
+    ];
+    let turns = vec![
+        SpeakerTurn {
+            start: 0,
+            stop: 105,
+            speaker: "Alice".into(),
+            confidence: 0.9,
+        },
+        SpeakerTurn {
+            start: 105,
+            stop: 160,
+            speaker: "0".into(),
+            confidence: 0.7,
+        },
+        SpeakerTurn {
+            start: 160,
+            stop: 300,
+            speaker: "Alice".into(),
+            confidence: 0.8,
+        },
+    ];
+
+    // By sentence, the short interjection overlapping less of the question is absorbed
+    let segments = assign_speakers(&words, &turns, SpeakerAssignment::Sentence);
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].text, " Hello there. Hi, how are you?");
+    assert_eq!((segments[0].start, segments[0].stop), (0, 300));
+
+    // By word, each word follows the turn it overlaps
+    let segments = assign_speakers(&words, &turns, SpeakerAssignment::Word);
+    let speakers: Vec<_> = segments.iter().map(|s| s.speaker.clone().unwrap()).collect();
+    assert_eq!(speakers, vec!["Alice", "0", "Alice"]);
+    assert_eq!(segments[1].text, " Hi,");
+    assert_eq!(segments[2].text, " how are you?");
+
+    // Words in a gap between turns go to the closest turn
+    let gap_turns = vec![
+        SpeakerTurn {
+            start: 0,
+            stop: 40,
+            speaker: "Alice".into(),
+            confidence: 0.9,
+        },
+        SpeakerTurn {
+            start: 240,
+            stop: 300,
+            speaker: "0".into(),
+            confidence: 0.7,
+        },
+    ];
+    let segments = assign_speakers(&words, &gap_turns, SpeakerAssignment::Word);
+    let speakers: Vec<_> = segments.iter().map(|s| s.speaker.clone().unwrap()).collect();
+    assert_eq!(speakers, vec!["Alice", "0"]);
+    assert_eq!(segments[0].text, " Hello there. Hi,");
+    assert_eq!(segments[1].text, " how are you?");
+
+    // Without any turn, consecutive units still merge into one segment
+    let segments = assign_speakers(&words, &[], SpeakerAssignment::Sentence);
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].speaker.as_deref(), Some("?"));
+    assert_eq!(segments[0].speaker_confidence, None);
+}
+
+#[test]
+fn test_vad_skips_silence() {
+    use crate::vad::{detect_speech, remove_silence};
+
+    // 1s of faint noise, 1s tone, 2s noise, 1s tone, 1s noise
+    let noise = |len: usize| (0..len).map(|i| ((i * 7919) % 21) as i16 - 10).collect::<Vec<_>>();
+    let tone = |len: usize| {
+        (0..len)
+            .map(|i| ((i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 16000.0).sin() * 8000.0) as i16)
+            .collect::<Vec<_>>()
+    };
+    let samples = [noise(16000), tone(16000), noise(32000), tone(16000), noise(16000)].concat();
+
+    let regions = detect_speech(&samples);
+    assert_eq!(regions.len(), 2);
+    // Regions cover the tones plus some padding
+    assert!(regions[0].start <= 16000 && regions[0].start >= 16000 - 4800);
+    assert!(regions[1].end >= 80000 && regions[1].end <= 80000 + 4800);
+
+    let (compact, timeline) = remove_silence(&samples, &regions);
+    assert!(compact.len() < samples.len() / 2 + 16000);
+    // Start of the shortened audio is the start of the first region, the end is the end of the last one
+    assert_eq!(timeline.to_original(0), (regions[0].start / 160) as i64);
+    let compact_end = (compact.len() / 160) as i64;
+    assert!((timeline.to_original(compact_end) - (regions[1].end / 160) as i64).abs() <= 1);
+    // The second tone starts at 4s in the original audio
+    let second = (compact.len() - (regions[1].end - regions[1].start)) / 160;
+    assert_eq!(timeline.to_original(second as i64), (regions[1].start / 160) as i64);
+}
+
+#[test]
+fn test_hallucination_heuristics() {
+    use crate::hallucination::{compression_ratio, find_repeats, COMPRESSION_RATIO_THRESHOLD};
+    use crate::transcript::Segment;
+
+    assert!(compression_ratio(" So the meeting moved to Thursday, and we still need the budget numbers.") < 1.5);
+    assert!(compression_ratio(&" Thank you.".repeat(12)) > COMPRESSION_RATIO_THRESHOLD);
+
+    let segment = |text: &str| Segment {
+        start: 0,
+        stop: 100,
+        text: text.into(),
+        speaker: None,
+        speaker_confidence: None,
+        suspect: None,
+    };
+    let segments: Vec<_> = [
+        " Hello there.",
+        " See you next week.",
+        " see you next week",
+        " See you next week.",
+        " Yes.",
+        " Yes.",
+        " Yes.",
+    ]
+    .iter()
+    .map(|text| segment(text))
+    .collect();
+    // The third identical sentence is a loop, single words are left alone
+    assert_eq!(find_repeats(&segments), vec![false, false, false, true, false, false, false]);
+}
+
+#[test]
+fn test_vocabulary_prompt_and_dictionary() {
+    use crate::vocabulary::{build_prompt, Dictionary, Replacement};
+
+    let words = |text: &str| text.split_whitespace().count();
+    let terms: Vec<String> = ["Samwise", "Kubernetes", "Samwise", "Priya Raman", "Ollama"]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+    let prompt = build_prompt(Some("Weekly sync."), &terms, 100, words);
+    assert_eq!(
+        prompt.as_deref(),
+        Some("Samwise, Kubernetes, Priya Raman, Ollama. Weekly sync.")
+    );
+    // Terms stop once the budget is reached, the initial prompt stays
+    let prompt = build_prompt(Some("Weekly sync."), &terms, 5, words);
+    assert_eq!(prompt.as_deref(), Some("Samwise, Kubernetes. Weekly sync."));
+    assert_eq!(build_prompt(None, &[], 5, words), None);
+
+    let rule = |find: &str, replace: &str, regex: bool, case_sensitive: bool| Replacement {
+        find: find.into(),
+        replace: replace.into(),
+        regex,
+        case_sensitive,
+    };
+    let dictionary = Dictionary::new(&[
+        rule("sam wise", "Samwise", false, false),
+        rule("AI", "A.I.", false, true),
+        rule(r"\bq(\d)\b", "Q$1", true, false),
+    ])
+    .unwrap();
+    assert_eq!(
+        dictionary.apply(" Sam Wise said the ai numbers for q3 are in, AI too."),
+        " Samwise said the ai numbers for Q3 are in, A.I. too."
+    );
+    assert!(Dictionary::new(&[rule("(unclosed", "", true, false)]).is_err());
+}
+
+#[test]
+fn test_splice_segments() {
+    use crate::transcript::{splice_segments, Segment};
+
+    let segment = |start, stop, text: &str| Segment {
+        start,
+        stop,
+        text: text.into(),
+        speaker: None,
+        speaker_confidence: None,
+        suspect: None,
+    };
+    let segments = vec![
+        segment(0, 500, " one"),
+        segment(500, 1000, " two"),
+        segment(1000, 1600, " three"),
+        segment(1600, 2000, " four"),
+    ];
+    // Range from 6s to 15s, " two" is mostly inside it and " three" is too
+    let replacement = vec![segment(600, 1100, " two again"), segment(1100, 1500, " three again")];
+    let spliced = splice_segments(&segments, replacement, 600, 1500);
+    let texts: Vec<_> = spliced.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, vec![" one", " two again", " three again", " four"]);
+}
+
+#[test]
+fn test_partial_transcript_resume() {
+    use crate::transcript::{splice_segments, Segment, Transcript};
+
+    // Transcripts stored before partial ones existed are complete
+    let stored: Transcript = serde_json::from_str(r#"{"processing_time_sec": 3, "segments": []}"#).unwrap();
+    assert!(!stored.is_partial());
+
+    let segment = |start, stop, text: &str| Segment {
+        start,
+        stop,
+        text: text.into(),
+        speaker: None,
+        speaker_confidence: None,
+        suspect: None,
+    };
+    let partial = Transcript {
+        processing_time_sec: 1,
+        segments: vec![segment(0, 500, " one"), segment(500, 900, " two")],
+        resume_from: Some(900),
+    };
+    let json = partial.as_json().unwrap();
+    let partial: Transcript = serde_json::from_str(&json).unwrap();
+    assert_eq!(partial.resume_from, Some(900));
+
+    // Resuming transcribes from the resume point to the end and appends it
+    let rest = vec![segment(900, 1300, " three"), segment(1300, 1800, " four")];
+    let resumed = splice_segments(&partial.segments, rest, partial.resume_from.unwrap(), i64::MAX);
+    let texts: Vec<_> = resumed.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, vec![" one", " two", " three", " four"]);
+}
+
+#[test]
+fn test_remote_upload() {
+    use crate::config::{BackendOptions, RemoteOptions};
+    use crate::remote::RemoteBackend;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    // Answers one request the way OpenAI does and returns it
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v1", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        // The multipart body ends with the closing boundary
+        while !String::from_utf8_lossy(&request).trim_end().ends_with("--") {
+            let read = stream.read(&mut buffer).unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let body = r#"{"text": " Hello there. General Kenobi.", "segments": [
+            {"start": 0.0, "end": 1.5, "text": " Hello there."},
+            {"start": 1.5, "end": 3.2, "text": " General Kenobi."}
+        ]}"#;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        String::from_utf8_lossy(&request).to_lowercase()
+    });
+
+    let remote = RemoteOptions {
+        url,
+        model: "whisper-1".into(),
+        api_key: Some("secret".into()),
+        max_upload_mb: None,
+    };
+    let options = TranscribeOptions {
+        lang: Some("en".into()),
+        backend: BackendOptions::Remote(remote.clone()),
+        ..Default::default()
+    };
+    // The key stays out of logs
+    assert!(!format!("{:?}", options).contains("secret"));
+    assert!(!format!("{:?}", remote).contains("secret"));
+
+    let backend = RemoteBackend::new(remote).unwrap();
+    let segments = backend.upload(&vec![0i16; 16000 * 4], &options, Some("Kenobi")).unwrap();
+    let request = server.join().unwrap();
+    assert!(request.starts_with("post /v1/audio/transcriptions"));
+    assert!(request.contains("authorization: bearer secret"));
+    assert!(request.contains("name=\"language\"\r\n\r\nen\r\n"));
+    assert!(request.contains("name=\"prompt\"\r\n\r\nkenobi\r\n"));
+    assert_eq!(segments.len(), 2);
+    assert_eq!((segments[1].start, segments[1].stop), (150, 320));
+    assert_eq!(segments[1].text, " General Kenobi.");
+}
+
+#[test]
+fn test_transcript_cache() {
+    use crate::cache::TranscriptCache;
+    use crate::transcript::{Segment, Transcript};
+
+    let dir = tempfile::tempdir().unwrap();
+    let model_path = dir.path().join("ggml-test.bin");
+    std::fs::write(&model_path, b"not really a model").unwrap();
+    let cache = TranscriptCache::open(dir.path().join("transcripts"), &model_path).unwrap();
+
+    let samples = vec![0i16, 100, -100, 50];
+    let options = TranscribeOptions {
+        path: "a.wav".into(),
+        lang: Some("en".into()),
+        ..Default::default()
+    };
+    let key = cache.key(&samples, &options, None).unwrap();
+    // The file path doesn't matter, the audio and options do
+    let moved = TranscribeOptions {
+        path: "b.wav".into(),
+        lang: Some("en".into()),
+        ..Default::default()
+    };
+    assert_eq!(key, cache.key(&samples, &moved, None).unwrap());
+    let translated = TranscribeOptions {
+        translate: Some(true),
+        ..moved
+    };
+    assert_ne!(key, cache.key(&samples, &translated, None).unwrap());
+    assert_ne!(key, cache.key(&samples[1..], &options, None).unwrap());
+
+    let transcript = |text: &str| Transcript {
+        processing_time_sec: 1,
+        segments: vec![Segment {
+            start: 0,
+            stop: 100,
+            text: text.into(),
+            speaker: None,
+            speaker_confidence: None,
+            suspect: None,
+        }],
+        resume_from: None,
+    };
+    assert!(cache.get(&key).is_none());
+    cache.put(&key, &transcript(" Hello")).unwrap();
+    assert_eq!(cache.get(&key).unwrap().segments[0].text, " Hello");
+
+    // Over the limit the oldest entry goes first
+    let small = cache.clone().with_max_bytes(150);
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    small.put("second", &transcript(" World")).unwrap();
+    assert!(small.get(&key).is_none());
+    assert!(small.get("second").is_some());
+
+    TranscriptCache::clear(&dir.path().join("transcripts")).unwrap();
+    assert!(cache.get("second").is_none());
+}
+
+#[test]
+fn test_chunked_stitching() {
+    use crate::chunk::{plan_chunks, Stitcher};
+    use crate::transcript::Segment;
+
+    // 100s of noise with a silence around 55s, a little before the 60s target cut
+    let mut samples: Vec<i16> = (0..100 * 16000).map(|i| if i % 2 == 0 { 3000 } else { -3000 }).collect();
+    samples[54 * 16000..56 * 16000].fill(0);
+    let options = TranscribeOptions {
+        chunk_secs: Some(60),
+        chunk_overlap_secs: Some(4),
+        ..Default::default()
+    };
+    let chunks = plan_chunks(&samples, &options);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].start, 0);
+    assert_eq!(chunks[1].end, samples.len());
+    // The cut lands in the silence and the chunks overlap around it
+    let middle = (chunks[0].end + chunks[1].start) / 2;
+    assert!((54 * 16000..56 * 16000).contains(&middle));
+    assert_eq!(chunks[0].end - chunks[1].start, 4 * 16000);
+
+    let unchunked = plan_chunks(&samples, &Default::default());
+    assert_eq!(unchunked, vec![0..samples.len()]);
+
+    let segment = |start, stop, text: &str| Segment {
+        start,
+        stop,
+        text: text.into(),
+        speaker: None,
+        speaker_confidence: None,
+        suspect: None,
+    };
+    let mut stitcher = Stitcher::new(vec![5500]);
+    let kept = stitcher.push(
+        0,
+        vec![
+            segment(0, 5000, " one"),
+            segment(5000, 5590, " two"),
+            segment(5600, 5700, " three"),
+        ],
+    );
+    // " three" lies past the cut, it's the next chunk's
+    assert_eq!(kept.len(), 2);
+    // " two" was heard by both chunks, timed a little differently
+    stitcher.push(1, vec![segment(5100, 5950, " Two."), segment(5950, 6200, " three four")]);
+    let texts: Vec<_> = stitcher.finish().into_iter().map(|s| s.text).collect();
+    assert_eq!(texts, vec![" one", " two", " three four"]);
+}
+
+#[test]
+fn test_model_validation() {
+    // A tiny model: header, 80x1 mel filters, two tokens and one f32 tensor of 4 elements
+    let mut bytes = Vec::new();
+    bytes.extend(0x67676d6c_u32.to_le_bytes());
+    for value in [51865_i32, 1500, 384, 6, 4, 448, 384, 6, 4, 80, 1, 80, 1] {
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.extend([0u8; 80 * 4]);
+    bytes.extend(2_i32.to_le_bytes());
+    for token in ["a", "bc"] {
+        bytes.extend((token.len() as u32).to_le_bytes());
+        bytes.extend(token.as_bytes());
+    }
+    for value in [1_i32, 1, 0, 4] {
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.extend(b"w");
+    bytes.extend([0u8; 16]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ggml-tiny.bin");
+    std::fs::write(&path, &bytes).unwrap();
+    let info = crate::model::inspect(&path).unwrap();
+    assert_eq!(info.model_type, "tiny");
+    assert_eq!(info.ftype, "f16");
+    assert!(info.multilingual);
+    assert_eq!((info.n_tokens, info.n_tensors), (2, 1));
+
+    std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+    let error = crate::model::inspect(&path).unwrap_err().to_string();
+    assert!(error.contains("truncated"), "{}", error);
+
+    // Diarization models are ONNX protobufs
+    std::fs::write(&path, [0x08, 0x07, 0x12, 0x07, 0x70, 0x79]).unwrap();
+    let error = crate::model::inspect(&path).unwrap_err().to_string();
+    assert!(error.contains("ONNX"), "{}", error);
+}
+
+#[test]
+fn test_draft_replacement() {
+    use crate::backend::TranscriptionBackend;
+    use crate::draft::Replacement;
+    use crate::transcribe::TranscribeRequest;
+    use crate::transcript::{Segment, Transcript};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Gives its segments to the callback as if it transcribed them
+    struct Scripted(Vec<Segment>);
+    impl TranscriptionBackend for Scripted {
+        fn transcribe(&self, request: TranscribeRequest) -> eyre::Result<Transcript> {
+            if let Some(callback) = request.new_segment_callback {
+                self.0.iter().cloned().for_each(callback);
+            }
+            Ok(Transcript {
+                segments: self.0.clone(),
+                processing_time_sec: 0,
+                resume_from: None,
+            })
+        }
+    }
+
+    let segment = |start, stop, text: &str| Segment {
+        start,
+        stop,
+        text: text.into(),
+        speaker: None,
+        speaker_confidence: None,
+        suspect: None,
+    };
+    let draft = Scripted(vec![segment(0, 300, " a"), segment(300, 600, " b"), segment(600, 900, " c")]);
+    let accurate = Scripted(vec![segment(0, 350, " A"), segment(350, 880, " B")]);
+
+    let shown = Rc::new(RefCell::new(Vec::new()));
+    let options = TranscribeOptions::default();
+    let transcript = TranscribeRequest::new(&options)
+        .draft(&draft)
+        .on_segment({
+            let shown = shown.clone();
+            move |segment| shown.borrow_mut().push(segment)
+        })
+        .on_replace({
+            let shown = shown.clone();
+            move |replacement: Replacement| {
+                replacement.apply(&mut shown.borrow_mut());
+                let texts: String = shown.borrow().iter().map(|s| s.text.clone()).collect();
+                // The draft stays readable until the accurate segments reach it
+                if replacement.stop == 350 {
+                    assert_eq!(texts, " A b c");
+                }
+            }
+        })
+        .run(&accurate)
+        .unwrap();
+
+    let texts: Vec<_> = shown.borrow().iter().map(|s| s.text.clone()).collect();
+    assert_eq!(texts, vec![" A", " B"]);
+    assert_eq!(transcript.segments.len(), 2);
+}
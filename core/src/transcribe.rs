@@ -1,24 +1,36 @@
 use crate::audio;
+use crate::backend::TranscriptionBackend;
+use crate::cache::TranscriptCache;
+use crate::chunk;
 use crate::config::TranscribeOptions;
+use crate::diarize::{self, DiarizePipeline, SpeakerAssignment, SpeakerProfile};
+use crate::draft::{self, ReplaceCallback, Replacement};
+use crate::hallucination::{self, SegmentQuality};
+use crate::model;
+use crate::pool::StatePool;
 use crate::transcript::{Segment, Transcript};
-use eyre::{bail, eyre, Context, OptionExt, Result};
+use crate::vad;
+use crate::vocabulary;
+use eyre::{bail, Context, OptionExt, Result};
+use std::ffi::{c_int, c_void};
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Instant;
 pub use whisper_rs::SegmentCallbackData;
 pub use whisper_rs::WhisperContext;
+pub use whisper_rs::WhisperState;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContextParameters};
 
-type ProgressCallbackType = once_cell::sync::Lazy<Mutex<Option<Box<dyn Fn(i32) + Send + Sync>>>>;
-static PROGRESS_CALLBACK: ProgressCallbackType = once_cell::sync::Lazy::new(|| Mutex::new(None));
-
 pub fn create_context(model_path: &Path, gpu_device: Option<i32>) -> Result<WhisperContext> {
     whisper_rs::install_whisper_tracing_trampoline();
     tracing::debug!("open model...");
     if !model_path.exists() {
         bail!("whisper file doesn't exist")
     }
+    let info = model::inspect(model_path)?;
+    tracing::debug!("model info: {:?}", info);
     let mut ctx_params = WhisperContextParameters::default();
     if !env!("CUDA_VERSION").is_empty() || !env!("ROCM_VERSION").is_empty() {
         // Nvidia or AMD
@@ -57,8 +69,18 @@ pub fn create_normalized_audio(source: PathBuf) -> Result<PathBuf> {
     Ok(out_path)
 }
 
-fn setup_params(options: &TranscribeOptions) -> FullParams {
-    let mut params = FullParams::new(SamplingStrategy::default());
+pub(crate) fn setup_params<'a>(ctx: &WhisperContext, options: &'a TranscribeOptions) -> FullParams<'a, 'a> {
+    let strategy = match (options.beam_size, options.best_of) {
+        (Some(beam_size), _) => SamplingStrategy::BeamSearch {
+            beam_size,
+            // not implemented in whisper.cpp
+            patience: -1.0,
+        },
+        (None, Some(best_of)) => SamplingStrategy::Greedy { best_of },
+        (None, None) => SamplingStrategy::default(),
+    };
+    tracing::debug!("sampling strategy {:?}", strategy);
+    let mut params = FullParams::new(strategy);
     tracing::debug!("set language to {:?}", options.lang);
 
     if let Some(true) = options.word_timestamps {
@@ -78,21 +100,61 @@ fn setup_params(options: &TranscribeOptions) -> FullParams {
     params.set_print_progress(true);
     params.set_print_realtime(false);
     params.set_print_timestamps(false);
-    params.set_suppress_blank(true);
+    params.set_suppress_blank(options.suppress_blank.unwrap_or(true));
     params.set_token_timestamps(true);
 
+    if let Some(true) = options.suppress_non_speech_tokens {
+        params.set_suppress_non_speech_tokens(true);
+    }
+    if let Some(tokens) = options.suppress_tokens.as_ref().filter(|tokens| !tokens.is_empty()) {
+        tracing::debug!("suppressing tokens {:?}", tokens);
+        // SAFETY: the callback only reads the tokens, which are borrowed for as long as the params
+        unsafe {
+            params.set_filter_logits_callback(Some(suppress_tokens_callback));
+            params.set_filter_logits_callback_user_data(tokens as *const Vec<i32> as *mut c_void);
+        }
+    }
+    if let Some(true) = options.no_context {
+        params.set_no_context(true);
+    }
+
     if let Some(temperature) = options.temperature {
         tracing::debug!("setting temperature to {temperature}");
         params.set_temperature(temperature);
     }
 
+    if let Some(temperature_increment) = options.temperature_increment {
+        params.set_temperature_inc(temperature_increment);
+    }
+    if let Some(entropy_threshold) = options.entropy_threshold {
+        params.set_entropy_thold(entropy_threshold);
+    }
+    if let Some(logprob_threshold) = options.logprob_threshold {
+        params.set_logprob_thold(logprob_threshold);
+    }
+    if let Some(no_speech_threshold) = options.no_speech_threshold {
+        params.set_no_speech_thold(no_speech_threshold);
+    }
+
     if let Some(max_text_ctx) = options.max_text_ctx {
         tracing::debug!("setting n_max_text_ctx to {}", max_text_ctx);
         params.set_n_max_text_ctx(max_text_ctx)
     }
 
     // handle args
-    if let Some(init_prompt) = options.init_prompt.to_owned() {
+    let vocabulary = options.vocabulary.as_deref().unwrap_or_default();
+    let init_prompt = vocabulary::build_prompt(
+        options.init_prompt.as_deref(),
+        vocabulary,
+        vocabulary::PROMPT_TOKEN_BUDGET,
+        // Prompts too long to tokenize are over the budget anyway
+        |text| {
+            ctx.tokenize(text, vocabulary::PROMPT_TOKEN_BUDGET * 4)
+                .map(|tokens| tokens.len())
+                .unwrap_or(usize::MAX)
+        },
+    );
+    if let Some(init_prompt) = init_prompt {
         tracing::debug!("setting init prompt to {init_prompt}");
         params.set_initial_prompt(&init_prompt);
     }
@@ -104,23 +166,362 @@ fn setup_params(options: &TranscribeOptions) -> FullParams {
     params
 }
 
+/// whisper.cpp has no list of tokens to suppress, so their logits are cleared before each token is sampled
+unsafe extern "C" fn suppress_tokens_callback(
+    ctx: *mut whisper_rs::WhisperSysContext,
+    _state: *mut whisper_rs::WhisperSysState,
+    _tokens: *const whisper_rs::WhisperTokenData,
+    _n_tokens: c_int,
+    logits: *mut f32,
+    user_data: *mut c_void,
+) {
+    let tokens = &*(user_data as *const Vec<i32>);
+    let n_vocab = whisper_rs::whisper_rs_sys::whisper_n_vocab(ctx);
+    for &token in tokens {
+        if (0..n_vocab).contains(&token) {
+            *logits.add(token as usize) = f32::NEG_INFINITY;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DiarizeOptions {
     pub segment_model_path: String,
     pub embedding_model_path: String,
+    /// Cosine similarity for speakers to be considered the same, above 0. `diarize::DEFAULT_CLUSTER_THRESHOLD` is a tuned default
     pub threshold: f32,
-    pub max_speakers: usize,
+    /// Bounds on the estimated number of speakers
+    pub min_speakers: Option<usize>,
+    pub max_speakers: Option<usize>,
+    /// Enrolled voices, matched before falling back to numbered speakers
+    pub speaker_profiles: Vec<SpeakerProfile>,
+    pub pipeline: DiarizePipeline,
+    /// Only used by the overlap pipeline
+    pub speaker_assignment: SpeakerAssignment,
 }
 
-pub fn transcribe(
+impl DiarizeOptions {
+    pub fn validate(&self) -> Result<()> {
+        if let (Some(min), Some(max)) = (self.min_speakers, self.max_speakers) {
+            if min > max {
+                bail!("min_speakers ({min}) can't be more than max_speakers ({max})")
+            }
+        }
+        if !(self.threshold > 0.0 && self.threshold <= 1.0) {
+            bail!(
+                "threshold must be a cosine similarity above 0 and at most 1, got {}",
+                self.threshold
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Keep only the audio between `offset_ms` and `offset_ms + duration_ms`.
+/// Done here rather than with the whisper params so VAD and diarization see the same audio.
+pub(crate) fn select_range(samples: Vec<i16>, options: &TranscribeOptions) -> Result<Vec<i16>> {
+    let start = options.offset_ms.unwrap_or(0) as usize * 16;
+    if start == 0 && options.duration_ms.is_none() {
+        return Ok(samples);
+    }
+    if start >= samples.len() {
+        bail!(
+            "offset {}ms is past the end of the audio ({}ms)",
+            options.offset_ms.unwrap_or(0),
+            samples.len() / 16
+        )
+    }
+    let end = match options.duration_ms {
+        Some(duration) => (start + duration as usize * 16).min(samples.len()),
+        None => samples.len(),
+    };
+    Ok(samples[start..end].to_vec())
+}
+
+pub type ProgressCallback = Box<dyn Fn(i32)>;
+pub type SegmentCallback = Box<dyn Fn(Segment)>;
+pub type AbortCallback = Rc<dyn Fn() -> bool>;
+
+/// One transcription and its handlers.
+/// Handlers belong to the request, so concurrent transcriptions never see each other's progress.
+pub struct TranscribeRequest<'a> {
+    pub(crate) options: &'a TranscribeOptions,
+    pub(crate) progress_callback: Option<ProgressCallback>,
+    pub(crate) new_segment_callback: Option<SegmentCallback>,
+    pub(crate) abort_callback: Option<AbortCallback>,
+    pub(crate) diarize_options: Option<DiarizeOptions>,
+    pub(crate) cache: Option<&'a TranscriptCache>,
+    pub(crate) state: Option<&'a mut WhisperState>,
+    pub(crate) pool: Option<&'a Arc<StatePool>>,
+    pub(crate) workers: usize,
+    pub(crate) draft: Option<&'a dyn TranscriptionBackend>,
+    pub(crate) replace_callback: Option<ReplaceCallback>,
+}
+
+impl<'a> TranscribeRequest<'a> {
+    pub fn new(options: &'a TranscribeOptions) -> Self {
+        Self {
+            options,
+            progress_callback: None,
+            new_segment_callback: None,
+            abort_callback: None,
+            diarize_options: None,
+            cache: None,
+            state: None,
+            pool: None,
+            workers: 1,
+            draft: None,
+            replace_callback: None,
+        }
+    }
+
+    /// Called with the progress in percent
+    pub fn on_progress(mut self, callback: impl Fn(i32) + 'static) -> Self {
+        self.progress_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Called with every segment as soon as it's transcribed.
+    /// With the hallucination checks on, segments come once checked so dropped ones are never sent.
+    pub fn on_segment(mut self, callback: impl Fn(Segment) + 'static) -> Self {
+        self.new_segment_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Polled during the transcription, returning true stops it
+    pub fn abort_when(mut self, callback: impl Fn() -> bool + 'static) -> Self {
+        self.abort_callback = Some(Rc::new(callback));
+        self
+    }
+
+    pub fn diarize(mut self, diarize_options: Option<DiarizeOptions>) -> Self {
+        self.diarize_options = diarize_options;
+        self
+    }
+
+    pub fn cache(mut self, cache: Option<&'a TranscriptCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Decode with this state, usually one from a `pool::StatePool`, instead of creating one
+    pub fn state(mut self, state: &'a mut WhisperState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Decode chunks of long audio (see `TranscribeOptions::chunk_secs`) on up to `workers` states,
+    /// the extra ones taken from the free slots of `pool`
+    pub fn chunk_workers(mut self, pool: &'a Arc<StatePool>, workers: usize) -> Self {
+        self.pool = Some(pool);
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Transcribe a quick draft on `backend` first, usually a tiny or base model, see `draft`.
+    /// Its segments go to `on_segment`, then the segments of the main transcription go to `on_replace`.
+    pub fn draft(mut self, backend: &'a dyn TranscriptionBackend) -> Self {
+        self.draft = Some(backend);
+        self
+    }
+
+    /// Called with every segment of the main transcription and the draft time range it supersedes
+    pub fn on_replace(mut self, callback: impl Fn(Replacement) + 'static) -> Self {
+        self.replace_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Transcribe on `backend`, a loaded `WhisperContext` or a `remote::RemoteBackend`
+    pub fn run<B: TranscriptionBackend + ?Sized>(mut self, backend: &B) -> Result<Transcript> {
+        match self.draft.take() {
+            Some(draft) => draft::run(draft, self.replace_callback.take(), self, backend),
+            None => backend.transcribe(self),
+        }
+    }
+}
+
+unsafe extern "C" fn progress_trampoline(
+    _: *mut whisper_rs::WhisperSysContext,
+    _: *mut whisper_rs::WhisperSysState,
+    progress: c_int,
+    user_data: *mut c_void,
+) {
+    let callback = *(user_data as *const &dyn Fn(i32));
+    callback(progress);
+}
+
+unsafe extern "C" fn abort_trampoline(user_data: *mut c_void) -> bool {
+    let callback = *(user_data as *const &dyn Fn() -> bool);
+    callback()
+}
+
+/// Segments of one decode
+pub(crate) struct Decoded {
+    pub segments: Vec<Segment>,
+    /// Set when it was aborted, the segments only cover the audio before this
+    pub resume_from: Option<i64>,
+}
+
+/// Transcribe `original_samples` in one whisper call, skipping silence when VAD is enabled.
+/// Segment timestamps are moved by `offset` centiseconds, to be relative to the file start.
+/// When aborted, the segments whisper finished so far are kept.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn decode(
     ctx: &WhisperContext,
+    state: &mut WhisperState,
+    mut params: FullParams,
     options: &TranscribeOptions,
-    progress_callback: Option<Box<dyn Fn(i32) + Send + Sync>>,
-    new_segment_callback: Option<Box<dyn Fn(Segment)>>,
-    abort_callback: Option<Box<dyn Fn() -> bool>>,
-    diarize_options: Option<DiarizeOptions>,
-) -> Result<Transcript> {
+    original_samples: &[i16],
+    offset: i64,
+    speech_threshold: f32,
+    new_segment_callback: Option<SegmentCallback>,
+    progress_callback: Option<&dyn Fn(i32)>,
+    abort_callback: Option<&dyn Fn() -> bool>,
+) -> Result<Decoded> {
+    // Whisper only sees the speech, timestamps are mapped back to the original audio afterwards
+    let mut timeline = None;
+    let mut speech_samples = None;
+    if let Some(true) = options.vad {
+        let regions = vad::detect_speech(original_samples);
+        if regions.is_empty() {
+            tracing::debug!("vad found no speech, transcribing the whole file");
+        } else {
+            let (samples, speech_timeline) = vad::remove_silence(original_samples, &regions);
+            tracing::debug!(
+                "vad kept {} regions, {} of {} samples",
+                regions.len(),
+                samples.len(),
+                original_samples.len()
+            );
+            speech_samples = Some(samples);
+            timeline = Some(speech_timeline);
+        }
+    }
+    let to_original = move |timestamp: i64| timeline.as_ref().map_or(timestamp, |t| t.to_original(timestamp)) + offset;
+    let whisper_samples = speech_samples.as_deref().unwrap_or(original_samples);
+    let mut samples = vec![0.0f32; whisper_samples.len()];
+
+    whisper_rs::convert_integer_to_float_audio(whisper_samples, &mut samples)?;
+
+    // Checked segments can be dropped or changed, so they're only sent once the checks are done
+    let filter_hallucinations = hallucination::enabled(options);
+    let (live_segment_callback, checked_segment_callback) = match new_segment_callback {
+        Some(callback) if filter_hallucinations => (None, Some(callback)),
+        callback => (callback, None),
+    };
+    if let Some(new_segment_callback) = live_segment_callback {
+        let to_original = to_original.clone();
+        let internal_new_segmet_callback = move |segment: SegmentCallbackData| {
+            new_segment_callback(Segment {
+                start: to_original(segment.start_timestamp),
+                stop: to_original(segment.end_timestamp),
+                speaker: None,
+                speaker_confidence: None,
+                text: segment.text,
+                suspect: None,
+            })
+        };
+        params.set_segment_callback_safe_lossy(internal_new_segmet_callback);
+    }
+
+    // whisper.cpp gets pointers to the handlers, which outlive the `full` call below.
+    // The closure taking setters of whisper-rs don't keep capturing closures at a stable address.
+    if let Some(ref abort_callback) = abort_callback {
+        unsafe {
+            params.set_abort_callback(Some(abort_trampoline));
+            params.set_abort_callback_user_data(abort_callback as *const &dyn Fn() -> bool as *mut c_void);
+        }
+    }
+    if let Some(ref progress_callback) = progress_callback {
+        unsafe {
+            params.set_progress_callback(Some(progress_trampoline));
+            params.set_progress_callback_user_data(progress_callback as *const &dyn Fn(i32) as *mut c_void);
+        }
+    }
+
+    tracing::debug!("setting state full...");
+    let mut aborted = false;
+    if let Err(error) = state.full(params, &samples) {
+        if !abort_callback.is_some_and(|abort| abort()) {
+            return Err(error).context("failed to transcribe");
+        }
+        // whisper.cpp keeps the segments of the windows it finished before stopping
+        tracing::debug!("transcription aborted, keeping the finished segments");
+        aborted = true;
+    }
+
+    tracing::debug!("getting segments count...");
+    let num_segments = state.full_n_segments().context("failed to get number of segments")?;
+    if num_segments == 0 {
+        return Ok(Decoded {
+            segments: Vec::new(),
+            resume_from: Some(offset).filter(|_| aborted),
+        });
+    }
+    tracing::debug!("found {} sentence segments", num_segments);
+
+    tracing::debug!("looping segments...");
+    let mut decoded = Vec::new();
+    for s in 0..num_segments {
+        let text = state.full_get_segment_text_lossy(s).context("failed to get segment")?;
+        let start = state.full_get_segment_t0(s).context("failed to get start timestamp")?;
+        let stop = state.full_get_segment_t1(s).context("failed to get end timestamp")?;
+        decoded.push(Segment {
+            text,
+            start,
+            stop,
+            speaker: None,
+            speaker_confidence: None,
+            suspect: None,
+        });
+    }
+    // Before filtering, which may drop the last segment
+    let resume_from = decoded.last().map(|segment| to_original(segment.stop)).filter(|_| aborted);
+    if filter_hallucinations {
+        let mut measured = Vec::with_capacity(decoded.len());
+        for (s, segment) in decoded.into_iter().enumerate() {
+            let from = (segment.start.max(0) as usize * 160).min(whisper_samples.len());
+            let to = (segment.stop.max(0) as usize * 160).clamp(from, whisper_samples.len());
+            let silence = vad::silence_ratio(&whisper_samples[from..to], speech_threshold);
+            let quality = SegmentQuality::measure(ctx, state, s as i32..s as i32 + 1, silence)?;
+            measured.push((segment, quality));
+        }
+        decoded = hallucination::filter_segments(ctx, state, options, whisper_samples, speech_threshold, measured)?;
+    }
+    let segments: Vec<Segment> = decoded
+        .into_iter()
+        .map(|mut segment| {
+            segment.start = to_original(segment.start);
+            segment.stop = to_original(segment.stop);
+            segment
+        })
+        .collect();
+    if let Some(checked_segment_callback) = checked_segment_callback {
+        for segment in &segments {
+            checked_segment_callback(segment.clone());
+        }
+    }
+    Ok(Decoded { segments, resume_from })
+}
+
+pub fn transcribe(ctx: &WhisperContext, request: TranscribeRequest) -> Result<Transcript> {
+    let TranscribeRequest {
+        options,
+        progress_callback,
+        new_segment_callback,
+        abort_callback,
+        diarize_options,
+        cache,
+        state,
+        pool,
+        workers,
+        ..
+    } = request;
     tracing::debug!("Transcribe called with {:?}", options);
+    options.validate()?;
+    if let Some(diarize_options) = &diarize_options {
+        diarize_options.validate()?;
+    }
 
     if !PathBuf::from(options.path.clone()).exists() {
         bail!("audio file doesn't exist")
@@ -128,30 +529,81 @@ pub fn transcribe(
 
     let out_path = create_normalized_audio(options.path.clone().into())?;
     tracing::debug!("out path is {}", out_path.display());
-    let original_samples = audio::parse_wav_file(&out_path)?;
-
-    let mut state = ctx.create_state().context("failed to create key")?;
+    let original_samples = select_range(audio::parse_wav_file(&out_path)?, options)?;
+    // Whisper timestamps of the selected range, in centiseconds, are moved by this to stay relative to the file start
+    let offset = options.offset_ms.unwrap_or(0) as i64 / 10;
+
+    let cache = cache.filter(|_| !options.no_cache.unwrap_or(false));
+    let cache_key = match cache {
+        Some(cache) => Some(cache.key(&original_samples, options, diarize_options.as_ref())?),
+        None => None,
+    };
+    if let (Some(cache), Some(key)) = (cache, &cache_key) {
+        if let Some(transcript) = cache.get(key) {
+            tracing::debug!("using cached transcript {}", key);
+            std::fs::remove_file(out_path)?;
+            if let Some(ref new_segment_callback) = new_segment_callback {
+                for segment in &transcript.segments {
+                    new_segment_callback(segment.clone());
+                }
+            }
+            if let Some(ref progress_callback) = progress_callback {
+                progress_callback(100);
+            }
+            return Ok(transcript);
+        }
+    }
+    let mut own_state;
+    let state = match state {
+        Some(state) => state,
+        None => {
+            own_state = ctx.create_state().context("failed to create key")?;
+            &mut own_state
+        }
+    };
 
-    let mut params = setup_params(options);
+    let mut params = setup_params(ctx, options);
 
     let mut segments = Vec::new();
 
+    let (diarize_options, overlap_diarize_options) = match diarize_options {
+        Some(diarize_options) if diarize_options.pipeline == DiarizePipeline::Overlap => (None, Some(diarize_options)),
+        diarize_options => (diarize_options, None),
+    };
+
+    // Computed once so every segment's silence is measured against the same noise floor
+    let filter_hallucinations = hallucination::enabled(options);
+    let speech_threshold = if filter_hallucinations {
+        vad::speech_threshold(&original_samples)
+    } else {
+        0.0
+    };
+
+    let mut resume_from = None;
     let st = std::time::Instant::now();
     if let Some(diarize_options) = diarize_options {
         tracing::debug!("Diarize enabled {:?}", diarize_options);
         params.set_single_segment(true);
 
-        let diarize_segments =
-            pyannote_rs::segment(&original_samples, 16000, diarize_options.segment_model_path).map_err(|e| eyre!("{:?}", e))?;
-        let mut embedding_manager = pyannote_rs::EmbeddingManager::new(diarize_options.max_speakers);
-        let mut extractor =
-            pyannote_rs::EmbeddingExtractor::new(diarize_options.embedding_model_path).map_err(|e| eyre!("{:?}", e))?;
-        for (i, diarize_segment) in diarize_segments.iter().enumerate() {
+        // Speakers are clustered over the whole recording before transcribing the segments
+        let embedded_segments = diarize::embed_segments(&original_samples, &diarize_options, abort_callback.as_deref())?;
+        let embeddings: Vec<Vec<f32>> = embedded_segments.iter().map(|(_, embedding)| embedding.clone()).collect();
+        let speaker_matches = diarize::identify_speakers(&embeddings, &diarize_options);
+        let diarize_segments: Vec<_> = embedded_segments.into_iter().map(|(segment, _)| segment).collect();
+        // Embedding stops early when aborted, even the segments found may not cover the whole audio
+        if abort_callback.as_ref().is_some_and(|abort| abort()) {
+            resume_from = Some(offset);
+        }
+        // End of the last speech segment transcribed
+        let mut transcribed_until = offset;
+        for (i, (diarize_segment, speaker_match)) in diarize_segments.iter().zip(speaker_matches).enumerate() {
             if let Some(ref abort_callback) = abort_callback {
                 if abort_callback() {
+                    resume_from = Some(transcribed_until);
                     break;
                 }
             }
+            transcribed_until = 100 * (diarize_segment.end as i64) + offset;
 
             // whisper compatible. segment indices
             tracing::debug!("diarize segment: {} - {}", diarize_segment.start, diarize_segment.end);
@@ -167,41 +619,34 @@ pub fn transcribe(
             tracing::debug!("looping segments...");
 
             if num_segments > 0 {
-                let embedding_result: Vec<f32> = match extractor.compute(&diarize_segment.samples) {
-                    Ok(result) => result.collect(),
-                    Err(error) => {
-                        tracing::error!("error: {:?}", error);
-                        tracing::debug!(
-                            "start = {:.2}, end = {:.2}, speaker = ?",
-                            diarize_segment.start,
-                            diarize_segment.end
-                        );
-                        continue; // Skip to the next segment
-                    }
-                };
-                // Find the speaker
-                let speaker = if embedding_manager.get_all_speakers().len() == diarize_options.max_speakers {
-                    embedding_manager
-                        .get_best_speaker_match(embedding_result)
-                        .map(|r| r.to_string())
-                        .unwrap_or("?".into())
-                } else {
-                    embedding_manager
-                        .search_speaker(embedding_result, diarize_options.threshold)
-                        .map(|r| r.to_string())
-                        .unwrap_or("?".into())
-                };
-
-                // convert to whisper comptible timestamps
-                let start = 100 * (diarize_segment.start as i64);
-                let stop = 100 * (diarize_segment.end as i64);
                 let text = state.full_get_segment_text_lossy(0).context("failed to get segment")?;
-                let segment = Segment {
-                    speaker: Some(speaker),
-                    start,
-                    stop,
+                let mut segment = Segment {
+                    speaker: Some(speaker_match.speaker),
+                    speaker_confidence: Some(speaker_match.confidence),
+                    start: 0,
+                    stop: (diarize_segment.samples.len() / 160) as i64,
                     text,
+                    suspect: None,
                 };
+                if filter_hallucinations {
+                    let silence = vad::silence_ratio(&diarize_segment.samples, speech_threshold);
+                    let quality = SegmentQuality::measure(ctx, state, 0..1, silence)?;
+                    let filtered = hallucination::filter_segments(
+                        ctx,
+                        state,
+                        options,
+                        &diarize_segment.samples,
+                        speech_threshold,
+                        vec![(segment, quality)],
+                    )?;
+                    match filtered.into_iter().next() {
+                        Some(kept) => segment = kept,
+                        None => continue,
+                    }
+                }
+                // convert to whisper comptible timestamps
+                segment.start = 100 * (diarize_segment.start as i64) + offset;
+                segment.stop = 100 * (diarize_segment.end as i64) + offset;
                 segments.push(segment.clone());
 
                 if let Some(ref new_segment_callback) = new_segment_callback {
@@ -216,72 +661,80 @@ pub fn transcribe(
             }
         }
     } else {
-        if let Some(callback) = progress_callback {
-            let mut guard = PROGRESS_CALLBACK.lock().map_err(|e| eyre!("{:?}", e))?;
-            let internal_progress_callback = move |progress: i32| callback(progress);
-            *guard = Some(Box::new(internal_progress_callback));
-        }
-        let mut samples = vec![0.0f32; original_samples.len()];
-
-        whisper_rs::convert_integer_to_float_audio(&original_samples, &mut samples)?;
-
-        if let Some(new_segment_callback) = new_segment_callback {
-            let internal_new_segmet_callback = move |segment: SegmentCallbackData| {
-                new_segment_callback(Segment {
-                    start: segment.start_timestamp,
-                    stop: segment.end_timestamp,
-                    speaker: None,
-                    text: segment.text,
-                })
-            };
-            params.set_segment_callback_safe_lossy(internal_new_segmet_callback);
-        }
-
-        if let Some(abort_callback) = abort_callback {
-            params.set_abort_callback_safe(abort_callback);
-        }
-
-        if PROGRESS_CALLBACK.lock().map_err(|e| eyre!("{:?}", e))?.as_ref().is_some() {
-            params.set_progress_callback_safe(|progress| {
-                // using move here lead to crash
-                tracing::debug!("progress callback {}", progress);
-                match PROGRESS_CALLBACK.lock() {
-                    Ok(callback_guard) => {
-                        if let Some(progress_callback) = callback_guard.as_ref() {
-                            progress_callback(progress);
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to lock PROGRESS_CALLBACK: {:?}", e);
-                    }
+        // Speakers are found first on their own, then given to the words whisper finds in the whole file
+        let speaker_turns = match overlap_diarize_options {
+            Some(ref diarize_options) => {
+                tracing::debug!("Diarize by overlap enabled {:?}", diarize_options);
+                params.set_token_timestamps(true);
+                params.set_split_on_word(true);
+                params.set_max_len(1);
+                let mut turns = diarize::speaker_turns(&original_samples, diarize_options, abort_callback.as_deref())?;
+                for turn in &mut turns {
+                    turn.start += offset;
+                    turn.stop += offset;
                 }
-            });
-        }
-
-        tracing::debug!("set start time...");
-
-        tracing::debug!("setting state full...");
-        state.full(params, &samples).context("failed to transcribe")?;
-        let _et = std::time::Instant::now();
-
-        tracing::debug!("getting segments count...");
-        let num_segments = state.full_n_segments().context("failed to get number of segments")?;
-        if num_segments == 0 {
-            bail!("no segements found!")
-        }
-        tracing::debug!("found {} sentence segments", num_segments);
-
-        tracing::debug!("looping segments...");
-        for s in 0..num_segments {
-            let text = state.full_get_segment_text_lossy(s).context("failed to get segment")?;
-            let start = state.full_get_segment_t0(s).context("failed to get start timestamp")?;
-            let stop = state.full_get_segment_t1(s).context("failed to get end timestamp")?;
-            segments.push(Segment {
-                text,
-                start,
-                stop,
-                speaker: None,
-            });
+                Some(turns)
+            }
+            None => None,
+        };
+
+        // Words have no speaker yet, so emit the speaker turns once they're assigned instead
+        let (live_segment_callback, new_segment_callback) = match speaker_turns {
+            Some(_) => (None, new_segment_callback),
+            None => (new_segment_callback, None),
+        };
+        let chunks = chunk::plan_chunks(&original_samples, options);
+        let decoded = if chunks.len() > 1 {
+            chunk::transcribe_chunks(
+                ctx,
+                chunk::Workers {
+                    state,
+                    pool,
+                    max: workers,
+                },
+                params,
+                options,
+                &original_samples,
+                &chunks,
+                offset,
+                speech_threshold,
+                chunk::Handlers {
+                    segment: live_segment_callback.as_deref(),
+                    progress: progress_callback.as_deref(),
+                    abort: abort_callback.as_deref(),
+                },
+            )?
+        } else {
+            let progress: Option<&dyn Fn(i32)> = progress_callback.as_deref();
+            let abort: Option<&dyn Fn() -> bool> = abort_callback.as_deref();
+            let decoded = decode(
+                ctx,
+                state,
+                params,
+                options,
+                &original_samples,
+                offset,
+                speech_threshold,
+                live_segment_callback,
+                progress,
+                abort,
+            )?;
+            if decoded.segments.is_empty() && decoded.resume_from.is_none() {
+                bail!("no segements found!")
+            }
+            decoded
+        };
+        segments = decoded.segments;
+        resume_from = decoded.resume_from;
+
+        if let (Some(turns), Some(diarize_options)) = (speaker_turns, overlap_diarize_options) {
+            tracing::debug!("assigning {} speaker turns to {} words", turns.len(), segments.len());
+            segments = diarize::assign_speakers(&segments, &turns, diarize_options.speaker_assignment);
+            if let Some(ref new_segment_callback) = new_segment_callback {
+                for segment in &segments {
+                    new_segment_callback(segment.clone());
+                }
+            }
         }
     }
 
@@ -289,10 +742,25 @@ pub fn transcribe(
     let mut transcript = Transcript {
         segments,
         processing_time_sec: Instant::now().duration_since(st).as_secs(),
+        resume_from,
     };
+    if let Some(resume_from) = resume_from {
+        tracing::debug!(
+            "transcription aborted, {} segments up to {}",
+            transcript.segments.len(),
+            resume_from
+        );
+    }
 
     // cleanup
     std::fs::remove_file(out_path)?;
 
+    let aborted = abort_callback.as_ref().map(|abort| abort()).unwrap_or(false);
+    if let (Some(cache), Some(key), false) = (cache, cache_key, aborted) {
+        if let Err(error) = cache.put(&key, &transcript) {
+            tracing::warn!("failed to cache transcript: {:?}", error);
+        }
+    }
+
     Ok(transcript)
 }
@@ -29,6 +29,9 @@ pub fn format_timestamp(seconds: i64, always_include_hours: bool, decimal_marker
 pub struct Transcript {
     pub processing_time_sec: u64,
     pub segments: Vec<Segment>,
+    /// Set when the transcription was aborted: the segments only cover the audio before this (centiseconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_from: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
@@ -38,6 +41,12 @@ pub struct Segment {
     pub text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub speaker: Option<String>,
+    /// How closely the voice matched the speaker, set when diarization assigned it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker_confidence: Option<f32>,
+    /// Set when the hallucination filter found the text likely made up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<bool>,
 }
 
 impl Segment {
@@ -65,6 +74,10 @@ impl Segment {
 }
 
 impl Transcript {
+    pub fn is_partial(&self) -> bool {
+        self.resume_from.is_some()
+    }
+
     pub fn as_text(&self) -> String {
         self.segments
             .iter()
@@ -90,3 +103,19 @@ impl Transcript {
             .1
     }
 }
+
+/// Replace the part of `segments` between `start` and `stop` (centiseconds) with `replacement`.
+/// Segments crossing a boundary go to the side holding most of them.
+pub fn splice_segments(segments: &[Segment], replacement: Vec<Segment>, start: i64, stop: i64) -> Vec<Segment> {
+    let inside = |segment: &Segment| {
+        let middle = (segment.start + segment.stop) / 2;
+        middle >= start && middle < stop
+    };
+    let mut spliced: Vec<Segment> = segments.iter().filter(|segment| !inside(segment)).cloned().collect();
+    let at = spliced
+        .iter()
+        .position(|segment| segment.start >= start)
+        .unwrap_or(spliced.len());
+    spliced.splice(at..at, replacement);
+    spliced
+}
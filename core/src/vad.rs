@@ -0,0 +1,170 @@
+//! Energy based voice activity detection.
+//! Finds speech regions in 16kHz mono audio so only those are given to whisper,
+//! and maps timestamps of the shortened audio back onto the original timeline.
+
+const SAMPLE_RATE: usize = 16000;
+/// 30ms frames
+const FRAME_SIZE: usize = SAMPLE_RATE * 30 / 1000;
+/// Frames this much louder than the noise floor are speech
+const SPEECH_MARGIN_DB: f32 = 12.0;
+/// Quietest level considered speech, whatever the noise floor
+const MIN_SPEECH_DB: f32 = -55.0;
+/// Padding kept around every region so word onsets and endings aren't cut
+const PADDING_MS: usize = 250;
+/// Silences shorter than this don't split regions
+const MIN_SILENCE_MS: usize = 600;
+/// Regions shorter than this are treated as clicks and noise
+const MIN_SPEECH_MS: usize = 250;
+/// Silence put between regions in the shortened audio, so whisper still sees a pause
+const GAP_MS: usize = 200;
+
+/// Range of samples containing speech
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeechRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+fn ms_to_samples(ms: usize) -> usize {
+    ms * SAMPLE_RATE / 1000
+}
+
+fn frame_db(frame: &[i16]) -> f32 {
+    let sum: f64 = frame.iter().map(|s| (*s as f64 / i16::MAX as f64).powi(2)).sum();
+    let rms = (sum / frame.len().max(1) as f64).sqrt();
+    (20.0 * rms.max(1e-10).log10()) as f32
+}
+
+fn threshold_from_levels(levels: &[f32]) -> f32 {
+    if levels.is_empty() {
+        return MIN_SPEECH_DB;
+    }
+    // The noise floor is the level of the quieter frames, recordings rarely have less than 10% silence
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted[sorted.len() / 10];
+    let threshold = (noise_floor + SPEECH_MARGIN_DB).max(MIN_SPEECH_DB);
+    tracing::debug!("vad noise floor {:.1}dB, threshold {:.1}dB", noise_floor, threshold);
+    threshold
+}
+
+/// Level (dB) above which a frame is speech, relative to the noise floor of the whole recording
+pub fn speech_threshold(samples: &[i16]) -> f32 {
+    let levels: Vec<f32> = samples.chunks(FRAME_SIZE).map(frame_db).collect();
+    threshold_from_levels(&levels)
+}
+
+/// Share of frames quieter than `threshold`, 1.0 when there's no speech at all
+pub fn silence_ratio(samples: &[i16], threshold: f32) -> f32 {
+    let frames = samples.chunks(FRAME_SIZE).count();
+    if frames == 0 {
+        return 1.0;
+    }
+    let silent = samples.chunks(FRAME_SIZE).filter(|frame| frame_db(frame) < threshold).count();
+    silent as f32 / frames as f32
+}
+
+/// Position of the middle of the quietest stretch of `samples`, to cut audio where nobody speaks
+pub fn quietest_point(samples: &[i16]) -> usize {
+    // Averaged over a few frames, so a stop consonant doesn't pass for a pause
+    const WINDOW_FRAMES: usize = 10;
+    let levels: Vec<f32> = samples.chunks(FRAME_SIZE).map(frame_db).collect();
+    let window = WINDOW_FRAMES.min(levels.len()).max(1);
+    levels
+        .windows(window)
+        .map(|w| w.iter().sum::<f32>())
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| ((i * FRAME_SIZE) + window * FRAME_SIZE / 2).min(samples.len()))
+        .unwrap_or(samples.len() / 2)
+}
+
+pub fn detect_speech(samples: &[i16]) -> Vec<SpeechRegion> {
+    let levels: Vec<f32> = samples.chunks(FRAME_SIZE).map(frame_db).collect();
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let threshold = threshold_from_levels(&levels);
+
+    // Speech frames close enough to each other form a region
+    let mut regions: Vec<SpeechRegion> = Vec::new();
+    for (i, level) in levels.iter().enumerate() {
+        if *level < threshold {
+            continue;
+        }
+        let start = i * FRAME_SIZE;
+        let end = ((i + 1) * FRAME_SIZE).min(samples.len());
+        match regions.last_mut() {
+            Some(last) if start <= last.end + ms_to_samples(MIN_SILENCE_MS) => last.end = end,
+            _ => regions.push(SpeechRegion { start, end }),
+        }
+    }
+    regions.retain(|r| r.end - r.start >= ms_to_samples(MIN_SPEECH_MS));
+
+    // Pad afterwards, merging regions the padding made overlap
+    let mut padded: Vec<SpeechRegion> = Vec::new();
+    for region in regions {
+        let start = region.start.saturating_sub(ms_to_samples(PADDING_MS));
+        let end = (region.end + ms_to_samples(PADDING_MS)).min(samples.len());
+        match padded.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => padded.push(SpeechRegion { start, end }),
+        }
+    }
+    padded
+}
+
+/// Position of a piece of the shortened audio in the original audio, in samples
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    compact_start: usize,
+    original_start: usize,
+    len: usize,
+}
+
+/// Maps timestamps of the shortened audio back to the original one
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    pieces: Vec<Piece>,
+}
+
+impl Timeline {
+    /// Convert a whisper timestamp (centiseconds) of the shortened audio to the original audio
+    pub fn to_original(&self, centiseconds: i64) -> i64 {
+        let sample = (centiseconds.max(0) as usize) * SAMPLE_RATE / 100;
+        let piece = self
+            .pieces
+            .iter()
+            .rev()
+            .find(|piece| piece.compact_start <= sample)
+            .or(self.pieces.first());
+        match piece {
+            // Timestamps falling in the inserted gap are moved to the end of the previous piece
+            Some(piece) => {
+                let offset = sample.saturating_sub(piece.compact_start).min(piece.len);
+                ((piece.original_start + offset) * 100 / SAMPLE_RATE) as i64
+            }
+            None => centiseconds,
+        }
+    }
+}
+
+/// Keep only the speech regions, separated by short silences.
+/// Returns the shortened audio and the timeline to remap its timestamps.
+pub fn remove_silence(samples: &[i16], regions: &[SpeechRegion]) -> (Vec<i16>, Timeline) {
+    let gap = ms_to_samples(GAP_MS);
+    let mut compact = Vec::with_capacity(regions.iter().map(|r| r.end - r.start + gap).sum());
+    let mut timeline = Timeline::default();
+    for region in regions {
+        if !compact.is_empty() {
+            compact.resize(compact.len() + gap, 0);
+        }
+        timeline.pieces.push(Piece {
+            compact_start: compact.len(),
+            original_start: region.start,
+            len: region.end - region.start,
+        });
+        compact.extend_from_slice(&samples[region.start..region.end]);
+    }
+    (compact, timeline)
+}
@@ -0,0 +1,109 @@
+//! Domain terms whisper keeps getting wrong: a vocabulary given to it in the initial prompt,
+//! and a dictionary of replacements applied to the text afterwards.
+
+use crate::transcript::Segment;
+use eyre::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// whisper.cpp keeps only the last n_text_ctx / 2 tokens of the prompt
+pub const PROMPT_TOKEN_BUDGET: usize = 223;
+
+/// Put as many vocabulary terms before the initial prompt as fit in `budget` tokens.
+/// The initial prompt is always kept whole, terms are added in order until one doesn't fit.
+pub fn build_prompt(
+    init_prompt: Option<&str>,
+    terms: &[String],
+    budget: usize,
+    count_tokens: impl Fn(&str) -> usize,
+) -> Option<String> {
+    let init_prompt = init_prompt.map(str::trim).filter(|p| !p.is_empty());
+    let render = |terms: &[&str]| match (terms.is_empty(), init_prompt) {
+        (true, prompt) => prompt.map(str::to_string),
+        (false, Some(prompt)) => Some(format!("{}. {}", terms.join(", "), prompt)),
+        (false, None) => Some(format!("{}.", terms.join(", "))),
+    };
+
+    let mut included: Vec<&str> = Vec::new();
+    for term in terms.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if included.contains(&term) {
+            continue;
+        }
+        included.push(term);
+        let tokens = render(&included).map(|p| count_tokens(&p)).unwrap_or(0);
+        if tokens > budget {
+            tracing::debug!("vocabulary prompt full at {} terms of {}", included.len() - 1, terms.len());
+            included.pop();
+            break;
+        }
+    }
+    render(&included)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replacement {
+    pub find: String,
+    pub replace: String,
+    /// `find` is a regular expression, `replace` can use its groups as `$1`
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// Compiled replacements, applied in order
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Dictionary {
+    pub fn new(replacements: &[Replacement]) -> Result<Self> {
+        let mut rules = Vec::with_capacity(replacements.len());
+        for replacement in replacements {
+            if replacement.find.is_empty() {
+                bail!("replacement rule has nothing to find")
+            }
+            let pattern = if replacement.regex {
+                replacement.find.clone()
+            } else {
+                // Whole words only, so short terms don't match inside longer ones
+                let escaped = regex::escape(&replacement.find);
+                let starts_word = replacement.find.starts_with(|c: char| c.is_alphanumeric());
+                let ends_word = replacement.find.ends_with(|c: char| c.is_alphanumeric());
+                format!(
+                    "{}{}{}",
+                    if starts_word { r"\b" } else { "" },
+                    escaped,
+                    if ends_word { r"\b" } else { "" }
+                )
+            };
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(!replacement.case_sensitive)
+                .build()
+                .with_context(|| format!("invalid pattern {:?}", replacement.find))?;
+            // Literal replacements shouldn't expand $ references
+            let replace = if replacement.regex {
+                replacement.replace.clone()
+            } else {
+                replacement.replace.replace('$', "$$")
+            };
+            rules.push((regex, replace));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (regex, replace) in &self.rules {
+            text = regex.replace_all(&text, replace.as_str()).into_owned();
+        }
+        text
+    }
+
+    pub fn apply_to_segments(&self, segments: &mut [Segment]) {
+        for segment in segments {
+            segment.text = self.apply(&segment.text);
+        }
+    }
+}
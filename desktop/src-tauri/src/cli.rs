@@ -1,15 +1,27 @@
-use clap::Parser;
-use eyre::{Context, ContextCompat, Result};
+use clap::{Parser, Subcommand};
+use eyre::{bail, Context, ContextCompat, Result};
 use once_cell::sync::Lazy;
+use samwise_text::prompt_templates::render_template;
+use samwise_text::text_generation::{generate_text, TextGenerationOptions};
+use serde_json::json;
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
 use std::time::Instant;
 use tauri::AppHandle;
-use vibe_core::config::TranscribeOptions;
-use vibe_core::transcribe;
-
-use crate::cmd::get_models_folder;
+use vibe_core::config::{BackendOptions, HallucinationFilter, RemoteOptions, TranscribeOptions};
+use vibe_core::pool::StatePool;
+use vibe_core::remote::RemoteBackend;
+use vibe_core::transcribe::{self, TranscribeRequest};
+use vibe_core::transcript::{Segment, Transcript};
+use vibe_core::vocabulary::{Dictionary, Replacement};
+
+use crate::cmd::generation::parse_strategy;
+use crate::cmd::templates::{find_template, template_variables};
+use crate::cmd::vocabulary;
+use crate::cmd::{get_models_folder, transcript_cache};
+use crate::database::get_pool;
 use crate::server;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -105,6 +117,106 @@ struct Args {
     // TODO: use possible values. confusing crate!
     max_sentence_len: Option<i32>,
 
+    /// Term to spell this way, added to the initial prompt. Can be repeated
+    #[arg(long)]
+    vocabulary: Vec<String>,
+
+    /// JSON file with replacement rules (find, replace, regex, case_sensitive). Defaults to the rules saved in the app
+    #[arg(long)]
+    dictionary: Option<PathBuf>,
+
+    /// Skip silence before transcribing
+    #[arg(long)]
+    vad: bool,
+
+    /// Flag or drop segments that look made up (repeated phrases, text over silence)
+    #[arg(long, value_parser = ["flag", "drop"])]
+    hallucination_filter: Option<String>,
+
+    /// Decode suspect segments again at higher temperatures
+    #[arg(long)]
+    temperature_fallback: bool,
+
+    /// Use beam search with this many beams instead of greedy decoding
+    #[arg(long)]
+    beam_size: Option<i32>,
+
+    /// Candidates sampled per window with greedy decoding
+    #[arg(long)]
+    best_of: Option<i32>,
+
+    /// Temperature increase when a window fails the entropy or logprob threshold
+    #[arg(long)]
+    temperature_increment: Option<f32>,
+
+    /// Entropy above which a window is decoded again
+    #[arg(long)]
+    entropy_threshold: Option<f32>,
+
+    /// Average log probability below which a window is decoded again
+    #[arg(long, allow_hyphen_values = true)]
+    logprob_threshold: Option<f32>,
+
+    /// No speech probability above which a window is considered silent
+    #[arg(long)]
+    no_speech_threshold: Option<f32>,
+
+    /// Allow segments to start with a blank
+    #[arg(long)]
+    no_suppress_blank: bool,
+
+    /// Suppress non speech tokens such as music notes
+    #[arg(long)]
+    suppress_non_speech_tokens: bool,
+
+    /// Token ids never to output, separated by commas
+    #[arg(long, value_delimiter = ',')]
+    suppress_tokens: Option<Vec<i32>>,
+
+    /// Start transcribing at this position, in milliseconds
+    #[arg(long)]
+    offset_ms: Option<i32>,
+
+    /// Transcribe only this much audio, in milliseconds
+    #[arg(long)]
+    duration_ms: Option<i32>,
+
+    /// Don't use the previous text as context
+    #[arg(long)]
+    no_context: bool,
+
+    /// Transcribe again even if the same file was transcribed with the same options
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Split audio longer than this many seconds into chunks transcribed separately
+    #[arg(long)]
+    chunk_secs: Option<u32>,
+
+    /// Seconds of audio shared by consecutive chunks
+    #[arg(long)]
+    chunk_overlap_secs: Option<u32>,
+
+    /// Chunks transcribed at once, each with its own whisper state
+    #[arg(long, default_value = "1")]
+    workers: usize,
+
+    /// Transcribe on a server with the OpenAI audio API instead of a local model, e.g. https://api.openai.com/v1
+    #[arg(long)]
+    remote_url: Option<String>,
+
+    /// Model name on the remote server
+    #[arg(long, default_value = "whisper-1")]
+    remote_model: String,
+
+    /// API key of the remote server, OPENAI_API_KEY is used when not given
+    #[arg(long)]
+    remote_api_key: Option<String>,
+
+    /// Largest file the remote server accepts in MB, longer audio is uploaded in chunks
+    #[arg(long)]
+    max_upload_mb: Option<u32>,
+
     /// Enable diarize (speaker labels)
     #[arg(long)]
     diarize: bool,
@@ -127,6 +239,71 @@ struct Args {
     /// Port
     #[arg(long, default_value = "3022")]
     port: u16,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Summarize a transcript using a prompt template
+    Summarize(SummarizeArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct SummarizeArgs {
+    /// Built-in template id (standup, one-on-one, sales-call, interview, lecture-notes) or name of a user template
+    #[arg(long, default_value = "standup")]
+    template: String,
+
+    /// Path to a JSON transcript (as written with --format json)
+    #[arg(long)]
+    transcript: Option<PathBuf>,
+
+    /// Path to audio file to transcribe first, instead of --transcript
+    #[arg(long)]
+    file: Option<String>,
+
+    /// Path to model, used with --file
+    #[arg(long, short)]
+    model: Option<PathBuf>,
+
+    /// Language of --file
+    #[arg(short, long, default_value = "english", value_parser = get_possible_languages())]
+    language: String,
+
+    /// JSON file with replacement rules applied to the transcript. Defaults to the rules saved in the app
+    #[arg(long)]
+    dictionary: Option<PathBuf>,
+
+    /// Text generation strategy
+    #[arg(long, default_value = "ollama", value_parser = ["ollama", "gemini"])]
+    strategy: String,
+
+    /// Base url of Ollama or any OpenAI compatible API
+    #[arg(long, default_value = "http://localhost:11434")]
+    ollama_base_url: String,
+
+    #[arg(long, default_value = "llama3.1")]
+    ollama_model: String,
+
+    #[arg(long, default_value = "gemini-1.5-flash")]
+    gemini_model: String,
+
+    /// API key. Defaults to OLLAMA_API_KEY or GEMINI_API_KEY environment variables
+    #[arg(long)]
+    api_key: Option<String>,
+
+    #[arg(long, default_value = "4096")]
+    max_output_tokens: i32,
+
+    /// Sampling temperature of the model, its own default when missing
+    #[arg(long)]
+    temperature: Option<f32>,
+
+    /// Path to write summary
+    #[arg(short, long)]
+    write: Option<PathBuf>,
 }
 
 fn get_possible_languages() -> Vec<String> {
@@ -142,7 +319,7 @@ fn get_possible_languages() -> Vec<String> {
 }
 
 pub fn get_possible_formats() -> Vec<String> {
-    vec!["txt".into(), "srt".into(), "vtt".into()]
+    vec!["txt".into(), "srt".into(), "vtt".into(), "json".into()]
 }
 
 fn prepare_model_path(path: &Path, app_handle: &tauri::AppHandle) -> Result<PathBuf> {
@@ -167,6 +344,82 @@ fn language_name_to_whisper_lang(name: &str) -> Result<String> {
     Ok(languages[name].as_str().context("as_str")?.to_string())
 }
 
+fn read_transcript(path: &Path) -> Result<Vec<Segment>> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    // Accept both the CLI json format and the segments array exported by the app
+    if let Ok(transcript) = serde_json::from_str::<Transcript>(&content) {
+        return Ok(transcript.segments);
+    }
+    serde_json::from_str::<Vec<Segment>>(&content).context("invalid transcript json")
+}
+
+/// Replacement rules from `path`, or the ones saved in the app when it isn't given
+async fn load_dictionary(app_handle: &AppHandle, path: Option<&Path>) -> Result<Dictionary> {
+    if let Some(path) = path {
+        let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let replacements: Vec<Replacement> = serde_json::from_str(&content).context("invalid dictionary json")?;
+        return Dictionary::new(&replacements);
+    }
+    // The app database may not exist yet
+    Ok(vocabulary::load_dictionary(app_handle).await.unwrap_or_else(|error| {
+        tracing::debug!("no saved replacement rules: {:?}", error);
+        Dictionary::default()
+    }))
+}
+
+async fn summarize(app_handle: &AppHandle, args: SummarizeArgs) -> Result<()> {
+    let dictionary = load_dictionary(app_handle, args.dictionary.as_deref()).await?;
+    let mut segments = if let Some(transcript) = args.transcript {
+        read_transcript(&transcript)?
+    } else if let Some(file) = args.file {
+        let model_path = prepare_model_path(&args.model.context("--model is required with --file")?, app_handle)?;
+        let options = TranscribeOptions {
+            path: file,
+            lang: Some(language_name_to_whisper_lang(&args.language)?),
+            verbose: Some(false),
+            ..Default::default()
+        };
+        eprintln!("Transcribe... 🔄");
+        let ctx = transcribe::create_context(&model_path, None)?;
+        let cache = transcript_cache(app_handle, &model_path);
+        TranscribeRequest::new(&options).cache(cache.as_ref()).run(&ctx)?.segments
+    } else {
+        bail!("Please provide --transcript or --file")
+    };
+    dictionary.apply_to_segments(&mut segments);
+
+    // User templates live in the app database, which may not exist yet
+    let pool = get_pool(app_handle).await.ok();
+    let template = find_template(pool.as_ref(), &args.template).await?;
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let prompt = render_template(&template.body, &template_variables(&segments, date, None));
+
+    let strategy = parse_strategy(&args.strategy)?;
+    let env_key = match args.strategy.as_str() {
+        "gemini" => "GEMINI_API_KEY",
+        _ => "OLLAMA_API_KEY",
+    };
+    let api_key = args.api_key.or_else(|| std::env::var(env_key).ok()).unwrap_or_default();
+    let options = TextGenerationOptions {
+        ollama_base_url: args.ollama_base_url,
+        ollama_model: args.ollama_model,
+        ollama_api_key: api_key.clone(),
+        google_api_key: api_key,
+        gemini_model: args.gemini_model,
+        max_output_tokens: args.max_output_tokens,
+        temperature: args.temperature,
+        ..Default::default()
+    };
+
+    eprintln!("Summarize with template {}... 🔄", template.name);
+    let summary = generate_text(strategy, &options, vec![json!({"role": "user", "content": prompt})]).await?;
+    println!("{}", summary);
+    if let Some(write_path) = args.write {
+        std::fs::write(write_path, summary).context("failed to write summary")?;
+    }
+    Ok(())
+}
+
 pub async fn run(app_handle: &AppHandle) -> Result<()> {
     #[cfg(target_os = "macos")]
     crate::dock::set_dock_visible(false);
@@ -174,6 +427,13 @@ pub async fn run(app_handle: &AppHandle) -> Result<()> {
     #[allow(unused_mut)]
     let mut args = Args::parse();
 
+    if let Some(Command::Summarize(summarize_args)) = args.command {
+        summarize(app_handle, summarize_args).await?;
+        app_handle.cleanup_before_exit();
+        eprintln!("Done ✅");
+        process::exit(0);
+    }
+
     if args.diarize && args.diarize_vad_model.is_none() {
         panic!("Please provide model path with --diarize-vad-model")
     }
@@ -187,10 +447,12 @@ pub async fn run(app_handle: &AppHandle) -> Result<()> {
         server::run(app_handle.clone(), args.host, args.port).await?;
     }
     let lang = language_name_to_whisper_lang(&args.language)?;
+    let dictionary = load_dictionary(app_handle, args.dictionary.as_deref()).await?;
     let options = TranscribeOptions {
         path: args.file.context("file")?,
         lang: Some(lang),
         init_prompt: args.init_prompt,
+        vocabulary: Some(args.vocabulary),
         n_threads: args.n_threads,
         temperature: args.temperature,
         translate: args.translate,
@@ -198,14 +460,62 @@ pub async fn run(app_handle: &AppHandle) -> Result<()> {
         max_text_ctx: args.max_text_ctx,
         word_timestamps: Some(args.word_timestamps),
         max_sentence_len: args.max_sentence_len,
+        vad: Some(args.vad),
+        hallucination_filter: args.hallucination_filter.as_deref().map(|filter| match filter {
+            "drop" => HallucinationFilter::Drop,
+            _ => HallucinationFilter::Flag,
+        }),
+        temperature_fallback: Some(args.temperature_fallback),
+        beam_size: args.beam_size,
+        best_of: args.best_of,
+        temperature_increment: args.temperature_increment,
+        entropy_threshold: args.entropy_threshold,
+        logprob_threshold: args.logprob_threshold,
+        no_speech_threshold: args.no_speech_threshold,
+        suppress_blank: Some(!args.no_suppress_blank),
+        suppress_non_speech_tokens: Some(args.suppress_non_speech_tokens),
+        suppress_tokens: args.suppress_tokens,
+        offset_ms: args.offset_ms,
+        duration_ms: args.duration_ms,
+        no_context: Some(args.no_context),
+        no_cache: Some(args.no_cache),
+        chunk_secs: args.chunk_secs,
+        chunk_overlap_secs: args.chunk_overlap_secs,
+        backend: match args.remote_url {
+            Some(url) => BackendOptions::Remote(RemoteOptions {
+                url,
+                model: args.remote_model,
+                api_key: args.remote_api_key.or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+                max_upload_mb: args.max_upload_mb,
+            }),
+            None => BackendOptions::Local,
+        },
     };
-    let model_path = prepare_model_path(&args.model.context("model")?, app_handle)?;
+    options.validate()?;
 
     eprintln!("Transcribe... 🔄");
     let start = Instant::now(); // Measure start time
-    let ctx = transcribe::create_context(&model_path, None)?;
     #[allow(unused_mut)]
-    let mut transcript = transcribe::transcribe(&ctx, &options, None, None, None, None)?;
+    let mut transcript = match options.backend.clone() {
+        BackendOptions::Remote(remote) => {
+            // The http client blocks, keep it off the async runtime
+            tauri::async_runtime::spawn_blocking(move || TranscribeRequest::new(&options).run(&RemoteBackend::new(remote)?))
+                .await??
+        }
+        BackendOptions::Local => {
+            let model_path = prepare_model_path(&args.model.context("model")?, app_handle)?;
+            let pool = Arc::new(StatePool::new(transcribe::create_context(&model_path, None)?, args.workers));
+            let mut state = pool.acquire(|_| {}, || false)?;
+            let cache = transcript_cache(app_handle, &model_path);
+            TranscribeRequest::new(&options)
+                .cache(cache.as_ref())
+                .state(&mut state)
+                .chunk_workers(&pool, args.workers)
+                .run(pool.context())?
+        }
+    };
+
+    dictionary.apply_to_segments(&mut transcript.segments);
 
     let elapsed = start.elapsed();
     println!(
@@ -230,6 +540,7 @@ pub async fn run(app_handle: &AppHandle) -> Result<()> {
                 "srt" => transcript.as_srt(),
                 "vtt" => transcript.as_vtt(),
                 "txt" => transcript.as_text(),
+                "json" => transcript.as_json()?,
                 _ => {
                     eprintln!("Invalid format specified. Defaulting to SRT format.");
                     transcript.as_srt()
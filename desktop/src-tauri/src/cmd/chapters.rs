@@ -0,0 +1,113 @@
+// src/chapters.rs
+
+use crate::cmd::generation::{parse_strategy, GenerationError};
+use crate::database::{get_pool, load_transcript};
+use eyre::{bail, Result};
+use samwise_text::chapters::{detect_chapters, to_ffmetadata, to_markdown, to_webvtt, Chapter, ChapterOptions, TimedText};
+use samwise_text::text_generation::TextGenerationOptions;
+use sqlx::SqlitePool;
+use vibe_core::transcript::Segment;
+
+fn timed_texts(segments: &[Segment]) -> Vec<TimedText> {
+    segments
+        .iter()
+        .map(|segment| TimedText {
+            start: segment.start,
+            stop: segment.stop,
+            text: segment.text.clone(),
+        })
+        .collect()
+}
+
+async fn stored_chapters(pool: &SqlitePool, file_name: &str) -> Result<Vec<Chapter>> {
+    let rows: Vec<(i64, i64, String)> =
+        sqlx::query_as("SELECT start, stop, title FROM chapter WHERE file_name = ? ORDER BY position")
+            .bind(file_name)
+            .fetch_all(pool)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(start, stop, title)| Chapter { start, stop, title })
+        .collect())
+}
+
+/// Split the stored transcript of a recording into titled chapters, replacing the chapters it had
+#[tauri::command]
+pub async fn generate_chapters(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    chapter_options: ChapterOptions,
+    options: TextGenerationOptions,
+    strategy_str: String,
+) -> Result<Vec<Chapter>, GenerationError> {
+    let strategy = parse_strategy(&strategy_str)?;
+    let pool = get_pool(&app_handle).await?;
+    let segments = load_transcript(&mut *pool.acquire().await?, &file_name).await?;
+    let chapters = detect_chapters(strategy, &options, &chapter_options, &timed_texts(&segments)).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM chapter WHERE file_name = ?")
+        .bind(&file_name)
+        .execute(&mut *tx)
+        .await?;
+    for (position, chapter) in chapters.iter().enumerate() {
+        sqlx::query("INSERT INTO chapter (file_name, position, start, stop, title) VALUES (?, ?, ?, ?, ?)")
+            .bind(&file_name)
+            .bind(position as i64)
+            .bind(chapter.start)
+            .bind(chapter.stop)
+            .bind(&chapter.title)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(chapters)
+}
+
+#[tauri::command]
+pub async fn list_chapters(app_handle: tauri::AppHandle, file_name: String) -> Result<Vec<Chapter>> {
+    let pool = get_pool(&app_handle).await?;
+    stored_chapters(&pool, &file_name).await
+}
+
+/// Rename the chapter at `position`, the titles written by the model are only a first draft
+#[tauri::command]
+pub async fn rename_chapter(app_handle: tauri::AppHandle, file_name: String, position: i64, title: String) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("UPDATE chapter SET title = ? WHERE file_name = ? AND position = ?")
+        .bind(title.trim())
+        .bind(file_name)
+        .bind(position)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_chapters(app_handle: tauri::AppHandle, file_name: String) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM chapter WHERE file_name = ?")
+        .bind(file_name)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+/// Stored chapters of a recording as "vtt" chapters, "md" headings over the transcript or "ffmetadata" chapter markers
+#[tauri::command]
+pub async fn export_chapters(app_handle: tauri::AppHandle, file_name: String, format: String) -> Result<String> {
+    let pool = get_pool(&app_handle).await?;
+    let chapters = stored_chapters(&pool, &file_name).await?;
+    if chapters.is_empty() {
+        bail!("{} has no chapters", file_name)
+    }
+    Ok(match format.as_str() {
+        "vtt" => to_webvtt(&chapters),
+        "md" => {
+            let segments = load_transcript(&mut *pool.acquire().await?, &file_name).await?;
+            to_markdown(&chapters, &timed_texts(&segments))
+        }
+        "ffmetadata" => to_ffmetadata(&chapters),
+        _ => bail!("unknown chapters format {}", format),
+    })
+}
@@ -1,25 +1,188 @@
 // src/chat.rs
 
-use eyre::{eyre, Result};
-use samwise_text::text_generation::{generate_text, TextGenerationOptions, TextGenerationStrategy};
+use crate::cmd::generation::{parse_strategy, GenerationError};
+use crate::database::get_pool;
+use crate::utils::LogError;
+use eyre::{ContextCompat, Result};
+use samwise_text::text_generation::{generate_text_with_usage, TextGenerationOptions};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sqlx::SqlitePool;
+
+const DEFAULT_THREAD_TITLE: &str = "New chat";
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatThread {
+    pub id: i64,
+    pub file_name: String,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatMessage {
+    pub id: i64,
+    pub thread_id: i64,
+    pub role: String,
+    pub content: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub created_at: String,
+}
+
+/// Message to store in a thread. Provider, model and token counts are only known for assistant replies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewChatMessage {
+    pub role: String,
+    pub content: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+}
+
+async fn insert_chat_message(pool: &SqlitePool, thread_id: i64, message: NewChatMessage) -> Result<ChatMessage> {
+    let mut tx = pool.begin().await?;
+    let chat_message: ChatMessage = sqlx::query_as(
+        "INSERT INTO chat_message (thread_id, role, content, provider, model, prompt_tokens, completion_tokens)
+         VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *",
+    )
+    .bind(thread_id)
+    .bind(message.role)
+    .bind(message.content)
+    .bind(message.provider)
+    .bind(message.model)
+    .bind(message.prompt_tokens)
+    .bind(message.completion_tokens)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE chat_thread SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(thread_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(chat_message)
+}
 
 #[tauri::command]
 pub async fn process_chat_message(
+    app_handle: tauri::AppHandle,
     options: TextGenerationOptions,
     messages: Vec<Value>,
     strategy_str: String,
-) -> Result<String, String> {
-    // Convert the strategy string to TextGenerationStrategy
-    let strategy = match strategy_str.as_str() {
-        "ollama" => TextGenerationStrategy::Ollama,
-        "gemini" => TextGenerationStrategy::GoogleGemini,
-        _ => return Err(format!("Invalid text generation strategy: {}", strategy_str)),
+    thread_id: Option<i64>,
+) -> Result<String, GenerationError> {
+    let strategy = parse_strategy(&strategy_str)?;
+
+    // The last user message is the one being sent now. Earlier ones are already stored in the thread.
+    let user_message = messages
+        .iter()
+        .rev()
+        .find(|message| message["role"] == "user")
+        .and_then(|message| message["content"].as_str())
+        .map(|content| content.to_string());
+
+    // The user turn is stored first so it isn't lost when generation fails
+    let pool = match thread_id {
+        Some(thread_id) => {
+            let pool = get_pool(&app_handle).await?;
+            if let Some(content) = user_message {
+                let message = NewChatMessage {
+                    role: "user".into(),
+                    content,
+                    ..Default::default()
+                };
+                insert_chat_message(&pool, thread_id, message).await?;
+            }
+            Some((pool, thread_id))
+        }
+        None => None,
     };
 
     // Generate text using the chosen strategy
-    match generate_text(strategy, &options, messages).await {
-        Ok(response) => Ok(response),
-        Err(e) => Err(e.to_string()),
+    let response = generate_text_with_usage(strategy, &options, messages).await?;
+
+    // The reply is already paid for, failing to store it shouldn't discard it
+    if let Some((pool, thread_id)) = pool {
+        let message = NewChatMessage {
+            role: "assistant".into(),
+            content: response.text.clone(),
+            provider: Some(strategy_str),
+            model: Some(response.model.clone()),
+            prompt_tokens: response.prompt_tokens,
+            completion_tokens: response.completion_tokens,
+        };
+        insert_chat_message(&pool, thread_id, message).await.log_error();
     }
+
+    Ok(response.text)
+}
+
+#[tauri::command]
+pub async fn create_chat_thread(app_handle: tauri::AppHandle, file_name: String, title: Option<String>) -> Result<ChatThread> {
+    let pool = get_pool(&app_handle).await?;
+    let thread = sqlx::query_as("INSERT INTO chat_thread (file_name, title) VALUES (?, ?) RETURNING *")
+        .bind(file_name)
+        .bind(title.unwrap_or_else(|| DEFAULT_THREAD_TITLE.to_string()))
+        .fetch_one(&pool)
+        .await?;
+    Ok(thread)
+}
+
+/// List threads of a recording, most recently active first
+#[tauri::command]
+pub async fn list_chat_threads(app_handle: tauri::AppHandle, file_name: String) -> Result<Vec<ChatThread>> {
+    let pool = get_pool(&app_handle).await?;
+    let threads = sqlx::query_as("SELECT * FROM chat_thread WHERE file_name = ? ORDER BY updated_at DESC, id DESC")
+        .bind(file_name)
+        .fetch_all(&pool)
+        .await?;
+    Ok(threads)
+}
+
+#[tauri::command]
+pub async fn get_chat_messages(app_handle: tauri::AppHandle, thread_id: i64) -> Result<Vec<ChatMessage>> {
+    let pool = get_pool(&app_handle).await?;
+    let messages = sqlx::query_as("SELECT * FROM chat_message WHERE thread_id = ? ORDER BY id")
+        .bind(thread_id)
+        .fetch_all(&pool)
+        .await?;
+    Ok(messages)
+}
+
+#[tauri::command]
+pub async fn append_chat_message(app_handle: tauri::AppHandle, thread_id: i64, message: NewChatMessage) -> Result<ChatMessage> {
+    let pool = get_pool(&app_handle).await?;
+    insert_chat_message(&pool, thread_id, message).await
+}
+
+#[tauri::command]
+pub async fn rename_chat_thread(app_handle: tauri::AppHandle, thread_id: i64, title: String) -> Result<ChatThread> {
+    let pool = get_pool(&app_handle).await?;
+    let thread: Option<ChatThread> =
+        sqlx::query_as("UPDATE chat_thread SET title = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING *")
+            .bind(title)
+            .bind(thread_id)
+            .fetch_optional(&pool)
+            .await?;
+    thread.context(format!("chat thread {} not found", thread_id))
+}
+
+#[tauri::command]
+pub async fn delete_chat_thread(app_handle: tauri::AppHandle, thread_id: i64) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM chat_message WHERE thread_id = ?")
+        .bind(thread_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM chat_thread WHERE id = ?")
+        .bind(thread_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
 }
@@ -0,0 +1,62 @@
+// src/generation.rs
+
+use eyre::{bail, Result};
+use samwise_text::error::TextGenerationError;
+use samwise_text::text_generation::TextGenerationStrategy;
+use serde::Serialize;
+
+/// Error of the commands that call a text generation provider, so the UI can tell a bad API key
+/// or an exhausted quota from other failures. `kind` is the `TextGenerationError` kind, or "other".
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationError {
+    pub kind: &'static str,
+    pub message: String,
+    pub retry_after_secs: Option<u64>,
+}
+
+impl From<eyre::Report> for GenerationError {
+    fn from(report: eyre::Report) -> Self {
+        match report.downcast_ref::<TextGenerationError>() {
+            Some(error) => GenerationError {
+                kind: error.kind(),
+                message: error.to_string(),
+                retry_after_secs: match error {
+                    TextGenerationError::Quota { retry_after_secs, .. } => *retry_after_secs,
+                    _ => None,
+                },
+            },
+            None => GenerationError {
+                kind: "other",
+                message: format!("{:#}", report),
+                retry_after_secs: None,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<sqlx::Error> for GenerationError {
+    fn from(error: sqlx::Error) -> Self {
+        eyre::Report::from(error).into()
+    }
+}
+
+impl From<serde_json::Error> for GenerationError {
+    fn from(error: serde_json::Error) -> Self {
+        eyre::Report::from(error).into()
+    }
+}
+
+/// Convert the strategy string used by the frontend to TextGenerationStrategy
+pub fn parse_strategy(strategy_str: &str) -> Result<TextGenerationStrategy> {
+    match strategy_str {
+        "ollama" => Ok(TextGenerationStrategy::Ollama),
+        "gemini" => Ok(TextGenerationStrategy::GoogleGemini),
+        _ => bail!("Invalid text generation strategy: {}", strategy_str),
+    }
+}
@@ -1,6 +1,6 @@
-use crate::config::{DEAFULT_MODEL_FILENAME, DEAFULT_MODEL_URL, STORE_FILENAME};
-use crate::setup::ModelContext;
-use crate::utils::{get_current_dir, LogError};
+use crate::config::{DEAFULT_MODEL_FILENAME, DEAFULT_MODEL_URL, DEFAULT_MAX_JOBS, STORE_FILENAME};
+use crate::setup::{ModelContext, ModelStatus};
+use crate::utils::{get_current_dir, random_string, LogError};
 use eyre::{bail, eyre, Context, ContextCompat, OptionExt, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -10,6 +10,7 @@ use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
+use std::time::Duration;
 use tauri::{
     window::{ProgressBarState, ProgressBarStatus},
     Manager,
@@ -17,10 +18,25 @@ use tauri::{
 use tauri::{Emitter, Listener, State, Wry};
 use tauri_plugin_store::{with_store, StoreCollection};
 use tokio::sync::Mutex;
+use vibe_core::cache::TranscriptCache;
+use vibe_core::config::BackendOptions;
+use vibe_core::diarize::{DiarizePipeline, SpeakerAssignment};
+use vibe_core::draft::Replacement;
+use vibe_core::model::ModelInfo;
+use vibe_core::pool::PoolStatus;
+use vibe_core::remote::RemoteBackend;
+use vibe_core::transcribe::TranscribeRequest;
 use vibe_core::transcript::Segment;
 use vibe_core::transcript::Transcript;
 pub mod audio;
+pub mod chapters;
 pub mod chat;
+pub mod generation;
+pub mod search;
+pub mod speakers;
+pub mod templates;
+pub mod translate;
+pub mod vocabulary;
 
 /// Return true if there's internet connection
 /// timeout in ms
@@ -142,6 +158,15 @@ pub async fn download_model(app_handle: tauri::AppHandle, url: Option<String>) -
         .download(&download_url, model_path.to_owned(), download_progress_callback)
         .await?;
     set_progress_bar(&app_handle_c, None)?;
+    // Don't keep a cut download or a wrong file, it would fail later when loading the model
+    if !abort_atomic.load(Ordering::Relaxed) {
+        if let Err(error) = vibe_core::model::inspect(&model_path) {
+            std::fs::remove_file(&model_path)
+                .context("failed to remove invalid model")
+                .log_error();
+            return Err(error.wrap_err("downloaded model is invalid"));
+        }
+    }
     Ok(model_path.to_str().context("to_str")?.to_string())
 }
 
@@ -188,11 +213,27 @@ pub async fn download_file(app_handle: tauri::AppHandle, url: String, path: Stri
     Ok(())
 }
 
+/// How speakers are put on the text, see `vibe_core::diarize::DiarizePipeline`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiarizeMode {
+    #[default]
+    PerSegment,
+    Word,
+    Sentence,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiarizeOptions {
+    /// 0 lets vibe_core pick its tuned clustering threshold
     threshold: f32,
+    /// 0 leaves the number of speakers unbounded
     max_speakers: usize,
+    #[serde(default)]
+    min_speakers: usize,
     enabled: bool,
+    #[serde(default)]
+    mode: DiarizeMode,
 }
 
 impl Default for DiarizeOptions {
@@ -201,42 +242,90 @@ impl Default for DiarizeOptions {
             enabled: false,
             threshold: 0.0,
             max_speakers: 0,
+            min_speakers: 0,
+            mode: DiarizeMode::default(),
         }
     }
 }
 
+/// Payload of `abort_transcribe`, every job is aborted when it has no job id
+#[derive(Debug, Default, Deserialize)]
+struct AbortTranscribe {
+    job_id: Option<String>,
+}
+
+/// Payload of `transcribe_waiting`
+#[derive(Debug, Clone, Serialize)]
+struct TranscribeWaiting {
+    job_id: String,
+    /// Jobs that run before this one
+    ahead: usize,
+}
+
+/// `job_id` identifies the job in `transcribe_waiting` and lets `abort_transcribe` stop only this job.
+/// A random one is used when it isn't given.
 #[tauri::command]
 pub async fn transcribe(
     app_handle: tauri::AppHandle,
-    options: vibe_core::config::TranscribeOptions,
+    mut options: vibe_core::config::TranscribeOptions,
     model_context_state: State<'_, Mutex<Option<ModelContext>>>,
     diarize_options: DiarizeOptions,
+    two_pass: Option<bool>,
+    job_id: Option<String>,
 ) -> Result<Transcript> {
-    let model_context = model_context_state.lock().await;
-    if model_context.is_none() {
-        bail!("Please load model first")
-    }
-    let ctx = model_context.as_ref().context("as ref")?;
+    let job_id = job_id.unwrap_or_else(|| random_string(16));
+    // Only held to get the pool, so jobs on the same model run side by side. Remote jobs don't need a model.
+    let (pool, model_path, draft) = if options.backend.is_local() {
+        // Loads it again if it was unloaded while idle
+        let (pool, model_path) = crate::setup::model_pool(&model_context_state).await?;
+        let draft = if two_pass.unwrap_or(false) {
+            crate::setup::draft_model(&model_context_state).await
+        } else {
+            None
+        };
+        (Some(pool), Some(model_path), draft)
+    } else {
+        (None, None, None)
+    };
     let app_handle_c = app_handle.clone();
 
-    let new_segment_callback = move |segment: Segment| {
+    if options.vocabulary.is_none() {
+        options.vocabulary = Some(vocabulary::load_vocabulary(&app_handle).await.unwrap_or_else(|error| {
+            tracing::warn!("failed to load vocabulary: {:?}", error);
+            Vec::new()
+        }));
+    }
+    // Invalid rules are rejected when added, so failing here means the database isn't there yet
+    let dictionary = vocabulary::load_dictionary(&app_handle).await.unwrap_or_else(|error| {
+        tracing::warn!("failed to load replacement rules: {:?}", error);
+        Default::default()
+    });
+    let dictionary_c = dictionary.clone();
+
+    let new_segment_callback = move |mut segment: Segment| {
+        segment.text = dictionary_c.apply(&segment.text);
         app_handle_c
             .clone()
             .emit_to("main", "new_segment", segment)
             .map_err(|e| eyre!("{:?}", e))
             .log_error();
     };
+    // Segments of the selected model replacing the draft of a two pass transcription
+    let app_handle_c = app_handle.clone();
+    let dictionary_c = dictionary.clone();
+    let replace_callback = move |mut replacement: Replacement| {
+        dictionary_c.apply_to_segments(&mut replacement.segments);
+        app_handle_c
+            .emit_to("main", "replace_segments", replacement)
+            .map_err(|e| eyre!("{:?}", e))
+            .log_error();
+    };
     let abort_atomic = Arc::new(AtomicBool::new(false));
     let abort_atomic_c = abort_atomic.clone();
-
-    // allow abort transcription
-    let app_handle_c = app_handle.clone();
-    app_handle.listen("abort_transcribe", move |_| {
-        let _ = set_progress_bar(&app_handle_c, None);
-        abort_atomic_c.store(true, Ordering::Relaxed);
-    });
-
-    let abort_callback = move || abort_atomic.load(Ordering::Relaxed);
+    let abort_callback = move || abort_atomic_c.load(Ordering::Relaxed);
+    // Also lets a job that waits for the model leave the queue
+    let abort_atomic_c = abort_atomic.clone();
+    let abort_waiting = move || abort_atomic_c.load(Ordering::Relaxed);
 
     let app_handle_c = app_handle.clone();
     let app_handle_c1 = app_handle.clone();
@@ -260,23 +349,106 @@ pub async fn transcribe(
             .to_str()
             .ok_or_eyre("tostr")?
             .to_string();
+        let speaker_profiles = speakers::load_speaker_profiles(&app_handle_c1).await.unwrap_or_else(|error| {
+            tracing::warn!("failed to load speaker profiles: {:?}", error);
+            Vec::new()
+        });
         core_diarize_options = Some(vibe_core::transcribe::DiarizeOptions {
             embedding_model_path,
             segment_model_path,
-            max_speakers: diarize_options.max_speakers,
-            threshold: diarize_options.threshold,
+            min_speakers: Some(diarize_options.min_speakers).filter(|n| *n > 0),
+            max_speakers: Some(diarize_options.max_speakers).filter(|n| *n > 0),
+            threshold: if diarize_options.threshold > 0.0 {
+                diarize_options.threshold
+            } else {
+                vibe_core::diarize::DEFAULT_CLUSTER_THRESHOLD
+            },
+            speaker_profiles,
+            pipeline: match diarize_options.mode {
+                DiarizeMode::PerSegment => DiarizePipeline::PerSegment,
+                DiarizeMode::Word | DiarizeMode::Sentence => DiarizePipeline::Overlap,
+            },
+            speaker_assignment: match diarize_options.mode {
+                DiarizeMode::Word => SpeakerAssignment::Word,
+                _ => SpeakerAssignment::Sentence,
+            },
         });
     }
-    let unwind_result = catch_unwind(AssertUnwindSafe(|| {
-        vibe_core::transcribe::transcribe(
-            &ctx.handle,
-            &options,
-            Some(Box::new(progress_callback)),
-            Some(Box::new(new_segment_callback)),
-            Some(Box::new(abort_callback)),
-            core_diarize_options,
-        )
-    }));
+    if let Some(diarize_options) = &core_diarize_options {
+        diarize_options.validate()?;
+    }
+    let cache = model_path.and_then(|model_path| transcript_cache(&app_handle_c1, Path::new(&model_path)));
+
+    // allow abort transcription, removed once the job is done so listeners don't pile up
+    let app_handle_c2 = app_handle_c.clone();
+    let abort_job_id = job_id.clone();
+    let abort_listener = app_handle_c.listen("abort_transcribe", move |event| {
+        let abort: AbortTranscribe = serde_json::from_str::<Option<_>>(event.payload())
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if abort.job_id.is_some_and(|id| id != abort_job_id) {
+            return;
+        }
+        let _ = set_progress_bar(&app_handle_c2, None);
+        abort_atomic.store(true, Ordering::Relaxed);
+    });
+
+    let job_options = options.clone();
+    let unwind_result = tauri::async_runtime::spawn_blocking(move || {
+        let mut options = job_options;
+        let mut state = match pool {
+            Some(ref pool) => {
+                let state = pool.acquire(
+                    |ahead| {
+                        app_handle_c1
+                            .emit_to("main", "transcribe_waiting", TranscribeWaiting { job_id, ahead })
+                            .map_err(|e| eyre!("{:?}", e))
+                            .log_error();
+                    },
+                    abort_waiting,
+                )?;
+                // Jobs running together share the cores
+                if options.n_threads.is_none() {
+                    options.n_threads = Some(pool.threads_per_job());
+                }
+                Some(state)
+            }
+            None => None,
+        };
+        // Created here, the client blocks
+        let remote = match options.backend {
+            BackendOptions::Remote(ref remote) => Some(RemoteBackend::new(remote.clone())?),
+            BackendOptions::Local => None,
+        };
+        let unwind_result = catch_unwind(AssertUnwindSafe(|| {
+            let mut request = TranscribeRequest::new(&options)
+                .on_progress(progress_callback)
+                .on_segment(new_segment_callback)
+                .abort_when(abort_callback)
+                .diarize(core_diarize_options)
+                .cache(cache.as_ref());
+            if let Some(ref draft) = draft {
+                request = request.draft(draft.as_ref()).on_replace(replace_callback);
+            }
+            match (&remote, &pool, state.as_mut()) {
+                (Some(remote), _, _) => request.run(remote),
+                (None, Some(pool), Some(state)) => request
+                    .state(state)
+                    .chunk_workers(pool, pool.status().max_jobs)
+                    .run(pool.context()),
+                _ => bail!("Please load model first"),
+            }
+        }));
+        if let Some(state) = state.filter(|_| unwind_result.is_err()) {
+            // whatever whisper.cpp left in the state isn't trusted by the next job
+            state.discard();
+        }
+        Ok::<_, eyre::Report>(unwind_result)
+    })
+    .await;
+    app_handle_c.unlisten(abort_listener);
+    let unwind_result = unwind_result??;
 
     let _ = set_progress_bar(&app_handle_c, None);
     match unwind_result {
@@ -284,12 +456,126 @@ pub async fn transcribe(
             bail!("transcribe crash: {:?}", error)
         }
         Ok(transcribe_result) => {
-            let transcript = transcribe_result.with_context(|| format!("options: {:?}", options))?;
+            let mut transcript = transcribe_result.with_context(|| format!("options: {:?}", options))?;
+            dictionary.apply_to_segments(&mut transcript.segments);
             Ok(transcript)
         }
     }
 }
 
+fn transcript_cache_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf> {
+    Ok(app_handle.path().app_cache_dir()?.join("transcripts"))
+}
+
+/// Cache of transcripts made with the model at `model_path`, none when it can't be opened
+pub fn transcript_cache(app_handle: &tauri::AppHandle, model_path: &Path) -> Option<TranscriptCache> {
+    transcript_cache_dir(app_handle)
+        .and_then(|dir| TranscriptCache::open(dir, model_path))
+        .map_err(|error| tracing::warn!("transcript cache unavailable: {:?}", error))
+        .ok()
+}
+
+#[tauri::command]
+pub async fn clear_transcript_cache(app_handle: tauri::AppHandle) -> Result<()> {
+    TranscriptCache::clear(&transcript_cache_dir(&app_handle)?)
+}
+
+/// Transcribe `start_ms` to `end_ms` of a recording again and splice the result into its stored transcript.
+/// Returns the updated transcript.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn transcribe_range(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    start_ms: i32,
+    end_ms: i32,
+    mut options: vibe_core::config::TranscribeOptions,
+    model_context_state: State<'_, Mutex<Option<ModelContext>>>,
+    diarize_options: DiarizeOptions,
+    job_id: Option<String>,
+) -> Result<Vec<Segment>> {
+    if start_ms < 0 || end_ms <= start_ms {
+        bail!("invalid range {}ms - {}ms", start_ms, end_ms)
+    }
+    let pool = crate::database::get_pool(&app_handle).await?;
+    let file_path: Option<String> = sqlx::query_scalar("SELECT file_path FROM recording WHERE file_name = ?")
+        .bind(&file_name)
+        .fetch_optional(&pool)
+        .await?;
+    options.path = file_path.with_context(|| format!("recording {} not found", file_name))?;
+    options.offset_ms = Some(start_ms);
+    options.duration_ms = Some(end_ms - start_ms);
+
+    let transcript = transcribe(
+        app_handle.clone(),
+        options,
+        model_context_state,
+        diarize_options,
+        None,
+        job_id,
+    )
+    .await?;
+
+    // An aborted range only replaces the part it got to
+    let end = transcript.resume_from.unwrap_or(end_ms as i64 / 10);
+    let mut tx = pool.begin().await?;
+    let segments = crate::database::load_transcript(&mut tx, &file_name).await?;
+    let segments = vibe_core::transcript::splice_segments(&segments, transcript.segments, start_ms as i64 / 10, end);
+    crate::database::store_transcript(&mut tx, &file_name, &segments).await?;
+    // Segment indices moved, the speaker tables are rebuilt from the transcript when next used
+    sqlx::query("DELETE FROM speaker WHERE file_name = ?")
+        .bind(&file_name)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(segments)
+}
+
+/// Transcribe the rest of a recording whose stored transcript an abort left partial, and append it.
+/// Speakers of the new part are clustered on that audio only, enrolled profiles still match across both.
+/// Returns the whole transcript, partial again if this one is aborted too.
+#[tauri::command]
+pub async fn resume_transcribe(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    mut options: vibe_core::config::TranscribeOptions,
+    model_context_state: State<'_, Mutex<Option<ModelContext>>>,
+    diarize_options: DiarizeOptions,
+    job_id: Option<String>,
+) -> Result<Transcript> {
+    let pool = crate::database::get_pool(&app_handle).await?;
+    let mut connection = pool.acquire().await?;
+    let resume_from = crate::database::load_resume_point(&mut connection, &file_name)
+        .await?
+        .with_context(|| format!("transcript of {} is already complete", file_name))?;
+    let file_path: Option<String> = sqlx::query_scalar("SELECT file_path FROM recording WHERE file_name = ?")
+        .bind(&file_name)
+        .fetch_optional(&mut *connection)
+        .await?;
+    drop(connection);
+    options.path = file_path.with_context(|| format!("recording {} not found", file_name))?;
+    options.offset_ms = Some((resume_from * 10) as i32);
+    options.duration_ms = None;
+
+    let transcript = transcribe(
+        app_handle.clone(),
+        options,
+        model_context_state,
+        diarize_options,
+        None,
+        job_id,
+    )
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let segments = crate::database::load_transcript(&mut tx, &file_name).await?;
+    let segments = vibe_core::transcript::splice_segments(&segments, transcript.segments, resume_from, i64::MAX);
+    crate::database::store_transcript(&mut tx, &file_name, &segments).await?;
+    crate::database::store_resume_point(&mut tx, &file_name, transcript.resume_from).await?;
+    tx.commit().await?;
+    Ok(Transcript { segments, ..transcript })
+}
+
 #[tauri::command]
 pub fn get_path_dst(src: String, suffix: String) -> Result<String> {
     let src = PathBuf::from(src);
@@ -371,33 +657,81 @@ pub fn is_avx2_enabled() -> bool {
 }
 
 #[tauri::command]
-pub async fn load_model(app_handle: tauri::AppHandle, model_path: String, gpu_device: Option<i32>) -> Result<String> {
+pub async fn load_model(
+    app_handle: tauri::AppHandle,
+    model_path: String,
+    gpu_device: Option<i32>,
+    max_jobs: Option<usize>,
+    idle_timeout_mins: Option<u64>,
+    draft_model_path: Option<String>,
+) -> Result<String> {
     let model_context_state: State<'_, Mutex<Option<ModelContext>>> = app_handle.state();
     let mut state_guard = model_context_state.lock().await;
-    if let Some(state) = state_guard.as_ref() {
+    // 0 keeps the model loaded
+    let idle_timeout = idle_timeout_mins
+        .filter(|mins| *mins > 0)
+        .map(|mins| Duration::from_secs(mins * 60));
+    if let Some(state) = state_guard.as_mut() {
         // check if new path is different
         if model_path != state.path || gpu_device != state.gpu_device {
             tracing::debug!("model path or gpu device changed. reloading");
-            // reload
-            let context = vibe_core::transcribe::create_context(Path::new(&model_path), gpu_device)?;
-            *state_guard = Some(ModelContext {
-                path: model_path.clone(),
-                handle: context,
-                gpu_device,
-            });
+            // reload, jobs still running keep the previous model until they finish
+            let max_jobs = max_jobs.unwrap_or(state.max_jobs);
+            *state_guard = Some(ModelContext::load(model_path.clone(), gpu_device, max_jobs, idle_timeout)?);
+        } else {
+            if let Some(max_jobs) = max_jobs {
+                state.set_max_jobs(max_jobs);
+            }
+            state.idle_timeout = idle_timeout;
         }
     } else {
         tracing::debug!("loading model first time");
-        let context = vibe_core::transcribe::create_context(Path::new(&model_path), gpu_device)?;
-        *state_guard = Some(ModelContext {
-            path: model_path.clone(),
-            handle: context,
-            gpu_device,
-        });
+        let max_jobs = max_jobs.unwrap_or(DEFAULT_MAX_JOBS);
+        *state_guard = Some(ModelContext::load(model_path.clone(), gpu_device, max_jobs, idle_timeout)?);
+    }
+    if let Some(state) = state_guard.as_mut() {
+        // Checked now, its load errors would otherwise only show in the logs
+        if let Some(ref draft_model_path) = draft_model_path {
+            vibe_core::model::inspect(Path::new(draft_model_path)).context("invalid draft model")?;
+        }
+        state.set_draft_path(draft_model_path);
     }
     Ok(model_path)
 }
 
+/// Concurrency limit and queue of the loaded model, None when no model is loaded
+#[tauri::command]
+pub async fn get_transcribe_queue(model_context_state: State<'_, Mutex<Option<ModelContext>>>) -> Result<Option<PoolStatus>> {
+    let model_context = model_context_state.lock().await;
+    Ok(model_context
+        .as_ref()
+        .and_then(|ctx| ctx.loaded_pool())
+        .map(|pool| pool.status()))
+}
+
+/// The chosen model, whether it's loaded and the memory it takes. None when no model was chosen.
+#[tauri::command]
+pub async fn get_model_status(model_context_state: State<'_, Mutex<Option<ModelContext>>>) -> Result<Option<ModelStatus>> {
+    let model_context = model_context_state.lock().await;
+    Ok(model_context.as_ref().map(|ctx| ctx.status()))
+}
+
+/// Check a model file and read its header, without loading it
+#[tauri::command]
+pub async fn get_model_info(model_path: String) -> Result<ModelInfo> {
+    vibe_core::model::inspect(Path::new(&model_path))
+}
+
+/// Free the model's memory now, the next transcription loads it again
+#[tauri::command]
+pub async fn unload_model(model_context_state: State<'_, Mutex<Option<ModelContext>>>) -> Result<()> {
+    let mut model_context = model_context_state.lock().await;
+    if let Some(ctx) = model_context.as_mut() {
+        ctx.unload();
+    }
+    Ok(())
+}
+
 #[tauri::command]
 pub fn is_portable() -> bool {
     env!("WINDOWS_PORTABLE") == "1"
@@ -0,0 +1,264 @@
+// src/search.rs
+
+use crate::cmd::generation::{parse_strategy, GenerationError};
+use crate::database::{get_pool, load_transcript};
+use eyre::{ContextCompat, Result};
+use samwise_text::embeddings::{cosine_similarity, embedding_model_name, generate_embeddings};
+use samwise_text::text_generation::{TextGenerationOptions, TextGenerationStrategy};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use vibe_core::transcript::Segment;
+
+/// Soft limit of characters per indexed chunk. Chunks always end on a segment boundary.
+const CHUNK_MAX_CHARS: usize = 800;
+/// How many candidates each ranker contributes before fusion
+const CANDIDATES_PER_RANKER: usize = 50;
+/// Reciprocal rank fusion constant
+const RRF_K: f64 = 60.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    pub file_name: String,
+    pub recording_name: String,
+    /// Chunk start, in the same units as transcript segments
+    pub start: i64,
+    /// Chunk stop, in the same units as transcript segments
+    pub stop: i64,
+    pub text: String,
+    pub score: f64,
+}
+
+struct Chunk {
+    start: i64,
+    stop: i64,
+    text: String,
+}
+
+fn chunk_segments(segments: &[Segment]) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut current: Option<Chunk> = None;
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let text = match segment.speaker {
+            Some(ref speaker) => format!("{}: {}", speaker, text),
+            None => text.to_string(),
+        };
+        match current.as_mut() {
+            Some(chunk) if chunk.text.len() + text.len() < CHUNK_MAX_CHARS => {
+                chunk.text.push(' ');
+                chunk.text.push_str(&text);
+                chunk.stop = segment.stop;
+            }
+            _ => {
+                if let Some(chunk) = current.take() {
+                    chunks.push(chunk);
+                }
+                current = Some(Chunk {
+                    start: segment.start,
+                    stop: segment.stop,
+                    text,
+                });
+            }
+        }
+    }
+    if let Some(chunk) = current {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+pub fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Build a FTS5 query which matches any of the words, without exposing the FTS syntax to the user
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"", term))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
+
+pub async fn index_transcript(
+    pool: &SqlitePool,
+    file_name: &str,
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+) -> Result<usize> {
+    let segments = load_transcript(&mut *pool.acquire().await?, file_name).await?;
+    let chunks = chunk_segments(&segments);
+
+    // Keyword search still works without embeddings, so don't fail the whole index
+    let texts: Vec<String> = chunks.iter().map(|c| c.text.clone()).collect();
+    let embeddings = match generate_embeddings(strategy, options, &texts).await {
+        Ok(embeddings) => Some(embeddings),
+        Err(error) => {
+            tracing::error!("failed to embed chunks of {}: {:?}", file_name, error);
+            None
+        }
+    };
+    let model = embedding_model_name(strategy, options);
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM transcript_chunk WHERE file_name = ?")
+        .bind(file_name)
+        .execute(&mut *tx)
+        .await?;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let embedding = embeddings.as_ref().map(|e| encode_embedding(&e[i]));
+        sqlx::query(
+            "INSERT INTO transcript_chunk (file_name, chunk_index, start, stop, text, embedding, embedding_model)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(file_name)
+        .bind(i as i64)
+        .bind(chunk.start)
+        .bind(chunk.stop)
+        .bind(&chunk.text)
+        .bind(embedding)
+        .bind(embeddings.as_ref().map(|_| model.as_str()))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    tracing::debug!("indexed {} chunks of {}", chunks.len(), file_name);
+    Ok(chunks.len())
+}
+
+pub async fn search(
+    pool: &SqlitePool,
+    query: &str,
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+    limit: usize,
+) -> Result<Vec<SearchHit>> {
+    let mut scores: HashMap<i64, f64> = HashMap::new();
+
+    // Keyword ranking
+    if let Some(fts_query) = fts_query(query) {
+        let keyword_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT rowid FROM transcript_chunk_fts WHERE transcript_chunk_fts MATCH ? ORDER BY bm25(transcript_chunk_fts) LIMIT ?",
+        )
+        .bind(fts_query)
+        .bind(CANDIDATES_PER_RANKER as i64)
+        .fetch_all(pool)
+        .await?;
+        for (rank, id) in keyword_ids.into_iter().enumerate() {
+            *scores.entry(id).or_default() += 1.0 / (RRF_K + rank as f64 + 1.0);
+        }
+    }
+
+    // Semantic ranking
+    match generate_embeddings(strategy, options, &[query.to_string()]).await {
+        Ok(query_embeddings) => {
+            let query_embedding = query_embeddings.first().context("missing query embedding")?;
+            let rows: Vec<(i64, Vec<u8>)> =
+                sqlx::query_as("SELECT id, embedding FROM transcript_chunk WHERE embedding IS NOT NULL AND embedding_model = ?")
+                    .bind(embedding_model_name(strategy, options))
+                    .fetch_all(pool)
+                    .await?;
+            let mut similarities: Vec<(i64, f32)> = rows
+                .into_iter()
+                .map(|(id, embedding)| (id, cosine_similarity(query_embedding, &decode_embedding(&embedding))))
+                .collect();
+            similarities.sort_by(|a, b| b.1.total_cmp(&a.1));
+            for (rank, (id, _)) in similarities.into_iter().take(CANDIDATES_PER_RANKER).enumerate() {
+                *scores.entry(id).or_default() += 1.0 / (RRF_K + rank as f64 + 1.0);
+            }
+        }
+        Err(error) => {
+            tracing::error!("failed to embed search query, using keyword search only: {:?}", error);
+        }
+    }
+
+    let mut ranked: Vec<(i64, f64)> = scores.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut hits = Vec::new();
+    for (id, score) in ranked.into_iter().take(limit) {
+        let (file_name, recording_name, start, stop, text): (String, String, i64, i64, String) = sqlx::query_as(
+            "SELECT c.file_name, COALESCE(r.pretty_name, r.name, c.file_name), c.start, c.stop, c.text
+             FROM transcript_chunk c LEFT JOIN recording r ON r.file_name = c.file_name
+             WHERE c.id = ?",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+        hits.push(SearchHit {
+            file_name,
+            recording_name,
+            start,
+            stop,
+            text,
+            score,
+        });
+    }
+    Ok(hits)
+}
+
+/// Index (or re-index) a single recording transcript for search
+#[tauri::command]
+pub async fn index_recording(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    options: TextGenerationOptions,
+    strategy_str: String,
+) -> Result<usize, GenerationError> {
+    let strategy = parse_strategy(&strategy_str)?;
+    let pool = get_pool(&app_handle).await?;
+    Ok(index_transcript(&pool, &file_name, strategy, &options).await?)
+}
+
+/// Index every transcribed recording which isn't indexed yet, or whose transcript changed since
+#[tauri::command]
+pub async fn index_all_recordings(
+    app_handle: tauri::AppHandle,
+    options: TextGenerationOptions,
+    strategy_str: String,
+) -> Result<usize, GenerationError> {
+    let strategy = parse_strategy(&strategy_str)?;
+    let pool = get_pool(&app_handle).await?;
+    let file_names: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT file_name FROM recording_insights
+         WHERE transcription IS NOT NULL AND file_name NOT IN (SELECT file_name FROM transcript_chunk)",
+    )
+    .fetch_all(&pool)
+    .await?;
+    let mut indexed = 0;
+    for file_name in file_names {
+        indexed += index_transcript(&pool, &file_name, strategy, &options).await?;
+    }
+    Ok(indexed)
+}
+
+/// Search all indexed recordings, combining keyword and semantic ranking
+#[tauri::command]
+pub async fn search_recordings(
+    app_handle: tauri::AppHandle,
+    query: String,
+    options: TextGenerationOptions,
+    strategy_str: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, GenerationError> {
+    let strategy = parse_strategy(&strategy_str)?;
+    let pool = get_pool(&app_handle).await?;
+    Ok(search(&pool, &query, strategy, &options, limit.unwrap_or(10)).await?)
+}
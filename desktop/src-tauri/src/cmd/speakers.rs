@@ -0,0 +1,389 @@
+// src/speakers.rs
+
+use crate::cmd::get_models_folder;
+use crate::cmd::search::{decode_embedding, encode_embedding};
+use crate::database::{get_pool, load_transcript, store_transcript};
+use eyre::{bail, eyre, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::path::PathBuf;
+use vibe_core::diarize::{average_embedding, compute_speaker_embedding, SpeakerProfile};
+use vibe_core::transcript::Segment;
+
+/// Enrolled speaker as shown to the user, without the embedding
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SpeakerProfileInfo {
+    pub id: i64,
+    pub name: String,
+    pub sample_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const PROFILE_COLUMNS: &str = "id, name, sample_count, created_at, updated_at";
+
+/// Profiles used to seed diarization. Returns nothing until the database exists.
+pub async fn load_speaker_profiles(app_handle: &tauri::AppHandle) -> Result<Vec<SpeakerProfile>> {
+    let pool = get_pool(app_handle).await?;
+    let rows: Vec<(String, Vec<u8>)> = sqlx::query_as("SELECT name, embedding FROM speaker_profile")
+        .fetch_all(&pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(name, embedding)| SpeakerProfile {
+            name,
+            embedding: decode_embedding(&embedding),
+        })
+        .collect())
+}
+
+/// Enroll a speaker from one or more sample clips.
+/// Enrolling an existing name adds the clips to its profile.
+#[tauri::command]
+pub async fn enroll_speaker_profile(
+    app_handle: tauri::AppHandle,
+    name: String,
+    paths: Vec<PathBuf>,
+) -> Result<SpeakerProfileInfo> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        bail!("speaker name can't be empty")
+    }
+    if paths.is_empty() {
+        bail!("at least one sample clip is required")
+    }
+    let models_folder = get_models_folder(app_handle.clone())?;
+    let segment_model_path = models_folder.join(crate::config::SEGMENT_MODEL_FILENAME);
+    let embedding_model_path = models_folder.join(crate::config::EMBEDDING_MODEL_FILENAME);
+    if !segment_model_path.exists() || !embedding_model_path.exists() {
+        bail!("speaker recognition models are not downloaded")
+    }
+
+    let sample_count = paths.len() as i64;
+    let embedding = tokio::task::spawn_blocking(move || -> Result<Vec<f32>> {
+        let segment_model_path = segment_model_path.to_string_lossy();
+        let embedding_model_path = embedding_model_path.to_string_lossy();
+        let embeddings = paths
+            .iter()
+            .map(|path| compute_speaker_embedding(path, &segment_model_path, &embedding_model_path))
+            .collect::<Result<Vec<_>>>()?;
+        average_embedding(&embeddings).ok_or_else(|| eyre!("no embeddings computed"))
+    })
+    .await??;
+
+    let pool = get_pool(&app_handle).await?;
+    let existing: Option<(Vec<u8>, i64)> = sqlx::query_as("SELECT embedding, sample_count FROM speaker_profile WHERE name = ?")
+        .bind(&name)
+        .fetch_optional(&pool)
+        .await?;
+    let (embedding, sample_count) = match existing {
+        Some((previous, previous_count)) => {
+            // Weighted by clip count so every clip contributes equally to the profile
+            let previous = decode_embedding(&previous);
+            let total = (previous_count + sample_count) as f32;
+            let merged = previous
+                .iter()
+                .zip(&embedding)
+                .map(|(a, b)| (a * previous_count as f32 + b * sample_count as f32) / total)
+                .collect::<Vec<f32>>();
+            (merged, previous_count + sample_count)
+        }
+        None => (embedding, sample_count),
+    };
+
+    let profile: SpeakerProfileInfo = sqlx::query_as(&format!(
+        "INSERT INTO speaker_profile (name, embedding, sample_count) VALUES (?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET embedding = excluded.embedding, sample_count = excluded.sample_count,
+         updated_at = CURRENT_TIMESTAMP
+         RETURNING {}",
+        PROFILE_COLUMNS
+    ))
+    .bind(&name)
+    .bind(encode_embedding(&embedding))
+    .bind(sample_count)
+    .fetch_one(&pool)
+    .await?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub async fn list_speaker_profiles(app_handle: tauri::AppHandle) -> Result<Vec<SpeakerProfileInfo>> {
+    let pool = get_pool(&app_handle).await?;
+    let profiles = sqlx::query_as(&format!("SELECT {} FROM speaker_profile ORDER BY name", PROFILE_COLUMNS))
+        .fetch_all(&pool)
+        .await?;
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub async fn rename_speaker_profile(app_handle: tauri::AppHandle, id: i64, name: String) -> Result<SpeakerProfileInfo> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        bail!("speaker name can't be empty")
+    }
+    let pool = get_pool(&app_handle).await?;
+    let profile: Option<SpeakerProfileInfo> = sqlx::query_as(&format!(
+        "UPDATE speaker_profile SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING {}",
+        PROFILE_COLUMNS
+    ))
+    .bind(name)
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?;
+    profile.ok_or_else(|| eyre!("speaker profile {} not found", id))
+}
+
+#[tauri::command]
+pub async fn delete_speaker_profile(app_handle: tauri::AppHandle, id: i64) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM speaker_profile WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+/// Speaker of a single recording. `label` is what diarization assigned, `name` is what the user sees.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RecordingSpeaker {
+    pub id: i64,
+    pub file_name: String,
+    pub label: String,
+    pub name: String,
+    pub segment_count: i64,
+}
+
+/// Hash of the transcript the speaker tables of a recording were built from.
+/// The frontend stores transcripts without going through Rust, so a changed hash is how a new transcript is noticed.
+fn transcript_hash(segments: &[Segment]) -> Result<String> {
+    Ok(sha256::digest(serde_json::to_string(segments)?))
+}
+
+async fn store_speaker_source(connection: &mut SqliteConnection, file_name: &str, segments: &[Segment]) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO speaker_source (file_name, transcript_hash) VALUES (?, ?)
+         ON CONFLICT (file_name) DO UPDATE SET transcript_hash = excluded.transcript_hash",
+    )
+    .bind(file_name)
+    .bind(transcript_hash(segments)?)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+/// Create the speaker tables of a recording from its stored transcript,
+/// unless they were built from this very transcript. Returns the transcript.
+async fn ensure_recording_speakers(connection: &mut SqliteConnection, file_name: &str) -> Result<Vec<Segment>> {
+    let segments = load_transcript(&mut *connection, file_name).await?;
+    let source: Option<String> = sqlx::query_scalar("SELECT transcript_hash FROM speaker_source WHERE file_name = ?")
+        .bind(file_name)
+        .fetch_optional(&mut *connection)
+        .await?;
+    if source.as_deref() == Some(transcript_hash(&segments)?.as_str()) {
+        return Ok(segments);
+    }
+
+    // The transcript was replaced since, start over from its labels.
+    // Renamed and merged speakers were written into it, so they are kept.
+    tracing::debug!("rebuilding speakers of {}", file_name);
+    sqlx::query("DELETE FROM speaker WHERE file_name = ?")
+        .bind(file_name)
+        .execute(&mut *connection)
+        .await?;
+    for (index, segment) in segments.iter().enumerate() {
+        let Some(label) = segment.speaker.as_deref() else {
+            continue;
+        };
+        let speaker_id: i64 = sqlx::query_scalar(
+            "INSERT INTO speaker (file_name, label, name) VALUES (?, ?, ?)
+             ON CONFLICT(file_name, label) DO UPDATE SET label = excluded.label
+             RETURNING id",
+        )
+        .bind(file_name)
+        .bind(label)
+        .bind(label)
+        .fetch_one(&mut *connection)
+        .await?;
+        sqlx::query("INSERT INTO segment_speaker (file_name, segment_index, speaker_id, confidence) VALUES (?, ?, ?, ?)")
+            .bind(file_name)
+            .bind(index as i64)
+            .bind(speaker_id)
+            .bind(segment.speaker_confidence)
+            .execute(&mut *connection)
+            .await?;
+    }
+    store_speaker_source(&mut *connection, file_name, &segments).await?;
+    Ok(segments)
+}
+
+/// Write the speaker names back into the stored transcript, so exports and summaries use them
+async fn sync_transcript_speakers(
+    connection: &mut SqliteConnection,
+    file_name: &str,
+    mut segments: Vec<Segment>,
+) -> Result<Vec<Segment>> {
+    let assignments: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT ss.segment_index, s.name FROM segment_speaker ss JOIN speaker s ON s.id = ss.speaker_id
+         WHERE ss.file_name = ?",
+    )
+    .bind(file_name)
+    .fetch_all(&mut *connection)
+    .await?;
+    for (index, name) in assignments {
+        if let Some(segment) = segments.get_mut(index as usize) {
+            segment.speaker = Some(name);
+        }
+    }
+    sqlx::query("DELETE FROM speaker WHERE file_name = ? AND id NOT IN (SELECT speaker_id FROM segment_speaker)")
+        .bind(file_name)
+        .execute(&mut *connection)
+        .await?;
+    store_transcript(&mut *connection, file_name, &segments).await?;
+    store_speaker_source(&mut *connection, file_name, &segments).await?;
+    Ok(segments)
+}
+
+async fn find_recording_speaker(connection: &mut SqliteConnection, file_name: &str, speaker_id: i64) -> Result<(String, String)> {
+    let speaker: Option<(String, String)> = sqlx::query_as("SELECT label, name FROM speaker WHERE id = ? AND file_name = ?")
+        .bind(speaker_id)
+        .bind(file_name)
+        .fetch_optional(connection)
+        .await?;
+    speaker.ok_or_else(|| eyre!("speaker {} not found in {}", speaker_id, file_name))
+}
+
+#[tauri::command]
+pub async fn list_recording_speakers(app_handle: tauri::AppHandle, file_name: String) -> Result<Vec<RecordingSpeaker>> {
+    let pool = get_pool(&app_handle).await?;
+    let mut tx = pool.begin().await?;
+    ensure_recording_speakers(&mut tx, &file_name).await?;
+    let speakers = sqlx::query_as(
+        "SELECT s.id, s.file_name, s.label, s.name, COUNT(ss.segment_index) AS segment_count
+         FROM speaker s LEFT JOIN segment_speaker ss ON ss.speaker_id = s.id
+         WHERE s.file_name = ? GROUP BY s.id ORDER BY MIN(ss.segment_index)",
+    )
+    .bind(&file_name)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(speakers)
+}
+
+/// Rename a speaker in every segment of a recording. Returns the updated transcript.
+#[tauri::command]
+pub async fn rename_recording_speaker(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    speaker_id: i64,
+    name: String,
+) -> Result<Vec<Segment>> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        bail!("speaker name can't be empty")
+    }
+    let pool = get_pool(&app_handle).await?;
+    let mut tx = pool.begin().await?;
+    let segments = ensure_recording_speakers(&mut tx, &file_name).await?;
+    find_recording_speaker(&mut tx, &file_name, speaker_id).await?;
+    let duplicate: Option<i64> = sqlx::query_scalar("SELECT id FROM speaker WHERE file_name = ? AND name = ? AND id != ?")
+        .bind(&file_name)
+        .bind(&name)
+        .bind(speaker_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if duplicate.is_some() {
+        bail!("{} is already a speaker of this recording, merge them instead", name)
+    }
+    sqlx::query("UPDATE speaker SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(&name)
+        .bind(speaker_id)
+        .execute(&mut *tx)
+        .await?;
+    let segments = sync_transcript_speakers(&mut tx, &file_name, segments).await?;
+    tx.commit().await?;
+    Ok(segments)
+}
+
+/// Merge `source_id` into `target_id`, for diarized speakers that are really one person.
+/// The source speaker is removed once it has no segments left.
+/// Returns the updated transcript.
+#[tauri::command]
+pub async fn merge_recording_speakers(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    source_id: i64,
+    target_id: i64,
+) -> Result<Vec<Segment>> {
+    if source_id == target_id {
+        bail!("can't merge a speaker with itself")
+    }
+    let pool = get_pool(&app_handle).await?;
+    let mut tx = pool.begin().await?;
+    let segments = ensure_recording_speakers(&mut tx, &file_name).await?;
+    find_recording_speaker(&mut tx, &file_name, source_id).await?;
+    find_recording_speaker(&mut tx, &file_name, target_id).await?;
+    sqlx::query("UPDATE segment_speaker SET speaker_id = ? WHERE speaker_id = ?")
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+    let segments = sync_transcript_speakers(&mut tx, &file_name, segments).await?;
+    tx.commit().await?;
+    Ok(segments)
+}
+
+/// Move the segments of a speaker starting at `from_segment` to the speaker called `name`,
+/// which is created if the recording doesn't have it yet. Returns the updated transcript.
+#[tauri::command]
+pub async fn split_recording_speaker(
+    app_handle: tauri::AppHandle,
+    file_name: String,
+    speaker_id: i64,
+    from_segment: i64,
+    name: String,
+) -> Result<Vec<Segment>> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        bail!("speaker name can't be empty")
+    }
+    let pool = get_pool(&app_handle).await?;
+    let mut tx = pool.begin().await?;
+    let segments = ensure_recording_speakers(&mut tx, &file_name).await?;
+    let (_, current_name) = find_recording_speaker(&mut tx, &file_name, speaker_id).await?;
+    if current_name == name {
+        bail!("{} already speaks these segments", name)
+    }
+    let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM speaker WHERE file_name = ? AND name = ?")
+        .bind(&file_name)
+        .bind(&name)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let new_speaker_id = match existing {
+        Some(id) => id,
+        None => {
+            // The name may already be the label of another speaker, so the new one gets a label of its own.
+            // A split label's number is never above the id of its own row, so one past the largest id is free.
+            let next_id: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) + 1 FROM speaker")
+                .fetch_one(&mut *tx)
+                .await?;
+            sqlx::query_scalar("INSERT INTO speaker (file_name, label, name) VALUES (?, ?, ?) RETURNING id")
+                .bind(&file_name)
+                .bind(format!("split-{}", next_id))
+                .bind(&name)
+                .fetch_one(&mut *tx)
+                .await?
+        }
+    };
+    let moved = sqlx::query("UPDATE segment_speaker SET speaker_id = ? WHERE speaker_id = ? AND segment_index >= ?")
+        .bind(new_speaker_id)
+        .bind(speaker_id)
+        .bind(from_segment)
+        .execute(&mut *tx)
+        .await?;
+    if moved.rows_affected() == 0 {
+        bail!("speaker {} has no segments from segment {}", current_name, from_segment)
+    }
+    let segments = sync_transcript_speakers(&mut tx, &file_name, segments).await?;
+    tx.commit().await?;
+    Ok(segments)
+}
@@ -0,0 +1,200 @@
+// src/templates.rs
+
+use crate::database::get_pool;
+use eyre::{bail, eyre, Result};
+use samwise_text::prompt_templates::{builtin_templates, find_builtin_template, render_template, PromptTemplate};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use vibe_core::transcript::Segment;
+
+const USER_TEMPLATE_ID_PREFIX: &str = "user-";
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+struct UserTemplateRow {
+    id: i64,
+    name: String,
+    description: Option<String>,
+    body: String,
+}
+
+impl From<UserTemplateRow> for PromptTemplate {
+    fn from(row: UserTemplateRow) -> Self {
+        PromptTemplate {
+            id: format!("{}{}", USER_TEMPLATE_ID_PREFIX, row.id),
+            name: row.name,
+            description: row.description.unwrap_or_default(),
+            body: row.body,
+            builtin: false,
+        }
+    }
+}
+
+fn parse_user_template_id(id: &str) -> Result<i64> {
+    id.strip_prefix(USER_TEMPLATE_ID_PREFIX)
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| eyre!("{} is not a user template", id))
+}
+
+fn format_duration(centiseconds: i64) -> String {
+    let seconds = centiseconds / 100;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
+
+/// Transcript as the model sees it: one line per segment with its start time and speaker
+pub fn transcript_as_prompt_text(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| {
+            let speaker = segment.speaker.as_ref().map(|s| format!("{}: ", s)).unwrap_or_default();
+            format!("[{}] {}{}", format_duration(segment.start), speaker, segment.text.trim())
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn template_variables(segments: &[Segment], date: String, title: Option<String>) -> HashMap<String, String> {
+    let mut speakers: Vec<&str> = Vec::new();
+    for speaker in segments.iter().filter_map(|s| s.speaker.as_deref()) {
+        if !speakers.contains(&speaker) {
+            speakers.push(speaker);
+        }
+    }
+    let duration = segments.iter().map(|s| s.stop).max().unwrap_or(0);
+
+    let mut variables = HashMap::new();
+    variables.insert("transcript".to_string(), transcript_as_prompt_text(segments));
+    variables.insert(
+        "speakers".to_string(),
+        if speakers.is_empty() {
+            "unknown speakers".to_string()
+        } else {
+            speakers.join(", ")
+        },
+    );
+    variables.insert("date".to_string(), date);
+    variables.insert("duration".to_string(), format_duration(duration));
+    if let Some(title) = title {
+        variables.insert("title".to_string(), title);
+    }
+    variables
+}
+
+pub async fn list_templates(pool: &SqlitePool) -> Result<Vec<PromptTemplate>> {
+    let rows: Vec<UserTemplateRow> = sqlx::query_as("SELECT id, name, description, body FROM prompt_template ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+    let mut templates = builtin_templates();
+    templates.extend(rows.into_iter().map(PromptTemplate::from));
+    Ok(templates)
+}
+
+/// Find a template by id, or a user template by name
+pub async fn find_template(pool: Option<&SqlitePool>, id_or_name: &str) -> Result<PromptTemplate> {
+    if let Some(template) = find_builtin_template(id_or_name) {
+        return Ok(template);
+    }
+    let Some(pool) = pool else {
+        bail!("template {} not found", id_or_name)
+    };
+    let row: Option<UserTemplateRow> =
+        sqlx::query_as("SELECT id, name, description, body FROM prompt_template WHERE name = ? OR id = ?")
+            .bind(id_or_name)
+            .bind(parse_user_template_id(id_or_name).ok())
+            .fetch_optional(pool)
+            .await?;
+    row.map(PromptTemplate::from)
+        .ok_or_else(|| eyre!("template {} not found", id_or_name))
+}
+
+#[tauri::command]
+pub async fn list_prompt_templates(app_handle: tauri::AppHandle) -> Result<Vec<PromptTemplate>> {
+    let pool = get_pool(&app_handle).await?;
+    list_templates(&pool).await
+}
+
+#[tauri::command]
+pub async fn create_prompt_template(
+    app_handle: tauri::AppHandle,
+    name: String,
+    description: Option<String>,
+    body: String,
+) -> Result<PromptTemplate> {
+    if find_builtin_template(&name).is_some() {
+        bail!("{} is the name of a built-in template", name)
+    }
+    let pool = get_pool(&app_handle).await?;
+    let row: UserTemplateRow = sqlx::query_as(
+        "INSERT INTO prompt_template (name, description, body) VALUES (?, ?, ?) RETURNING id, name, description, body",
+    )
+    .bind(name)
+    .bind(description)
+    .bind(body)
+    .fetch_one(&pool)
+    .await?;
+    Ok(row.into())
+}
+
+#[tauri::command]
+pub async fn update_prompt_template(
+    app_handle: tauri::AppHandle,
+    id: String,
+    name: String,
+    description: Option<String>,
+    body: String,
+) -> Result<PromptTemplate> {
+    let id = parse_user_template_id(&id)?;
+    let pool = get_pool(&app_handle).await?;
+    let row: Option<UserTemplateRow> = sqlx::query_as(
+        "UPDATE prompt_template SET name = ?, description = ?, body = ?, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ? RETURNING id, name, description, body",
+    )
+    .bind(name)
+    .bind(description)
+    .bind(body)
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?;
+    row.map(PromptTemplate::from)
+        .ok_or_else(|| eyre!("template {} not found", id))
+}
+
+#[tauri::command]
+pub async fn delete_prompt_template(app_handle: tauri::AppHandle, id: String) -> Result<()> {
+    let id = parse_user_template_id(&id)?;
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM prompt_template WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+/// Fill a template body with the variables of a transcript.
+/// Date and title come from the recording when `file_name` is provided.
+#[tauri::command]
+pub async fn render_prompt_template(
+    app_handle: tauri::AppHandle,
+    body: String,
+    segments: Vec<Segment>,
+    file_name: Option<String>,
+) -> Result<String> {
+    let mut date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let mut title = None;
+    if let Some(file_name) = file_name {
+        let pool = get_pool(&app_handle).await?;
+        let recording: Option<(String, String)> = sqlx::query_as(
+            "SELECT COALESCE(pretty_name, name), COALESCE(DATE(created_at), '') FROM recording WHERE file_name = ?",
+        )
+        .bind(file_name)
+        .fetch_optional(&pool)
+        .await?;
+        if let Some((recording_title, recording_date)) = recording {
+            title = Some(recording_title);
+            if !recording_date.is_empty() {
+                date = recording_date;
+            }
+        }
+    }
+    Ok(render_template(&body, &template_variables(&segments, date, title)))
+}
@@ -0,0 +1,111 @@
+// src/translate.rs
+
+use crate::cmd::generation::{parse_strategy, GenerationError};
+use crate::database::get_pool;
+use crate::utils::LogError;
+use eyre::{eyre, Result};
+use samwise_text::text_generation::TextGenerationOptions;
+use samwise_text::translation::{translate_segments, TranslationOptions};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tauri::Emitter;
+use vibe_core::transcript::{Segment, Transcript};
+
+/// Translation of a recording's transcript, kept next to the original
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TranscriptTrack {
+    pub id: i64,
+    pub file_name: String,
+    pub language: String,
+    /// Segments as JSON, the same boundaries and timestamps as the original transcript
+    pub transcription: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Translate `segments` to `translation.target_language` with the chosen text generation model.
+/// When `file_name` is given the translation is stored as a track of that recording, replacing one in the same language.
+#[tauri::command]
+pub async fn translate_transcript(
+    app_handle: tauri::AppHandle,
+    segments: Vec<Segment>,
+    translation: TranslationOptions,
+    options: TextGenerationOptions,
+    strategy_str: String,
+    file_name: Option<String>,
+) -> Result<Transcript, GenerationError> {
+    let strategy = parse_strategy(&strategy_str)?;
+    let st = Instant::now();
+    let texts: Vec<String> = segments.iter().map(|segment| segment.text.clone()).collect();
+    let total = texts.len().max(1);
+    let translated = translate_segments(strategy, &options, &translation, &texts, |done| {
+        app_handle
+            .emit_to("main", "translate_progress", done * 100 / total)
+            .map_err(|e| eyre!("{:?}", e))
+            .log_error();
+    })
+    .await?;
+
+    let segments = segments
+        .into_iter()
+        .zip(translated)
+        .map(|(segment, text)| Segment {
+            // Whisper starts segments with a space, kept so the text joins the same way
+            text: if segment.text.starts_with(' ') {
+                format!(" {}", text)
+            } else {
+                text
+            },
+            ..segment
+        })
+        .collect::<Vec<_>>();
+    let transcript = Transcript {
+        segments,
+        processing_time_sec: st.elapsed().as_secs(),
+        resume_from: None,
+    };
+
+    if let Some(file_name) = file_name {
+        let model = match strategy_str.as_str() {
+            "gemini" => &options.gemini_model,
+            _ => &options.ollama_model,
+        };
+        let pool = get_pool(&app_handle).await?;
+        sqlx::query(
+            "INSERT INTO transcript_track (file_name, language, transcription, provider, model) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (file_name, language) DO UPDATE SET transcription = excluded.transcription,
+             provider = excluded.provider, model = excluded.model, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(&file_name)
+        .bind(translation.target_language.trim())
+        .bind(serde_json::to_string(&transcript.segments)?)
+        .bind(&strategy_str)
+        .bind(model)
+        .execute(&pool)
+        .await?;
+    }
+    Ok(transcript)
+}
+
+/// Translations stored for a recording, by language
+#[tauri::command]
+pub async fn list_transcript_tracks(app_handle: tauri::AppHandle, file_name: String) -> Result<Vec<TranscriptTrack>> {
+    let pool = get_pool(&app_handle).await?;
+    let tracks = sqlx::query_as("SELECT * FROM transcript_track WHERE file_name = ? ORDER BY language")
+        .bind(file_name)
+        .fetch_all(&pool)
+        .await?;
+    Ok(tracks)
+}
+
+#[tauri::command]
+pub async fn delete_transcript_track(app_handle: tauri::AppHandle, id: i64) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM transcript_track WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
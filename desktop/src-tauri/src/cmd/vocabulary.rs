@@ -0,0 +1,129 @@
+// src/vocabulary.rs
+
+use crate::database::get_pool;
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use vibe_core::vocabulary::{Dictionary, Replacement};
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VocabularyTerm {
+    pub id: i64,
+    pub term: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReplacementRule {
+    pub id: i64,
+    pub find: String,
+    pub replace: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    pub created_at: String,
+}
+
+impl ReplacementRule {
+    fn as_replacement(&self) -> Replacement {
+        Replacement {
+            find: self.find.clone(),
+            replace: self.replace.clone(),
+            regex: self.is_regex,
+            case_sensitive: self.case_sensitive,
+        }
+    }
+}
+
+const RULE_COLUMNS: &str = "id, find, replace, is_regex, case_sensitive, created_at";
+
+/// Terms given to whisper in the initial prompt, oldest first so they keep their place when the budget is tight
+pub async fn load_vocabulary(app_handle: &tauri::AppHandle) -> Result<Vec<String>> {
+    let pool = get_pool(app_handle).await?;
+    let terms = sqlx::query_scalar("SELECT term FROM vocabulary_term ORDER BY id")
+        .fetch_all(&pool)
+        .await?;
+    Ok(terms)
+}
+
+/// Replacement rules applied to transcripts, in the order they were added
+pub async fn load_dictionary(app_handle: &tauri::AppHandle) -> Result<Dictionary> {
+    let pool = get_pool(app_handle).await?;
+    let rules: Vec<ReplacementRule> = sqlx::query_as(&format!("SELECT {} FROM replacement_rule ORDER BY id", RULE_COLUMNS))
+        .fetch_all(&pool)
+        .await?;
+    let replacements: Vec<Replacement> = rules.iter().map(ReplacementRule::as_replacement).collect();
+    Dictionary::new(&replacements)
+}
+
+#[tauri::command]
+pub async fn list_vocabulary(app_handle: tauri::AppHandle) -> Result<Vec<VocabularyTerm>> {
+    let pool = get_pool(&app_handle).await?;
+    let terms = sqlx::query_as("SELECT id, term, created_at FROM vocabulary_term ORDER BY id")
+        .fetch_all(&pool)
+        .await?;
+    Ok(terms)
+}
+
+#[tauri::command]
+pub async fn add_vocabulary_term(app_handle: tauri::AppHandle, term: String) -> Result<VocabularyTerm> {
+    let term = term.trim().to_string();
+    if term.is_empty() {
+        bail!("term can't be empty")
+    }
+    let pool = get_pool(&app_handle).await?;
+    let term = sqlx::query_as(
+        "INSERT INTO vocabulary_term (term) VALUES (?)
+         ON CONFLICT(term) DO UPDATE SET term = excluded.term
+         RETURNING id, term, created_at",
+    )
+    .bind(term)
+    .fetch_one(&pool)
+    .await?;
+    Ok(term)
+}
+
+#[tauri::command]
+pub async fn delete_vocabulary_term(app_handle: tauri::AppHandle, id: i64) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM vocabulary_term WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_replacement_rules(app_handle: tauri::AppHandle) -> Result<Vec<ReplacementRule>> {
+    let pool = get_pool(&app_handle).await?;
+    let rules = sqlx::query_as(&format!("SELECT {} FROM replacement_rule ORDER BY id", RULE_COLUMNS))
+        .fetch_all(&pool)
+        .await?;
+    Ok(rules)
+}
+
+#[tauri::command]
+pub async fn add_replacement_rule(app_handle: tauri::AppHandle, replacement: Replacement) -> Result<ReplacementRule> {
+    // Reject patterns that don't compile before they break every transcription
+    Dictionary::new(std::slice::from_ref(&replacement))?;
+    let pool = get_pool(&app_handle).await?;
+    let rule = sqlx::query_as(&format!(
+        "INSERT INTO replacement_rule (find, replace, is_regex, case_sensitive) VALUES (?, ?, ?, ?) RETURNING {}",
+        RULE_COLUMNS
+    ))
+    .bind(replacement.find)
+    .bind(replacement.replace)
+    .bind(replacement.regex)
+    .bind(replacement.case_sensitive)
+    .fetch_one(&pool)
+    .await?;
+    Ok(rule)
+}
+
+#[tauri::command]
+pub async fn delete_replacement_rule(app_handle: tauri::AppHandle, id: i64) -> Result<()> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM replacement_rule WHERE id = ?")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
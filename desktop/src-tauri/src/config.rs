@@ -4,6 +4,9 @@ pub const STORE_FILENAME: &str = "app_config.json";
 pub const DEAFULT_MODEL_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin?download=true";
 pub const DEAFULT_MODEL_FILENAME: &str = "ggml-medium.bin";
 
+/// Transcriptions running at once on the loaded model, the others wait for a free state
+pub const DEFAULT_MAX_JOBS: usize = 1;
+
 // Diarization
 pub const SEGMENT_MODEL_FILENAME: &str = "segmentation-3.0.onnx";
 pub const EMBEDDING_MODEL_FILENAME: &str = "wespeaker_en_voxceleb_CAM++.onnx";
@@ -1,4 +1,104 @@
+use eyre::{bail, Context, ContextCompat, Result};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::SqliteConnection;
+use tauri::Manager;
 use tauri_plugin_sql::{Migration, MigrationKind};
+use tokio::sync::OnceCell;
+use vibe_core::transcript::Segment;
+
+/// Same file the sql plugin opens for `sqlite:samwise.db`, relative to the app config dir
+pub const DATABASE_FILENAME: &str = "samwise.db";
+
+/// Connection pool used by Rust commands. The frontend keeps using the sql plugin.
+#[derive(Default)]
+pub struct DatabaseState(OnceCell<SqlitePool>);
+
+pub async fn get_pool(app_handle: &tauri::AppHandle) -> Result<SqlitePool> {
+    let state = app_handle.state::<DatabaseState>();
+    let pool = state
+        .0
+        .get_or_try_init(|| async {
+            let path = app_handle.path().app_config_dir()?.join(DATABASE_FILENAME);
+            if !path.exists() {
+                bail!(
+                    "database not found at {}. please open Samwise once to create it",
+                    path.display()
+                )
+            }
+            tracing::debug!("opening database at {}", path.display());
+            let options = SqliteConnectOptions::new().filename(path).foreign_keys(true);
+            Ok::<_, eyre::Report>(SqlitePool::connect_with(options).await?)
+        })
+        .await?;
+    Ok(pool.clone())
+}
+
+/// Latest transcript stored for a recording
+pub async fn load_transcript(connection: &mut SqliteConnection, file_name: &str) -> Result<Vec<Segment>> {
+    let transcription: Option<String> = sqlx::query_scalar(
+        "SELECT transcription FROM recording_insights WHERE file_name = ? AND transcription IS NOT NULL ORDER BY id DESC LIMIT 1",
+    )
+    .bind(file_name)
+    .fetch_optional(connection)
+    .await?;
+    let transcription = transcription.with_context(|| format!("no transcription found for {}", file_name))?;
+    serde_json::from_str(&transcription).context("failed to parse stored transcription")
+}
+
+/// Replace the stored transcript of a recording, keeping its summary and dropping its search index
+pub async fn store_transcript(connection: &mut SqliteConnection, file_name: &str, segments: &[Segment]) -> Result<()> {
+    let transcription = serde_json::to_string(segments)?;
+    let updated = sqlx::query(
+        "UPDATE recording_insights SET transcription = ?, updated_at = CURRENT_TIMESTAMP
+         WHERE id = (SELECT id FROM recording_insights WHERE file_name = ? ORDER BY id DESC LIMIT 1)",
+    )
+    .bind(&transcription)
+    .bind(file_name)
+    .execute(&mut *connection)
+    .await?;
+    if updated.rows_affected() == 0 {
+        sqlx::query("INSERT INTO recording_insights (file_name, transcription) VALUES (?, ?)")
+            .bind(file_name)
+            .bind(&transcription)
+            .execute(&mut *connection)
+            .await?;
+    }
+    // The search chunks are dropped by the recording_insights triggers, `index_all_recordings` indexes it again
+    Ok(())
+}
+
+/// Where the stored transcript of a recording stops when an aborted transcription left it partial, in centiseconds
+pub async fn load_resume_point(connection: &mut SqliteConnection, file_name: &str) -> Result<Option<i64>> {
+    let resume_from: Option<Option<i64>> = sqlx::query_scalar(
+        "SELECT resume_from FROM recording_insights WHERE file_name = ? AND transcription IS NOT NULL ORDER BY id DESC LIMIT 1",
+    )
+    .bind(file_name)
+    .fetch_optional(connection)
+    .await?;
+    Ok(resume_from.flatten())
+}
+
+/// Mark the stored transcript of a recording partial or complete, see `load_resume_point`
+pub async fn store_resume_point(connection: &mut SqliteConnection, file_name: &str, resume_from: Option<i64>) -> Result<()> {
+    sqlx::query(
+        "UPDATE recording_insights SET resume_from = ?, updated_at = CURRENT_TIMESTAMP
+         WHERE id = (SELECT id FROM recording_insights WHERE file_name = ? ORDER BY id DESC LIMIT 1)",
+    )
+    .bind(resume_from)
+    .bind(file_name)
+    .execute(&mut *connection)
+    .await?;
+    let status = match resume_from {
+        Some(_) => "TRANSCRIPTION_PARTIAL",
+        None => "TRANSCRIPTION_COMPLETED",
+    };
+    sqlx::query("UPDATE recording SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE file_name = ?")
+        .bind(status)
+        .bind(file_name)
+        .execute(&mut *connection)
+        .await?;
+    Ok(())
+}
 
 pub fn get_migrations() -> Vec<Migration> {
     vec![
@@ -38,5 +138,194 @@ pub fn get_migrations() -> Vec<Migration> {
             sql: "ALTER TABLE recording_insights ADD COLUMN summary_prompt TEXT;",
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 4,
+            description: "create_transcript_chunk_table_with_fts_index",
+            sql: "CREATE TABLE transcript_chunk (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_name VARCHAR(255) NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                start INTEGER NOT NULL,
+                stop INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                embedding BLOB,
+                embedding_model TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (file_name) REFERENCES recording(file_name)
+            );
+            CREATE INDEX idx_transcript_chunk_file_name ON transcript_chunk(file_name);
+            CREATE VIRTUAL TABLE transcript_chunk_fts USING fts5(
+                text,
+                content='transcript_chunk',
+                content_rowid='id'
+            );
+            CREATE TRIGGER transcript_chunk_after_insert AFTER INSERT ON transcript_chunk BEGIN
+                INSERT INTO transcript_chunk_fts(rowid, text) VALUES (new.id, new.text);
+            END;
+            CREATE TRIGGER transcript_chunk_after_delete AFTER DELETE ON transcript_chunk BEGIN
+                INSERT INTO transcript_chunk_fts(transcript_chunk_fts, rowid, text) VALUES ('delete', old.id, old.text);
+            END;",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 5,
+            description: "create_chat_thread_and_chat_message_tables",
+            sql: "CREATE TABLE chat_thread (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_name VARCHAR(255) NOT NULL,
+                title TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (file_name) REFERENCES recording(file_name)
+            );
+            CREATE INDEX idx_chat_thread_file_name ON chat_thread(file_name);
+            CREATE TABLE chat_message (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                thread_id INTEGER NOT NULL,
+                role VARCHAR(20) NOT NULL,
+                content TEXT NOT NULL,
+                provider VARCHAR(55),
+                model TEXT,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (thread_id) REFERENCES chat_thread(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_chat_message_thread_id ON chat_message(thread_id);",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 6,
+            description: "create_prompt_template_table",
+            sql: "CREATE TABLE prompt_template (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                description TEXT,
+                body TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 7,
+            description: "create_speaker_profile_table",
+            sql: "CREATE TABLE speaker_profile (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                embedding BLOB NOT NULL,
+                sample_count INTEGER NOT NULL DEFAULT 1,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 8,
+            description: "create_speaker_and_segment_speaker_tables",
+            sql: "CREATE TABLE speaker (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_name VARCHAR(255) NOT NULL,
+                label TEXT NOT NULL,
+                name TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (file_name, label),
+                FOREIGN KEY (file_name) REFERENCES recording(file_name)
+            );
+            CREATE TABLE segment_speaker (
+                file_name VARCHAR(255) NOT NULL,
+                segment_index INTEGER NOT NULL,
+                speaker_id INTEGER NOT NULL,
+                confidence REAL,
+                PRIMARY KEY (file_name, segment_index),
+                FOREIGN KEY (speaker_id) REFERENCES speaker(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_segment_speaker_speaker_id ON segment_speaker(speaker_id);",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 9,
+            description: "create_vocabulary_and_replacement_tables",
+            sql: "CREATE TABLE vocabulary_term (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                term TEXT NOT NULL UNIQUE,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE replacement_rule (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                find TEXT NOT NULL,
+                replace TEXT NOT NULL,
+                is_regex BOOLEAN NOT NULL DEFAULT 0,
+                case_sensitive BOOLEAN NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 10,
+            description: "add_resume_point_to_recording_insights",
+            sql: "ALTER TABLE recording_insights ADD COLUMN resume_from INTEGER;",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 11,
+            description: "create_transcript_track_table",
+            sql: "CREATE TABLE transcript_track (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_name VARCHAR(255) NOT NULL,
+                language TEXT NOT NULL,
+                transcription TEXT NOT NULL,
+                provider TEXT,
+                model TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (file_name, language),
+                FOREIGN KEY (file_name) REFERENCES recording(file_name)
+            );",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 12,
+            description: "create_chapter_table",
+            sql: "CREATE TABLE chapter (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_name VARCHAR(255) NOT NULL,
+                position INTEGER NOT NULL,
+                start INTEGER NOT NULL,
+                stop INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (file_name, position),
+                FOREIGN KEY (file_name) REFERENCES recording(file_name)
+            );",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 13,
+            description: "create_speaker_source_table",
+            sql: "CREATE TABLE speaker_source (
+                file_name VARCHAR(255) PRIMARY KEY,
+                transcript_hash TEXT NOT NULL,
+                FOREIGN KEY (file_name) REFERENCES recording(file_name)
+            );",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 14,
+            description: "drop_transcript_chunks_when_transcription_changes",
+            // The frontend writes transcripts too, so the stale search chunks are dropped here rather than by each writer
+            sql: "CREATE TRIGGER recording_insights_transcription_after_update
+            AFTER UPDATE OF transcription ON recording_insights
+            WHEN old.transcription IS NOT new.transcription BEGIN
+                DELETE FROM transcript_chunk WHERE file_name = new.file_name;
+            END;
+            CREATE TRIGGER recording_insights_transcription_after_insert
+            AFTER INSERT ON recording_insights
+            WHEN new.transcription IS NOT NULL BEGIN
+                DELETE FROM transcript_chunk WHERE file_name = new.file_name;
+            END;",
+            kind: MigrationKind::Up,
+        },
     ]
 }
@@ -70,8 +70,15 @@ fn main() -> Result<()> {
             cmd::download_file,
             cmd::get_cargo_features,
             cmd::transcribe,
+            cmd::transcribe_range,
+            cmd::resume_transcribe,
+            cmd::clear_transcript_cache,
             cmd::download_model,
             cmd::load_model,
+            cmd::get_transcribe_queue,
+            cmd::get_model_status,
+            cmd::get_model_info,
+            cmd::unload_model,
             cmd::get_commit_hash,
             cmd::get_cuda_version,
             cmd::get_rocm_version,
@@ -89,6 +96,42 @@ fn main() -> Result<()> {
             cmd::is_portable,
             cmd::get_logs_folder,
             cmd::chat::process_chat_message,
+            cmd::chat::create_chat_thread,
+            cmd::chat::list_chat_threads,
+            cmd::chat::get_chat_messages,
+            cmd::chat::append_chat_message,
+            cmd::chat::rename_chat_thread,
+            cmd::chat::delete_chat_thread,
+            cmd::templates::list_prompt_templates,
+            cmd::templates::create_prompt_template,
+            cmd::templates::update_prompt_template,
+            cmd::templates::delete_prompt_template,
+            cmd::templates::render_prompt_template,
+            cmd::search::index_recording,
+            cmd::search::index_all_recordings,
+            cmd::search::search_recordings,
+            cmd::translate::translate_transcript,
+            cmd::translate::list_transcript_tracks,
+            cmd::translate::delete_transcript_track,
+            cmd::chapters::generate_chapters,
+            cmd::chapters::list_chapters,
+            cmd::chapters::rename_chapter,
+            cmd::chapters::delete_chapters,
+            cmd::chapters::export_chapters,
+            cmd::speakers::enroll_speaker_profile,
+            cmd::speakers::list_speaker_profiles,
+            cmd::speakers::rename_speaker_profile,
+            cmd::speakers::delete_speaker_profile,
+            cmd::speakers::list_recording_speakers,
+            cmd::speakers::rename_recording_speaker,
+            cmd::speakers::merge_recording_speakers,
+            cmd::speakers::split_recording_speaker,
+            cmd::vocabulary::list_vocabulary,
+            cmd::vocabulary::add_vocabulary_term,
+            cmd::vocabulary::delete_vocabulary_term,
+            cmd::vocabulary::list_replacement_rules,
+            cmd::vocabulary::add_replacement_rule,
+            cmd::vocabulary::delete_replacement_rule,
             #[cfg(windows)]
             cmd::set_high_gpu_preference
         ])
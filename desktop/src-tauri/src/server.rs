@@ -1,25 +1,43 @@
+use crate::cmd::search::SearchHit;
 use crate::cmd::{self, DiarizeOptions};
-use crate::setup::ModelContext;
-use axum::extract::State;
+use crate::setup::{ModelContext, ModelStatus};
+use axum::extract::{Query, State};
 use axum::http::StatusCode;
 use axum::response::Result;
 use axum::routing::post;
 use axum::Json;
 use axum::{routing::get, Router};
 use eyre::eyre;
+use samwise_text::text_generation::TextGenerationOptions;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tauri::Manager;
+use serde_json::{json, Value};
+use tauri::{Emitter, Manager};
 use tokio::sync::Mutex;
-use utoipa::{OpenApi, ToSchema};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
-use vibe_core::config::TranscribeOptions;
+use vibe_core::config::{BackendOptions, HallucinationFilter, RemoteOptions, TranscribeOptions};
+use vibe_core::model::ModelInfo;
+use vibe_core::pool::PoolStatus;
 use vibe_core::transcript::{Segment, Transcript};
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(list_models, load, transcribe),
-    components(schemas(TranscribeOptions, LoadPayload, Transcript, Segment))
+    paths(list_models, load, transcribe, abort, queue, model_status, search),
+    components(schemas(
+        TranscribeOptions,
+        HallucinationFilter,
+        BackendOptions,
+        RemoteOptions,
+        LoadPayload,
+        AbortPayload,
+        PoolStatus,
+        ModelStatus,
+        ModelInfo,
+        Transcript,
+        Segment,
+        SearchPayload,
+        SearchHit
+    ))
 )]
 struct ApiDoc;
 
@@ -27,8 +45,12 @@ pub async fn run(app_handle: tauri::AppHandle, host: String, port: u16) -> eyre:
     let app = Router::new()
         .merge(SwaggerUi::new("/docs").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .route("/transcribe", post(transcribe))
+        .route("/abort", post(abort))
         .route("/load", post(load))
         .route("/list", get(list_models))
+        .route("/queue", get(queue))
+        .route("/model", get(model_status))
+        .route("/search", post(search))
         .with_state(app_handle);
 
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
@@ -43,6 +65,10 @@ pub async fn run(app_handle: tauri::AppHandle, host: String, port: u16) -> eyre:
 struct LoadPayload {
     pub model_path: String,
     pub gpu_device: Option<i32>,
+    /// Transcriptions running at once, the others wait for a free slot
+    pub max_jobs: Option<usize>,
+    /// Minutes without transcriptions before the model is unloaded, it's loaded again by the next one. 0 or missing keeps it loaded.
+    pub idle_timeout_mins: Option<u64>,
 }
 
 /// Load model from path
@@ -54,9 +80,16 @@ struct LoadPayload {
 	),
 )]
 async fn load(State(app_handle): State<tauri::AppHandle>, Json(payload): Json<LoadPayload>) -> Result<String, String> {
-    cmd::load_model(app_handle, payload.model_path, payload.gpu_device)
-        .await
-        .map_err(|e| e.to_string())
+    cmd::load_model(
+        app_handle,
+        payload.model_path,
+        payload.gpu_device,
+        payload.max_jobs,
+        payload.idle_timeout_mins,
+        None,
+    )
+    .await
+    .map_err(|e| e.to_string())
 }
 
 /// List all Todo items
@@ -90,22 +123,142 @@ async fn list_models(State(app_handle): State<tauri::AppHandle>) -> Result<Json<
     Ok(Json(Value::Array(model_files.into_iter().map(Value::String).collect())))
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TranscribeQuery {
+    /// Id to abort this transcription with, while it runs or waits for the model
+    pub job_id: Option<String>,
+}
+
 /// Transcribe file
+///
+/// Set `backend` to transcribe on a remote server instead of the loaded model, no model is needed then.
 #[utoipa::path(
 	post,
 	path = "/transcribe",
+	params(TranscribeQuery),
 	responses(
-		(status = 200, description = "List all models", body = Transcript)
+		(status = 200, description = "List all models", body = Transcript),
+		(status = 400, description = "Invalid options", body = String)
 	)
 )]
 async fn transcribe(
     State(app_handle): State<tauri::AppHandle>,
+    Query(query): Query<TranscribeQuery>,
     Json(payload): Json<TranscribeOptions>,
 ) -> Result<Json<Transcript>, (StatusCode, String)> {
+    payload.validate().map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
     let model_context_state: tauri::State<'_, Mutex<Option<ModelContext>>> = app_handle.state();
-    let transcript = cmd::transcribe(app_handle.clone(), payload, model_context_state, DiarizeOptions::default())
+    let transcript = cmd::transcribe(
+        app_handle.clone(),
+        payload,
+        model_context_state,
+        DiarizeOptions::default(),
+        None,
+        query.job_id,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(transcript))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AbortPayload {
+    /// `job_id` given to `/transcribe`
+    pub job_id: String,
+}
+
+/// Abort transcription
+///
+/// Stop one transcription, the others on the model keep running. A running job returns what it transcribed so far,
+/// a waiting one leaves the queue with an error.
+#[utoipa::path(
+	post,
+	path = "/abort",
+	request_body = AbortPayload,
+	responses(
+		(status = 200, description = "Abort requested")
+	)
+)]
+async fn abort(
+    State(app_handle): State<tauri::AppHandle>,
+    Json(payload): Json<AbortPayload>,
+) -> Result<(), (StatusCode, String)> {
+    app_handle
+        .emit("abort_transcribe", json!({ "job_id": payload.job_id }))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Transcription queue
+///
+/// Transcriptions running on the loaded model and waiting for it.
+#[utoipa::path(
+	get,
+	path = "/queue",
+	responses(
+		(status = 200, description = "Queue of the loaded model", body = PoolStatus),
+		(status = 404, description = "No model loaded", body = String)
+	)
+)]
+async fn queue(State(app_handle): State<tauri::AppHandle>) -> Result<Json<PoolStatus>, (StatusCode, String)> {
+    let model_context_state: tauri::State<'_, Mutex<Option<ModelContext>>> = app_handle.state();
+    let status = cmd::get_transcribe_queue(model_context_state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Please load model first".to_string()))?;
+    Ok(Json(status))
+}
+
+/// Model status
+///
+/// The chosen model, whether it's loaded and an estimate of the memory it takes.
+#[utoipa::path(
+	get,
+	path = "/model",
+	responses(
+		(status = 200, description = "Status of the chosen model", body = ModelStatus),
+		(status = 404, description = "No model chosen", body = String)
+	)
+)]
+async fn model_status(State(app_handle): State<tauri::AppHandle>) -> Result<Json<ModelStatus>, (StatusCode, String)> {
+    let model_context_state: tauri::State<'_, Mutex<Option<ModelContext>>> = app_handle.state();
+    let status = cmd::get_model_status(model_context_state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Please load model first".to_string()))?;
+    Ok(Json(status))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SearchPayload {
+    pub query: String,
+    /// Text generation options used to embed the query
+    #[schema(value_type = Object)]
+    pub options: TextGenerationOptions,
+    /// "ollama" or "gemini"
+    pub strategy: String,
+    pub limit: Option<usize>,
+}
+
+/// Search recordings
+///
+/// Search indexed transcripts by keywords and meaning.
+#[utoipa::path(
+	post,
+	path = "/search",
+	request_body = SearchPayload,
+	responses(
+		(status = 200, description = "Ranked search hits", body = Vec<SearchHit>)
+	)
+)]
+async fn search(
+    State(app_handle): State<tauri::AppHandle>,
+    Json(payload): Json<SearchPayload>,
+) -> Result<Json<Vec<SearchHit>>, (StatusCode, String)> {
+    let hits = cmd::search::search_recordings(app_handle, payload.query, payload.options, payload.strategy, payload.limit)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(transcript))
+    Ok(Json(hits))
 }
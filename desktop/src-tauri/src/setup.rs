@@ -1,25 +1,256 @@
 use crate::{
     cli::{self, is_cli_detected},
     config::STORE_FILENAME,
+    database::DatabaseState,
     panic_hook,
     utils::{get_issue_url, LogError},
 };
-use eyre::eyre;
+use eyre::{eyre, ContextCompat};
 use once_cell::sync::Lazy;
+use serde::Serialize;
 use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tauri::{App, Manager};
 use tauri_plugin_dialog::DialogExt;
 use tauri_plugin_shell::ShellExt;
 use tauri_plugin_store::StoreBuilder;
 use tokio::sync::Mutex;
+use utoipa::ToSchema;
+use vibe_core::model::ModelInfo;
+use vibe_core::pool::{PoolStatus, StatePool};
 use vibe_core::transcribe::WhisperContext;
 
 pub static STATIC_APP: Lazy<std::sync::Mutex<Option<tauri::AppHandle>>> = Lazy::new(|| std::sync::Mutex::new(None));
 
+/// How often the idle model is checked for unloading
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The chosen model, loaded while in use. Its weights are dropped after `idle_timeout` without jobs
+/// and loaded again by the next transcription.
 pub struct ModelContext {
     pub path: String,
     pub gpu_device: Option<i32>,
-    pub handle: WhisperContext,
+    pub max_jobs: usize,
+    /// None keeps the model loaded
+    pub idle_timeout: Option<Duration>,
+    pub info: ModelInfo,
+    pool: Option<Arc<StatePool>>,
+    /// Held while the weights are loaded again after an idle unload, the state lock isn't held meanwhile
+    loading: Arc<Mutex<()>>,
+    /// Small model for the quick draft of two pass transcriptions, loaded on first use
+    pub draft_path: Option<String>,
+    draft: Option<Arc<WhisperContext>>,
+    /// Held while the draft model loads, like `loading`
+    draft_loading: Arc<Mutex<()>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ModelStatus {
+    pub path: String,
+    pub gpu_device: Option<i32>,
+    pub loaded: bool,
+    /// Being loaded again by a transcription after it was unloaded while idle
+    pub loading: bool,
+    pub info: ModelInfo,
+    /// Size of the weights, an estimate of the memory the model takes in RAM or VRAM
+    pub model_bytes: Option<u64>,
+    /// States allocated on top of the weights, one per running job plus the ones kept for reuse
+    pub states: usize,
+    pub idle_secs: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    pub queue: Option<PoolStatus>,
+    pub draft_path: Option<String>,
+    pub draft_loaded: bool,
+}
+
+impl ModelContext {
+    pub fn load(path: String, gpu_device: Option<i32>, max_jobs: usize, idle_timeout: Option<Duration>) -> eyre::Result<Self> {
+        let info = vibe_core::model::inspect(Path::new(&path))?;
+        let mut model_context = Self {
+            path,
+            gpu_device,
+            max_jobs,
+            idle_timeout,
+            info,
+            pool: None,
+            loading: Default::default(),
+            draft_path: None,
+            draft: None,
+            draft_loading: Default::default(),
+        };
+        model_context.pool()?;
+        Ok(model_context)
+    }
+
+    /// The pool of the loaded model, loading it while the caller holds the state lock.
+    /// Transcriptions go through `model_pool`, which doesn't.
+    fn pool(&mut self) -> eyre::Result<Arc<StatePool>> {
+        if let Some(pool) = &self.pool {
+            return Ok(pool.clone());
+        }
+        tracing::debug!("loading model {}", self.path);
+        let context = vibe_core::transcribe::create_context(Path::new(&self.path), self.gpu_device)?;
+        let pool = Arc::new(StatePool::new(context, self.max_jobs));
+        self.pool = Some(pool.clone());
+        Ok(pool)
+    }
+
+    pub fn set_draft_path(&mut self, draft_path: Option<String>) {
+        if draft_path != self.draft_path {
+            self.draft_path = draft_path;
+            self.draft = None;
+        }
+    }
+
+    /// The pool only when the model is loaded
+    pub fn loaded_pool(&self) -> Option<&Arc<StatePool>> {
+        self.pool.as_ref()
+    }
+
+    pub fn set_max_jobs(&mut self, max_jobs: usize) {
+        self.max_jobs = max_jobs;
+        if let Some(pool) = &self.pool {
+            pool.set_max_jobs(max_jobs);
+        }
+    }
+
+    /// Drop the weights, jobs holding the pool keep it until they finish
+    pub fn unload(&mut self) {
+        self.draft = None;
+        if self.pool.take().is_some() {
+            tracing::debug!("unloaded model {}", self.path);
+        }
+    }
+
+    /// Unload once no job used the model for the idle timeout. Returns whether it was unloaded.
+    pub fn unload_if_idle(&mut self) -> bool {
+        let (Some(timeout), Some(pool)) = (self.idle_timeout, &self.pool) else {
+            return false;
+        };
+        // A job that took the pool but didn't get a state yet still counts as using it
+        let in_use = Arc::strong_count(pool) > 1;
+        let expired = pool.idle_for().is_some_and(|idle| idle >= timeout);
+        if in_use || !expired {
+            return false;
+        }
+        tracing::debug!("model idle for over {:?}", timeout);
+        self.unload();
+        true
+    }
+
+    pub fn status(&self) -> ModelStatus {
+        let pool = self.pool.as_ref();
+        ModelStatus {
+            path: self.path.clone(),
+            gpu_device: self.gpu_device,
+            loaded: pool.is_some(),
+            loading: self.loading.try_lock().is_err(),
+            info: self.info.clone(),
+            model_bytes: pool
+                .and_then(|_| fs::metadata(&self.path).ok())
+                .map(|metadata| metadata.len()),
+            states: pool.map_or(0, |pool| pool.status().running + pool.idle_states()),
+            idle_secs: pool.and_then(|pool| pool.idle_for()).map(|idle| idle.as_secs()),
+            idle_timeout_secs: self.idle_timeout.map(|timeout| timeout.as_secs()),
+            queue: pool.map(|pool| pool.status()),
+            draft_path: self.draft_path.clone(),
+            draft_loaded: self.draft.is_some(),
+        }
+    }
+}
+
+/// The pool of the chosen model and its path, loading the model again if it was unloaded while idle.
+/// It's loaded on a blocking thread without holding the state lock, so the status and queue commands
+/// keep answering meanwhile.
+pub async fn model_pool(model_context_state: &Mutex<Option<ModelContext>>) -> eyre::Result<(Arc<StatePool>, String)> {
+    let loading = {
+        let model_context = model_context_state.lock().await;
+        let ctx = model_context.as_ref().context("Please load model first")?;
+        if let Some(pool) = &ctx.pool {
+            return Ok((pool.clone(), ctx.path.clone()));
+        }
+        ctx.loading.clone()
+    };
+    // Jobs started while the model loads wait for it rather than loading it again
+    let _loading = loading.lock().await;
+    let (path, gpu_device) = {
+        let model_context = model_context_state.lock().await;
+        let ctx = model_context.as_ref().context("Please load model first")?;
+        if let Some(pool) = &ctx.pool {
+            return Ok((pool.clone(), ctx.path.clone()));
+        }
+        (ctx.path.clone(), ctx.gpu_device)
+    };
+    tracing::debug!("loading model {}", path);
+    let model_path = path.clone();
+    let context =
+        tauri::async_runtime::spawn_blocking(move || vibe_core::transcribe::create_context(Path::new(&model_path), gpu_device))
+            .await??;
+
+    let mut model_context = model_context_state.lock().await;
+    let ctx = model_context.as_mut().context("Please load model first")?;
+    let pool = Arc::new(StatePool::new(context, ctx.max_jobs));
+    // Another model was chosen meanwhile, this job still gets the one it loaded
+    if ctx.path == path && ctx.gpu_device == gpu_device {
+        ctx.pool = Some(pool.clone());
+    }
+    Ok((pool, path))
+}
+
+/// The draft model, loaded on first use the same way as `model_pool`.
+/// None when it isn't set or fails to load since the draft is optional.
+pub async fn draft_model(model_context_state: &Mutex<Option<ModelContext>>) -> Option<Arc<WhisperContext>> {
+    let loading = {
+        let model_context = model_context_state.lock().await;
+        let ctx = model_context.as_ref()?;
+        if ctx.draft.is_some() {
+            return ctx.draft.clone();
+        }
+        ctx.draft_path.as_ref()?;
+        ctx.draft_loading.clone()
+    };
+    let _loading = loading.lock().await;
+    let (path, gpu_device) = {
+        let model_context = model_context_state.lock().await;
+        let ctx = model_context.as_ref()?;
+        if ctx.draft.is_some() {
+            return ctx.draft.clone();
+        }
+        (ctx.draft_path.clone()?, ctx.gpu_device)
+    };
+    tracing::debug!("loading draft model {}", path);
+    let draft_path = path.clone();
+    let loaded =
+        tauri::async_runtime::spawn_blocking(move || vibe_core::transcribe::create_context(Path::new(&draft_path), gpu_device))
+            .await;
+    let draft = match loaded.map_err(eyre::Report::from).and_then(|result| result) {
+        Ok(context) => Arc::new(context),
+        Err(error) => {
+            tracing::warn!("failed to load draft model: {:?}", error);
+            return None;
+        }
+    };
+
+    let mut model_context = model_context_state.lock().await;
+    // Kept only if it's still the chosen draft model
+    if let Some(ctx) = model_context.as_mut().filter(|ctx| ctx.draft_path.as_ref() == Some(&path)) {
+        ctx.draft = Some(draft.clone());
+    }
+    Some(draft)
+}
+
+/// Unload the model when it's idle for longer than its timeout
+async fn unload_idle_model(app_handle: tauri::AppHandle) {
+    loop {
+        tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+        let model_context_state = app_handle.state::<Mutex<Option<ModelContext>>>();
+        let mut model_context = model_context_state.lock().await;
+        if let Some(model_context) = model_context.as_mut() {
+            model_context.unload_if_idle();
+        }
+    }
 }
 
 pub fn setup(app: &App) -> Result<(), Box<dyn std::error::Error>> {
@@ -31,6 +262,10 @@ pub fn setup(app: &App) -> Result<(), Box<dyn std::error::Error>> {
 
     // Manage model context
     app.manage(Mutex::new(None::<ModelContext>));
+    tauri::async_runtime::spawn(unload_idle_model(app.app_handle().clone()));
+
+    // Manage database pool for Rust commands
+    app.manage(DatabaseState::default());
 
     let mut store = StoreBuilder::new(STORE_FILENAME).build(app.handle().clone());
     let _ = store.load();
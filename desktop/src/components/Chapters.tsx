@@ -0,0 +1,155 @@
+import React, { useContext, useEffect, useState } from 'react'
+import { useTranslation } from 'react-i18next'
+import { invoke } from '@tauri-apps/api/core'
+import * as dialog from '@tauri-apps/plugin-dialog'
+import * as fs from '@tauri-apps/plugin-fs'
+import toast from 'react-hot-toast'
+import { formatTimestamp } from '~/lib/transcript'
+import { NamedPath, reportGenerationError } from '~/lib/utils'
+import { usePreferenceProvider } from '~/providers/Preference'
+import { ErrorModalContext } from '~/providers/ErrorModal'
+
+interface Chapter {
+	start: number
+	stop: number
+	title: string
+}
+
+type ChapterMethod = 'embeddings' | 'llm'
+type ChapterFormat = 'vtt' | 'md' | 'ffmetadata'
+
+const formatExtensions: Record<ChapterFormat, string> = {
+	vtt: 'chapters.vtt',
+	md: 'md',
+	ffmetadata: 'ffmetadata.txt',
+}
+
+interface ChaptersProps {
+	file: NamedPath
+}
+
+const Chapters: React.FC<ChaptersProps> = ({ file }) => {
+	const { t } = useTranslation()
+	const preference = usePreferenceProvider()
+	const { setState: setErrorModal } = useContext(ErrorModalContext)
+	const [chapters, setChapters] = useState<Chapter[]>([])
+	const [method, setMethod] = useState<ChapterMethod>('embeddings')
+	const [generating, setGenerating] = useState(false)
+
+	useEffect(() => {
+		setChapters([])
+		invoke<Chapter[]>('list_chapters', { fileName: file.name })
+			.then(setChapters)
+			.catch((error) => console.error('Failed to load chapters:', error))
+	}, [file.name])
+
+	async function generate() {
+		setGenerating(true)
+		try {
+			const options = {
+				ollama_base_url: preference.chatModelOptions.ollama_base_url,
+				ollama_model: preference.chatModelOptions.ollama_model,
+				ollama_api_key: preference.chatModelOptions.ollama_api_key,
+				google_api_key: preference.chatModelOptions.gemini_api_key,
+				gemini_model: preference.chatModelOptions.gemini_model,
+				max_output_tokens: 2048,
+				temperature: preference.chatModelOptions.temperature,
+				top_k: preference.chatModelOptions.top_k,
+				top_p: preference.chatModelOptions.top_p,
+			}
+			const result = await invoke<Chapter[]>('generate_chapters', {
+				fileName: file.name,
+				chapterOptions: { method },
+				options,
+				strategyStr: preference.chatModelOptions.strategy,
+			})
+			setChapters(result)
+		} catch (error) {
+			reportGenerationError(error, t, (log) => setErrorModal({ open: true, log }))
+		} finally {
+			setGenerating(false)
+		}
+	}
+
+	function setTitle(position: number, title: string) {
+		setChapters((chapters) => chapters.map((chapter, index) => (index === position ? { ...chapter, title } : chapter)))
+	}
+
+	async function rename(position: number, title: string) {
+		await invoke('rename_chapter', { fileName: file.name, position, title }).catch((error) => setErrorModal({ open: true, log: String(error) }))
+	}
+
+	async function deleteChapters() {
+		await invoke('delete_chapters', { fileName: file.name })
+		setChapters([])
+	}
+
+	async function exportChapters(format: ChapterFormat) {
+		try {
+			const text = await invoke<string>('export_chapters', { fileName: file.name, format })
+			const ext = formatExtensions[format]
+			const defaultPath = await invoke<NamedPath>('get_save_path', { srcPath: file.path, targetExt: ext })
+			const filePath = await dialog.save({
+				filters: [{ name: '', extensions: [ext.split('.').pop()!] }],
+				canCreateDirectories: true,
+				defaultPath: defaultPath.path,
+			})
+			if (filePath) {
+				await fs.writeTextFile(filePath, text)
+				toast(t('common.save-success'))
+			}
+		} catch (error) {
+			setErrorModal({ open: true, log: String(error) })
+		}
+	}
+
+	return (
+		<div className="flex flex-col gap-3 w-full h-full">
+			<div className="flex flex-row gap-2 items-center">
+				<select value={method} onChange={(e) => setMethod(e.target.value as ChapterMethod)} className="select select-bordered flex-1">
+					<option value="embeddings">{t('common.chapters-by-embeddings')}</option>
+					<option value="llm">{t('common.chapters-by-llm')}</option>
+				</select>
+				<button onMouseDown={generate} disabled={generating} className="btn btn-primary">
+					{generating ? t('common.generating-chapters') : t('common.generate-chapters')}
+				</button>
+				{chapters.length > 0 && (
+					<button onMouseDown={deleteChapters} disabled={generating} className="btn bg-base-300 text-base-content">
+						{t('common.delete')}
+					</button>
+				)}
+			</div>
+			{chapters.length > 0 && (
+				<>
+					<div className="flex flex-col gap-2 overflow-y-auto">
+						{chapters.map((chapter, index) => (
+							<div key={index} className="flex flex-row gap-3 items-center">
+								<span className="font-mono opacity-70 w-20 shrink-0">{formatTimestamp(chapter.start, false, '.', false)}</span>
+								<input
+									value={chapter.title}
+									onChange={(e) => setTitle(index, e.target.value)}
+									onBlur={(e) => rename(index, e.target.value)}
+									disabled={generating}
+									className="input input-bordered input-sm flex-1"
+								/>
+							</div>
+						))}
+					</div>
+					<div className="flex flex-row gap-2">
+						<button onMouseDown={() => exportChapters('vtt')} className="btn btn-sm">
+							{t('common.export-chapters-vtt')}
+						</button>
+						<button onMouseDown={() => exportChapters('md')} className="btn btn-sm">
+							{t('common.export-chapters-markdown')}
+						</button>
+						<button onMouseDown={() => exportChapters('ffmetadata')} className="btn btn-sm">
+							{t('common.export-chapters-ffmetadata')}
+						</button>
+					</div>
+				</>
+			)}
+		</div>
+	)
+}
+
+export default Chapters
@@ -6,7 +6,7 @@ import { invoke } from '@tauri-apps/api/core';
 import { Segment, asSrt } from '~/lib/transcript'
 import { usePreferenceProvider } from '~/providers/Preference';
 import { ReactComponent as DeleteIcon } from '~/icons/cancel.svg';
-import { ModifyState } from '~/lib/utils'
+import { ModifyState, NamedPath, reportGenerationError } from '~/lib/utils'
 import { ErrorModalContext } from '~/providers/ErrorModal'
 import ReactMarkdown from 'react-markdown';
 
@@ -14,6 +14,7 @@ interface ChatProps {
   segments: Segment[] | null;
   messages: Message[];
   setMessages: ModifyState<Message[]>
+  file?: NamedPath;
 }
 
 export interface Message {
@@ -22,13 +23,71 @@ export interface Message {
   content: string;
 }
 
-const Chat: React.FC<ChatProps> = ({ segments, messages, setMessages }) => {
+interface ChatThread {
+  id: number;
+  file_name: string;
+  title: string;
+  created_at: string;
+  updated_at: string;
+}
+
+interface StoredChatMessage {
+  id: number;
+  role: 'user' | 'assistant';
+  content: string;
+}
+
+const THREAD_TITLE_LENGTH = 40;
+
+const Chat: React.FC<ChatProps> = ({ segments, messages, setMessages, file }) => {
   const { t } = useTranslation();
   const preference = usePreferenceProvider();
   const [inputMessage, setInputMessage] = useState('');
   const [isLoading, setIsLoading] = useState(false);
   const { setState: setErrorModal } = useContext(ErrorModalContext)
   const chatContainerRef = useRef<HTMLDivElement>(null);
+  const [threads, setThreads] = useState<ChatThread[]>([]);
+  const [threadId, setThreadId] = useState<number | null>(null);
+
+  const openThread = async (id: number | null) => {
+    setThreadId(id);
+    if (id === null) {
+      setMessages([]);
+      return;
+    }
+    const stored = await invoke<StoredChatMessage[]>('get_chat_messages', { threadId: id });
+    setMessages(stored.map(({ id, role, content }) => ({ id: id.toString(), role, content })));
+  };
+
+  // Reopen the most recent thread of the recording
+  useEffect(() => {
+    if (!file?.name) return;
+    invoke<ChatThread[]>('list_chat_threads', { fileName: file.name })
+      .then((stored) => {
+        setThreads(stored);
+        return openThread(stored[0]?.id ?? null);
+      })
+      .catch((error) => console.error('Failed to load chat threads:', error));
+  }, [file?.name]);
+
+  const ensureThread = async (firstMessage: string) => {
+    if (threadId !== null || !file?.name) return threadId;
+    const thread = await invoke<ChatThread>('create_chat_thread', {
+      fileName: file.name,
+      title: firstMessage.trim().slice(0, THREAD_TITLE_LENGTH),
+    });
+    setThreads((prevThreads) => [thread, ...prevThreads]);
+    setThreadId(thread.id);
+    return thread.id;
+  };
+
+  const handleDeleteThread = async () => {
+    if (threadId === null) return;
+    await invoke('delete_chat_thread', { threadId });
+    const remaining = threads.filter((thread) => thread.id !== threadId);
+    setThreads(remaining);
+    await openThread(remaining[0]?.id ?? null);
+  };
 
   useEffect(() => {
     if (chatContainerRef.current) {
@@ -74,14 +133,16 @@ const Chat: React.FC<ChatProps> = ({ segments, messages, setMessages }) => {
         google_api_key: preference.chatModelOptions.gemini_api_key,
 		gemini_model: preference.chatModelOptions.gemini_model,
         max_output_tokens: 2048,
+        temperature: preference.chatModelOptions.temperature,
+        top_k: preference.chatModelOptions.top_k,
+        top_p: preference.chatModelOptions.top_p,
       };
 	  
-	  console.log(chatMessages)
-
       const result = await invoke<string>('process_chat_message', {
         options,
         messages: chatMessages,
         strategyStr: preference.chatModelOptions.strategy,
+        threadId: await ensureThread(inputMessage),
       });
 
       const newAssistantMessage: Message = {
@@ -94,7 +155,7 @@ const Chat: React.FC<ChatProps> = ({ segments, messages, setMessages }) => {
 
     } catch (error) {
       console.error('Error in chat:', error);
-      setErrorModal({open: true, log: String(error)})
+      reportGenerationError(error, t, (log) => setErrorModal({open: true, log}))
     } finally {
       setIsLoading(false);
     }
@@ -106,7 +167,29 @@ const Chat: React.FC<ChatProps> = ({ segments, messages, setMessages }) => {
 
   return (
     <div className="w-full h-full bg-base-200 p-4 rounded-lg flex flex-col">
-      <h2 className="text-2xl font-bold mb-4 text-base-content">{t('common.chat')}</h2>
+      <div className="flex flex-row gap-2 items-center mb-4">
+        <h2 className="text-2xl font-bold text-base-content flex-grow">{t('common.chat')}</h2>
+        {file?.name && (
+          <>
+            <select
+              value={threadId ?? ''}
+              onChange={(e) => openThread(e.target.value ? Number(e.target.value) : null)}
+              className="select select-bordered select-sm max-w-xs"
+              disabled={isLoading}
+            >
+              <option value="">{t('common.new-chat')}</option>
+              {threads.map((thread) => (
+                <option key={thread.id} value={thread.id}>{thread.title}</option>
+              ))}
+            </select>
+            {threadId !== null && (
+              <button onClick={handleDeleteThread} className="btn btn-sm" disabled={isLoading}>
+                {t('common.delete')}
+              </button>
+            )}
+          </>
+        )}
+      </div>
       
       <div ref={chatContainerRef} className="flex-grow overflow-auto mb-4 space-y-4">
 	  	{messages.map((message) => (
@@ -4,7 +4,7 @@ import { ReactComponent as ChevronDown } from '~/icons/chevron-down.svg'
 import { ReactComponent as ChevronUp } from '~/icons/chevron-up.svg'
 import { ModifyState, cx } from '~/lib/utils'
 import { InfoTooltip } from './InfoTooltip'
-import { ModelOptions as IModelOptions, usePreferenceProvider } from '~/providers/Preference'
+import { DiarizeMode, HallucinationFilter, ModelOptions as IModelOptions, usePreferenceProvider } from '~/providers/Preference'
 import { useToastProvider } from '~/providers/Toast'
 import { listen } from '@tauri-apps/api/event'
 import { ask } from '@tauri-apps/plugin-dialog'
@@ -78,6 +78,83 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 				{t('common.more-options')}
 			</div>
 			<div className="collapse-content w-full">
+				<label className="form-control w-full">
+					<div className="label">
+						<span className="label-text flex items-center gap-1">
+							<InfoTooltip text={t('common.info-transcription-backend')} />
+							{t('common.transcription-backend')}
+						</span>
+					</div>
+					<select
+						value={options.backend?.type ?? 'local'}
+						onChange={(e) =>
+							setOptions({
+								...options,
+								backend: e.target.value === 'remote' ? { type: 'remote', url: 'https://api.openai.com/v1', model: 'whisper-1' } : undefined,
+							})
+						}
+						className="select select-bordered">
+						<option value="local">{t('common.backend-local')}</option>
+						<option value="remote">{t('common.backend-remote')}</option>
+					</select>
+				</label>
+				{options.backend?.type === 'remote' && (
+					<>
+						<label className="form-control w-full">
+							<div className="label">
+								<span className="label-text">{t('common.remote-url')}</span>
+							</div>
+							<input
+								value={options.backend.url}
+								onChange={(e) => options.backend?.type === 'remote' && setOptions({ ...options, backend: { ...options.backend, url: e.target.value } })}
+								className="input input-bordered"
+								type="text"
+							/>
+						</label>
+						<label className="form-control w-full">
+							<div className="label">
+								<span className="label-text">{t('common.remote-model')}</span>
+							</div>
+							<input
+								value={options.backend.model}
+								onChange={(e) => options.backend?.type === 'remote' && setOptions({ ...options, backend: { ...options.backend, model: e.target.value } })}
+								className="input input-bordered"
+								type="text"
+							/>
+						</label>
+						<label className="form-control w-full">
+							<div className="label">
+								<span className="label-text">{t('common.api-key')}</span>
+							</div>
+							<input
+								value={options.backend.api_key ?? ''}
+								onChange={(e) =>
+									options.backend?.type === 'remote' && setOptions({ ...options, backend: { ...options.backend, api_key: e.target.value || undefined } })
+								}
+								className="input input-bordered"
+								type="password"
+							/>
+						</label>
+						<label className="form-control w-full">
+							<div className="label">
+								<span className="label-text flex items-center gap-1">
+									<InfoTooltip text={t('common.info-max-upload-mb')} />
+									{t('common.max-upload-mb')}
+								</span>
+							</div>
+							<input
+								value={options.backend.max_upload_mb ?? 25}
+								onChange={(e) =>
+									options.backend?.type === 'remote' &&
+									setOptions({ ...options, backend: { ...options.backend, max_upload_mb: parseInt(e.target.value) || undefined } })
+								}
+								className="input input-bordered"
+								type="number"
+								min={2}
+							/>
+						</label>
+					</>
+				)}
 				<div className="form-control w-full mt-3">
 					<label className="label cursor-pointer">
 						<span className="label-text flex items-center gap-1 cursor-default">
@@ -87,6 +164,22 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 						<input type="checkbox" className="toggle toggle-primary" checked={preference.recognizeSpeakers} onChange={onRecognizeSpeakerChange} />
 					</label>
 				</div>
+				<label className="form-control w-full">
+					<div className="label">
+						<span className="label-text flex items-center gap-1">
+							<InfoTooltip text={t('common.info-min-speakers')} />
+							{t('common.min-speakers')}
+						</span>
+					</div>
+					<input
+						onChange={(e) => preference.setMinSpeakers(parseInt(e.target.value) || 0)}
+						value={preference.minSpeakers}
+						className="input input-bordered"
+						type="number"
+						min={0}
+					/>
+				</label>
+
 				<label className="form-control w-full">
 					<div className="label">
 						<span className="label-text flex items-center gap-1">
@@ -95,10 +188,11 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 						</span>
 					</div>
 					<input
-						onChange={(e) => preference.setMaxSpeakers(parseInt(e.target.value) || 5)}
+						onChange={(e) => preference.setMaxSpeakers(parseInt(e.target.value) || 0)}
 						value={preference.maxSpeakers}
 						className="input input-bordered"
 						type="number"
+						min={0}
 					/>
 				</label>
 
@@ -120,6 +214,23 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 					/>
 				</label>
 
+				<label className="form-control w-full">
+					<div className="label">
+						<span className="label-text flex items-center gap-1">
+							<InfoTooltip text={t('common.info-diarize-mode')} />
+							{t('common.diarize-mode')}
+						</span>
+					</div>
+					<select
+						value={preference.diarizeMode}
+						onChange={(e) => preference.setDiarizeMode(e.target.value as DiarizeMode)}
+						className="select select-bordered">
+						<option value="per_segment">{t('common.diarize-mode-per-segment')}</option>
+						<option value="sentence">{t('common.diarize-mode-sentence')}</option>
+						<option value="word">{t('common.diarize-mode-word')}</option>
+					</select>
+				</label>
+
 				<div className="form-control w-full mt-3">
 					<label className="label cursor-pointer">
 						<span className="label-text flex items-center gap-1 cursor-default">
@@ -164,6 +275,54 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 						/>
 					</label>
 				</div>
+				<div className="form-control w-full mt-3">
+					<label className="label cursor-pointer">
+						<span className="label-text flex items-center gap-1 cursor-default">
+							<InfoTooltip text={t('common.info-skip-silence')} />
+							{t('common.skip-silence')}
+						</span>
+
+						<input
+							type="checkbox"
+							className="toggle toggle-primary"
+							checked={options.vad ?? false}
+							onChange={(e) => setOptions({ ...options, vad: e.target.checked })}
+						/>
+					</label>
+				</div>
+				<label className="form-control w-full">
+					<div className="label">
+						<span className="label-text flex items-center gap-1">
+							<InfoTooltip text={t('common.info-hallucination-filter')} />
+							{t('common.hallucination-filter')}
+						</span>
+					</div>
+					<select
+						value={options.hallucination_filter ?? ''}
+						onChange={(e) =>
+							setOptions({ ...options, hallucination_filter: (e.target.value || undefined) as HallucinationFilter | undefined })
+						}
+						className="select select-bordered">
+						<option value="">{t('common.hallucination-filter-off')}</option>
+						<option value="flag">{t('common.hallucination-filter-flag')}</option>
+						<option value="drop">{t('common.hallucination-filter-drop')}</option>
+					</select>
+				</label>
+				<div className="form-control w-full mt-3">
+					<label className="label cursor-pointer">
+						<span className="label-text flex items-center gap-1 cursor-default">
+							<InfoTooltip text={t('common.info-temperature-fallback')} />
+							{t('common.temperature-fallback')}
+						</span>
+
+						<input
+							type="checkbox"
+							className="toggle toggle-primary"
+							checked={options.temperature_fallback ?? false}
+							onChange={(e) => setOptions({ ...options, temperature_fallback: e.target.checked })}
+						/>
+					</label>
+				</div>
 				<label className="form-control w-full">
 					<div className="label">
 						<span className="label-text flex items-center gap-1">
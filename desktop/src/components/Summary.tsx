@@ -1,6 +1,6 @@
 import React, { useState, useRef, useEffect, useContext } from 'react'
 import { useTranslation } from 'react-i18next'
-import { ModifyState } from '~/lib/utils'
+import { ModifyState, reportGenerationError } from '~/lib/utils'
 import { Segment, asSrt } from '~/lib/transcript'
 import { invoke } from '@tauri-apps/api/core'
 import { usePreferenceProvider } from '~/providers/Preference'
@@ -9,6 +9,14 @@ import { getDbManager } from '~/lib/database'
 import ReactMarkdown from 'react-markdown'
 import { ErrorModalContext } from '~/providers/ErrorModal'
 
+interface PromptTemplate {
+  id: string
+  name: string
+  description: string
+  body: string
+  builtin: boolean
+}
+
 interface SummaryProps {
   summary: string
   loading: boolean
@@ -27,9 +35,16 @@ const Summary: React.FC<SummaryProps> = ({ summary, loading, setSummary, segment
   const [editableSummary, setEditableSummary] = useState(summary)
   const editTextareaRef = useRef<HTMLTextAreaElement>(null)
   const { setState: setErrorModal } = useContext(ErrorModalContext)
+  const [templates, setTemplates] = useState<PromptTemplate[]>([])
 
   setSummaryPrompt(summaryPrompt || defaultSummaryPrompt)
 
+  useEffect(() => {
+    invoke<PromptTemplate[]>('list_prompt_templates')
+      .then(setTemplates)
+      .catch((error) => console.error('Failed to load prompt templates:', error))
+  }, [])
+
   useEffect(() => {
     setEditableSummary(summary)
   }, [summary])
@@ -63,9 +78,16 @@ const Summary: React.FC<SummaryProps> = ({ summary, loading, setSummary, segment
     setIsEditing(false)
   }
 
-  const generateSummaryPrompt = (summaryInstructions: string, segments: Segment[] = []) => {
+  const generateSummaryPrompt = async (summaryInstructions: string, segments: Segment[] = []) => {
     let userMessage = summaryInstructions + "\n\n";
-    if (segments.length) {
+    if (summaryInstructions.includes('{{')) {
+      // Template variables such as {{transcript}} are filled in by the backend
+      userMessage = await invoke<string>('render_prompt_template', {
+        body: summaryInstructions,
+        segments,
+        fileName: files.length === 1 ? files[0].name : null,
+      })
+    } else if (segments.length) {
       userMessage += asSrt(segments, t('common.speaker-prefix'))
     }
     return [
@@ -85,7 +107,7 @@ const Summary: React.FC<SummaryProps> = ({ summary, loading, setSummary, segment
     setGeneratingSummary(true)
 
     try {
-      const messages = generateSummaryPrompt(summaryPrompt, segments)
+      const messages = await generateSummaryPrompt(summaryPrompt, segments)
       const options = {
         ollama_base_url: preference.chatModelOptions.ollama_base_url,
         ollama_model: preference.chatModelOptions.ollama_model,
@@ -93,6 +115,9 @@ const Summary: React.FC<SummaryProps> = ({ summary, loading, setSummary, segment
         google_api_key: preference.chatModelOptions.gemini_api_key,
 		gemini_model: preference.chatModelOptions.gemini_model,
         max_output_tokens: 4096,
+        temperature: preference.chatModelOptions.temperature,
+        top_k: preference.chatModelOptions.top_k,
+        top_p: preference.chatModelOptions.top_p,
       }
 
       const result = await invoke<string>('process_chat_message', {
@@ -111,7 +136,7 @@ const Summary: React.FC<SummaryProps> = ({ summary, loading, setSummary, segment
 		  );
 	  }
     } catch (error) {
-      setErrorModal({'open': true, 'log': String(error)})
+      reportGenerationError(error, t, (log) => setErrorModal({'open': true, 'log': log}))
     } finally {
       setGeneratingSummary(false)
     }
@@ -122,6 +147,21 @@ const Summary: React.FC<SummaryProps> = ({ summary, loading, setSummary, segment
       <h2 className="text-2xl font-bold mb-4">{t('common.summary')}</h2>
       
       <div className="mb-4">
+        <select
+          className="select select-bordered select-sm w-full mb-2"
+          value=""
+          onChange={(e) => {
+            const template = templates.find((template) => template.id === e.target.value)
+            if (template) setSummaryPrompt(template.body)
+          }}
+        >
+          <option value="" disabled>{t('common.prompt-template')}</option>
+          {templates.map((template) => (
+            <option key={template.id} value={template.id} title={template.description}>
+              {template.name}
+            </option>
+          ))}
+        </select>
         <label htmlFor="summaryPrompt" className="block text-sm font-medium mb-2">
           {t('common.summary-prompt')}
         </label>
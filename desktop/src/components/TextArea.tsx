@@ -6,7 +6,7 @@ import { ReactComponent as AlignRightIcon } from '~/icons/align-right.svg'
 import { ReactComponent as CopyIcon } from '~/icons/copy.svg'
 import { ReactComponent as DownloadIcon } from '~/icons/download.svg'
 import { ReactComponent as PrintIcon } from '~/icons/print.svg'
-import { Segment, asJson, asSrt, asText, asVtt } from '~/lib/transcript'
+import { Segment, asJson, asSrt, asText, asVtt, bilingualSegments } from '~/lib/transcript'
 import { ModifyState, NamedPath, cx, openPath } from '~/lib/utils'
 import { TextFormat, formatExtensions } from './FormatSelect'
 import { usePreferenceProvider } from '~/providers/Preference'
@@ -126,12 +126,15 @@ export default function TextArea({
 	readonly,
 	placeholder,
 	file,
+	original,
 }: {
 	segments: Segment[] | null
 	setSegments: ModifyState<Segment[] | null>
 	readonly: boolean
 	placeholder?: string
 	file: NamedPath
+	// Set for a translation, subtitles then show the original line above the translated one
+	original?: Segment[] | null
 }) {
 	const { t } = useTranslation()
 	const preference = usePreferenceProvider()
@@ -145,11 +148,12 @@ export default function TextArea({
 
 	useEffect(() => {
 		if (segments) {
+			const subtitles = original ? bilingualSegments(original, segments) : segments
 			setText(
 				preference.textFormat === 'vtt'
-					? asVtt(segments, t('common.speaker-prefix'))
+					? asVtt(subtitles, t('common.speaker-prefix'))
 					: preference.textFormat === 'srt'
-					? asSrt(segments, t('common.speaker-prefix'))
+					? asSrt(subtitles, t('common.speaker-prefix'))
 					: preference.textFormat === 'json'
 					? asJson(segments)
 					: asText(segments, t('common.speaker-prefix'))
@@ -157,7 +161,7 @@ export default function TextArea({
 		} else {
 			setText('')
 		}
-	}, [preference.textFormat, segments])
+	}, [preference.textFormat, segments, original])
 
 	async function download(text: string, format: TextFormat, file: NamedPath) {
 		if (format === 'html') {
@@ -0,0 +1,137 @@
+import React, { useContext, useEffect, useState } from 'react'
+import { useTranslation } from 'react-i18next'
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { Segment, Transcript } from '~/lib/transcript'
+import { NamedPath, reportGenerationError } from '~/lib/utils'
+import { usePreferenceProvider } from '~/providers/Preference'
+import { ErrorModalContext } from '~/providers/ErrorModal'
+import TextArea from './TextArea'
+
+interface TranscriptTrack {
+	id: number
+	file_name: string
+	language: string
+	transcription: string
+	provider?: string
+	model?: string
+	created_at: string
+	updated_at: string
+}
+
+interface TranslationProps {
+	segments: Segment[] | null
+	file: NamedPath
+}
+
+const Translation: React.FC<TranslationProps> = ({ segments, file }) => {
+	const { t } = useTranslation()
+	const preference = usePreferenceProvider()
+	const { setState: setErrorModal } = useContext(ErrorModalContext)
+	const [targetLanguage, setTargetLanguage] = useState('')
+	const [tracks, setTracks] = useState<TranscriptTrack[]>([])
+	const [translated, setTranslated] = useState<Segment[] | null>(null)
+	const [translating, setTranslating] = useState(false)
+	const [progress, setProgress] = useState<number | null>(null)
+
+	async function loadTracks(language?: string) {
+		const stored = await invoke<TranscriptTrack[]>('list_transcript_tracks', { fileName: file.name })
+		setTracks(stored)
+		const track = stored.find((track) => track.language === language) ?? stored[0]
+		if (track) {
+			setTargetLanguage(track.language)
+			setTranslated(JSON.parse(track.transcription))
+		}
+	}
+
+	useEffect(() => {
+		setTranslated(null)
+		loadTracks().catch((error) => console.error('Failed to load translations:', error))
+	}, [file.name])
+
+	useEffect(() => {
+		const unlisten = listen<number>('translate_progress', (event) => setProgress(event.payload))
+		return () => {
+			unlisten.then((unlisten) => unlisten())
+		}
+	}, [])
+
+	function showTrack(language: string) {
+		const track = tracks.find((track) => track.language === language)
+		setTargetLanguage(language)
+		setTranslated(track ? JSON.parse(track.transcription) : null)
+	}
+
+	async function translate() {
+		if (!segments?.length || !targetLanguage.trim()) return
+		setTranslating(true)
+		setProgress(0)
+		try {
+			const options = {
+				ollama_base_url: preference.chatModelOptions.ollama_base_url,
+				ollama_model: preference.chatModelOptions.ollama_model,
+				ollama_api_key: preference.chatModelOptions.ollama_api_key,
+				google_api_key: preference.chatModelOptions.gemini_api_key,
+				gemini_model: preference.chatModelOptions.gemini_model,
+				max_output_tokens: 4096,
+				temperature: preference.chatModelOptions.temperature,
+				top_k: preference.chatModelOptions.top_k,
+				top_p: preference.chatModelOptions.top_p,
+			}
+			const result = await invoke<Transcript>('translate_transcript', {
+				segments,
+				translation: { target_language: targetLanguage.trim() },
+				options,
+				strategyStr: preference.chatModelOptions.strategy,
+				fileName: file.name,
+			})
+			setTranslated(result.segments)
+			await loadTracks(targetLanguage.trim())
+		} catch (error) {
+			reportGenerationError(error, t, (log) => setErrorModal({ open: true, log }))
+		} finally {
+			setTranslating(false)
+			setProgress(null)
+		}
+	}
+
+	async function deleteTrack() {
+		const track = tracks.find((track) => track.language === targetLanguage)
+		if (!track) return
+		await invoke('delete_transcript_track', { id: track.id })
+		setTranslated(null)
+		await loadTracks()
+	}
+
+	return (
+		<div className="flex flex-col gap-3 w-full h-full">
+			<div className="flex flex-row gap-2 items-center">
+				<input
+					list="translation-tracks"
+					value={targetLanguage}
+					onChange={(e) => showTrack(e.target.value)}
+					placeholder={t('common.target-language')}
+					className="input input-bordered flex-1"
+				/>
+				<datalist id="translation-tracks">
+					{tracks.map((track) => (
+						<option key={track.id} value={track.language} />
+					))}
+				</datalist>
+				<button onMouseDown={translate} disabled={translating || !segments?.length} className="btn btn-primary">
+					{translating ? `${t('common.translating')} ${progress ?? 0}%` : t('common.translate')}
+				</button>
+				{tracks.some((track) => track.language === targetLanguage) && (
+					<button onMouseDown={deleteTrack} disabled={translating} className="btn bg-base-300 text-base-content">
+						{t('common.delete')}
+					</button>
+				)}
+			</div>
+			{translated && (
+				<TextArea segments={translated} setSegments={setTranslated} readonly={translating} file={file} original={segments} />
+			)}
+		</div>
+	)
+}
+
+export default Translation
@@ -9,13 +9,45 @@ export interface Transcript {
 	processing_time?: Duration
 	segments: Segment[]
 	word_segments?: Segment[]
+	// Set when the transcription was aborted, the segments stop here
+	resume_from?: number
 }
 
 export interface Segment {
 	start: number
 	stop: number
 	text: string
-	speaker?: number
+	speaker?: number | string
+	speaker_confidence?: number
+	suspect?: boolean
+}
+
+// Accurate segments that supersede the draft segments between start and stop
+export interface Replacement {
+	start: number
+	stop: number
+	segments: Segment[]
+}
+
+// Same rule as vibe_core's Replacement::apply, a draft segment is replaced when its middle is in the range
+export function applyReplacement(segments: Segment[], replacement: Replacement) {
+	const kept = segments.filter((segment) => {
+		const middle = Math.floor((segment.start + segment.stop) / 2)
+		return middle < replacement.start || middle >= replacement.stop
+	})
+	const index = kept.findIndex((segment) => segment.start >= replacement.start)
+	const at = index === -1 ? kept.length : index
+	return [...kept.slice(0, at), ...replacement.segments, ...kept.slice(at)]
+}
+
+// Original segments with the translated text on a second line, for bilingual subtitles.
+// Segments are paired by timestamps, so a translation of an older transcript only fills the segments that didn't change.
+export function bilingualSegments(original: Segment[], translated: Segment[]) {
+	const byTime = new Map(translated.map((segment) => [`${segment.start}-${segment.stop}`, segment.text.trim()]))
+	return original.map((segment) => {
+		const translation = byTime.get(`${segment.start}-${segment.stop}`)
+		return translation ? { ...segment, text: `${segment.text.trim()}\n${translation}` } : segment
+	})
 }
 
 export function formatTimestamp(seconds: number, alwaysIncludeHours: boolean, decimalMarker: string, includeMilliseconds: boolean = true): string {
@@ -6,6 +6,8 @@ import * as os from '@tauri-apps/plugin-os'
 import * as config from './config'
 import { Dispatch, SetStateAction } from 'react'
 import { Store } from '@tauri-apps/plugin-store'
+import { TFunction } from 'i18next'
+import toast from 'react-hot-toast'
 
 export interface NamedPath {
 	name: string
@@ -33,6 +35,16 @@ export function cx(...cns: (boolean | string | undefined)[]): string {
 	return cns.filter(Boolean).join(' ')
 }
 
+export function formatBytes(bytes: number) {
+	const units = ['B', 'KB', 'MB', 'GB']
+	let index = 0
+	while (bytes >= 1024 && index < units.length - 1) {
+		bytes /= 1024
+		index++
+	}
+	return `${bytes.toFixed(index > 1 ? 1 : 0)} ${units[index]}`
+}
+
 export function formatLongString(str: string, n: number) {
 	if (str.length > n) {
 		return str.substring(0, n) + '...'
@@ -144,6 +156,40 @@ export async function openPath(path: NamedPath) {
 
 export async function getModelsFolder() {}
 
-export function formatSpeaker(speaker?: number, prefix = 'Speaker') {
+export function formatSpeaker(speaker?: number | string, prefix = 'Speaker') {
+	// Enrolled speakers are labeled by name
+	if (typeof speaker === 'string' && isNaN(Number(speaker))) {
+		return `${speaker}: `
+	}
 	return `${prefix} ${speaker ?? '?'}: `
 }
+
+/** Error of the commands calling a language model, GenerationError in cmd/chat.rs */
+export interface GenerationError {
+	kind: 'auth' | 'quota' | 'network' | 'content_filter' | 'api' | 'invalid_response' | 'other'
+	message: string
+	retry_after_secs: number | null
+}
+
+export function asGenerationError(error: unknown): GenerationError {
+	if (typeof error === 'object' && error !== null && 'kind' in error && 'message' in error) {
+		return error as GenerationError
+	}
+	return { kind: 'other', message: String(error), retry_after_secs: null }
+}
+
+/** Point to the API key or ask to wait when the provider refused, show the details of other errors with `showLog` */
+export function reportGenerationError(error: unknown, t: TFunction, showLog: (log: string) => void) {
+	const generationError = asGenerationError(error)
+	if (generationError.kind === 'auth') {
+		toast.error(t('common.llm-error-auth'))
+	} else if (generationError.kind === 'quota') {
+		toast.error(
+			generationError.retry_after_secs
+				? t('common.llm-error-quota-retry', { seconds: generationError.retry_after_secs })
+				: t('common.llm-error-quota'),
+		)
+	} else {
+		showLog(generationError.message)
+	}
+}
@@ -22,6 +22,8 @@ export function viewModel() {
 	const [inProgress, setInProgress] = useState(false)
 	const [isAborting, setIsAborting] = useState(false)
 	const isAbortingRef = useRef<boolean>(false)
+	// Id of the file being transcribed, cancelling doesn't touch other jobs on the model
+	const jobIdRef = useRef<string | null>(null)
 	const preference = usePreferenceProvider()
 	const navigate = useNavigate()
 
@@ -96,7 +98,15 @@ export function viewModel() {
 		}
 		setInProgress(true)
 		let localIndex = 0
-		await invoke('load_model', { modelPath: preference.modelPath, gpuDevice: preference.gpuDevice })
+		// Remote servers don't need the local model
+		if (preference.modelOptions.backend?.type !== 'remote') {
+			await invoke('load_model', {
+				modelPath: preference.modelPath,
+				gpuDevice: preference.gpuDevice,
+				idleTimeoutMins: preference.modelIdleTimeout,
+				draftModelPath: preference.twoPass ? preference.draftModelPath : null,
+			})
+		}
 		setCurrentIndex(localIndex)
 		const loopStartTime = performance.now()
 		for (const file of files) {
@@ -111,13 +121,22 @@ export function viewModel() {
 				}
 				const startTime = performance.now()
 
-				const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, enabled: preference.recognizeSpeakers }
+				const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, min_speakers: preference.minSpeakers, enabled: preference.recognizeSpeakers, mode: preference.diarizeMode }
+				const jobId = crypto.randomUUID()
+				jobIdRef.current = jobId
 				const res: Transcript = await invoke('transcribe', {
 					options,
 					modelPath: preference.modelPath,
 					diarizeOptions,
+					jobId,
 				})
 
+				// Aborted, the partial transcript isn't written as if it were the whole file
+				if (res.resume_from != null) {
+					navigate('/')
+					break
+				}
+
 				// Calculate time
 				let total = Math.round((performance.now() - startTime) / 1000)
 				console.info(`Transcribe ${file.name} took ${total} seconds.`)
@@ -174,7 +193,7 @@ export function viewModel() {
 		}
 		isAbortingRef.current = true
 
-		emit('abort_transcribe')
+		emit('abort_transcribe', { job_id: jobIdRef.current })
 		setIsAborting(true)
 		setInProgress(false)
 	}
@@ -3,6 +3,7 @@ import LanguageInput from '~/components/LanguageInput'
 import Layout from '~/components/Layout'
 import ModelOptions from '~/components/Params'
 import TextArea from '~/components/TextArea'
+import { formatTimestamp } from '~/lib/transcript'
 import { cx } from '~/lib/utils'
 import AudioInput from '~/pages/home/AudioInput'
 import AudioPlayer from './AudioPlayer'
@@ -17,6 +18,8 @@ import { ReactComponent as DashboardIcon } from '~/icons/file.svg'
 import Dashboard from './Dashboard'
 import Summary from '~/components/Summary'
 import Chat from '~/components/Chat'
+import Translation from '~/components/Translation'
+import Chapters from '~/components/Chapters'
 
 export default function Home() {
 	const { t } = useTranslation()
@@ -87,12 +90,17 @@ export default function Home() {
 								<button onMouseDown={vm.transcribe} className="btn btn-primary mt-3">
 									{t('common.transcribe')}
 								</button>
+								{vm.resumeFrom !== null && vm.segments && (
+									<button onMouseDown={vm.resumeTranscribe} className="btn btn-outline btn-primary mt-2">
+										{t('common.resume-transcription', { time: formatTimestamp(vm.resumeFrom, false, '.', false) })}
+									</button>
+								)}
 								<ModelOptions options={vm.preference.modelOptions} setOptions={vm.preference.setModelOptions} />
 							</>
 						)}
 					</div>
 					<div className="h-20" />
-					{vm.loading && <ProgressPanel isAborting={vm.isAborting} onAbort={vm.onAbort} progress={vm.progress} />}
+					{vm.loading && <ProgressPanel isAborting={vm.isAborting} onAbort={vm.onAbort} progress={vm.progress} jobsAhead={vm.jobsAhead} />}
 					{(vm.segments || vm.loading) && (
 						<div ref={transcriptRef} className="flex flex-col mt-5 items-center w-[90%] max-w-[1000px] h-[84vh] m-auto">
 							<div className="tabs tabs-boxed mb-4">
@@ -114,6 +122,18 @@ export default function Home() {
 								>
 								{t('common.chat')}
 							</a>
+							<a 
+								className={`tab ${vm.activeTab === 'translation' ? 'tab-active' : ''}`}
+								onClick={() => vm.setActiveTab('translation')}
+								>
+								{t('common.translation')}
+							</a>
+							<a 
+								className={`tab ${vm.activeTab === 'chapters' ? 'tab-active' : ''}`}
+								onClick={() => vm.setActiveTab('chapters')}
+								>
+								{t('common.chapters')}
+							</a>
 							</div>
 							{vm.activeTab === 'transcript' && (
 							<TextArea
@@ -142,9 +162,20 @@ export default function Home() {
 										segments={vm.segments} 
 										messages={vm.messages}
 										setMessages={vm.setMessages}
+										file={vm.files?.[0]}
 									/>
 								</div>
 							)}
+							{vm.activeTab === 'translation' && (
+								<div className="flex flex-col mt-5 items-center w-[100%] max-w-[1000px] h-[84vh] m-auto">
+									<Translation segments={vm.segments} file={vm.files?.[0]} />
+								</div>
+							)}
+							{vm.activeTab === 'chapters' && (
+								<div className="flex flex-col mt-5 items-center w-[100%] max-w-[1000px] h-[84vh] m-auto">
+									<Chapters file={vm.files?.[0]} />
+								</div>
+							)}
 						</div>
 					)}
 				</>
@@ -4,9 +4,10 @@ interface ProgressPanelProps {
 	isAborting: boolean
 	onAbort: () => void
 	progress: number | null
+	jobsAhead?: number | null
 }
 
-export default function ProgressPanel({ isAborting, onAbort, progress }: ProgressPanelProps) {
+export default function ProgressPanel({ isAborting, onAbort, progress, jobsAhead }: ProgressPanelProps) {
 	const { t } = useTranslation()
 	return (
 		<div className="w-full flex flex-col items-center">
@@ -14,6 +15,8 @@ export default function ProgressPanel({ isAborting, onAbort, progress }: Progres
 				<span className="loading loading-spinner text-primary"></span>
 				{isAborting ? (
 					<p>{t('common.aborting')}...</p>
+				) : jobsAhead ? (
+					<p>{t('common.waiting-for-model', { count: jobsAhead })}</p>
 				) : (
 					<p>
 						{t('common.transcribing')} {progress ? `${Math.round(progress)}%` : '0%'}
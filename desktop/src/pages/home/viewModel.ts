@@ -36,10 +36,16 @@ export function viewModel() {
 	const [loading, setLoading] = useState(false)
 	const [isRecording, setIsRecording] = useState(false)
 	const abortRef = useRef<boolean>(false)
+	// Id of the running transcription, so an abort or queue update only concerns this job
+	const jobIdRef = useRef<string | null>(null)
 	const [isAborting, setIsAborting] = useState(false)
 	const [segments, setSegments] = useState<transcript.Segment[] | null>(null)
 	const [audio, setAudio] = useState<HTMLAudioElement | null>(null)
 	const [progress, setProgress] = useState<number | null>(0)
+	// Transcriptions ahead of this one while the model is busy
+	const [jobsAhead, setJobsAhead] = useState<number | null>(null)
+	// Where an aborted transcript stops, to resume from there
+	const [resumeFrom, setResumeFrom] = useState<number | null>(null)
 
 	const { files, setFiles } = useFilesContext()
 	const [tabIndex, setTabIndex] = useState(0)
@@ -49,7 +55,7 @@ export function viewModel() {
 	const [outputDevice, setOutputDevice] = useState<AudioDevice | null>(null)
 	const [summary, setSummary] = useState<string>('')
 	const [summaryPrompt, setSummaryPrompt] = useState<string>('')
-	const [activeTab, setActiveTab] = useState<'transcript' | 'summary' | 'chat'>('transcript')
+	const [activeTab, setActiveTab] = useState<'transcript' | 'summary' | 'chat' | 'translation' | 'chapters'>('transcript')
 	const [messages, setMessages] = useState<Message[]>([]);
 
 	const { updateApp, availableUpdate } = useContext(UpdaterContext)
@@ -68,9 +74,15 @@ export function viewModel() {
 		await listen('transcribe_progress', (event) => {
 			const value = event.payload as number
 			if (value >= 0 && value <= 100) {
+				setJobsAhead(null)
 				setProgress(value)
 			}
 		})
+		await listen<{ job_id: string; ahead: number }>('transcribe_waiting', (event) => {
+			if (event.payload.job_id === jobIdRef.current) {
+				setJobsAhead(event.payload.ahead)
+			}
+		})
 		await listen<transcript.Segment>('new_segment', (event) => {
 			const { payload } = event
 			setSegments((prev) => {
@@ -88,6 +100,10 @@ export function viewModel() {
 				return isDuplicate ? prev : [...prev, payload]
 			})
 		})
+		// Segments of the selected model replacing the quick draft
+		await listen<transcript.Replacement>('replace_segments', (event) => {
+			setSegments((prev) => transcript.applyReplacement(prev ?? [], event.payload))
+		})
 	}
 
 	async function handleRecordFinish() {
@@ -123,7 +139,7 @@ export function viewModel() {
 	async function onAbort() {
 		setIsAborting(true)
 		abortRef.current = true
-		event.emit('abort_transcribe')
+		event.emit('abort_transcribe', { job_id: jobIdRef.current })
 	}
 
 	async function selectFiles() {
@@ -137,14 +153,15 @@ export function viewModel() {
 			],
 		})
 		if (selected) {
+			setResumeFrom(null)
 			const newFiles: NamedPath[] = []
 			const dbManager = getDbManager()
 			for (const file of selected) {
 				newFiles.push({ name: file.name ?? '', path: file.path })
 
 				// We need to check if the filepath already exists in file_path in db
-				const existingRecordings = await dbManager.select<{id: number, name: string, transcription: string}>(
-					`SELECT r.id, r.name, ri.transcription 
+				const existingRecordings = await dbManager.select<{id: number, name: string, transcription: string, resume_from: number | null}>(
+					`SELECT r.id, r.name, ri.transcription, ri.resume_from
 					 FROM recording r
 					 LEFT JOIN recording_insights ri ON r.file_name = ri.file_name
 					 WHERE r.file_path = :filePath`,
@@ -154,6 +171,7 @@ export function viewModel() {
 				if(Array.isArray(existingRecordings) && existingRecordings.length > 0 && existingRecordings[0].transcription) {
 					try{	
 						if (existingRecordings[0].transcription) setSegments(JSON.parse(existingRecordings[0].transcription))
+						setResumeFrom(existingRecordings[0].resume_from ?? null)
 					}
 					catch{
 						continue;
@@ -265,21 +283,34 @@ export function viewModel() {
 
 	async function transcribe() {
 		setSegments(null)
+		setResumeFrom(null)
 		setLoading(true)
 		abortRef.current = false
+		const jobId = crypto.randomUUID()
+		jobIdRef.current = jobId
 		let res: transcript.Transcript;
 		try {
-			await invoke('load_model', { modelPath: preference.modelPath, gpuDevice: preference.gpuDevice })
+			// Remote servers don't need the local model
+			if (preference.modelOptions.backend?.type !== 'remote') {
+				await invoke('load_model', {
+					modelPath: preference.modelPath,
+					gpuDevice: preference.gpuDevice,
+					idleTimeoutMins: preference.modelIdleTimeout,
+					draftModelPath: preference.twoPass ? preference.draftModelPath : null,
+				})
+			}
 			const options = {
 				path: files[0].path,
 				...preference.modelOptions,
 			}
 			const startTime = performance.now()
-			const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, enabled: preference.recognizeSpeakers }
+			const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, min_speakers: preference.minSpeakers, enabled: preference.recognizeSpeakers, mode: preference.diarizeMode }
 			res = await invoke('transcribe', {
 				options,
 				modelPath: preference.modelPath,
 				diarizeOptions,
+				twoPass: preference.twoPass && !!preference.draftModelPath,
+				jobId,
 			})
 
 			// Calcualte time
@@ -287,6 +318,7 @@ export function viewModel() {
 			console.info(`Transcribe took ${total} seconds.`)
 
 			setSegments(res.segments)
+			setResumeFrom(res.resume_from ?? null)
 			// Store or update transcription in the database
 			const dbManager = getDbManager();
 			const fileName = files[0].name;
@@ -303,7 +335,7 @@ export function viewModel() {
 			// Check if insights already exist for this recording
 			let recordingId: number = existingRecordings[0].id;
 			  await dbManager.update('recording', 
-				{ status: 'TRANSCRIPTION_COMPLETED' },
+				{ status: res.resume_from != null ? 'TRANSCRIPTION_PARTIAL' : 'TRANSCRIPTION_COMPLETED' },
 				'id = :id',
 				{ id: recordingId }
 			  );
@@ -318,7 +350,7 @@ export function viewModel() {
 			if (existingInsights.length > 0) {
 			  // Update existing insights
 			  await dbManager.update('recording_insights',
-				{ transcription: transcriptionText },
+				{ transcription: transcriptionText, resume_from: res.resume_from ?? null },
 				'file_name = :fileName',
 				{ fileName }
 			  );
@@ -327,6 +359,7 @@ export function viewModel() {
 			  await dbManager.insert('recording_insights', {
 				file_name: fileName,
 				transcription: transcriptionText,
+				resume_from: res.resume_from ?? null,
 			  });
 			}
 		} catch (error) {
@@ -339,6 +372,7 @@ export function viewModel() {
 			setLoading(false)
 			setIsAborting(false)
 			setProgress(null)
+			setJobsAhead(null)
 			if (!abortRef.current) {
 				// Focus back the window and play sound
 				if (preference.soundOnFinish) {
@@ -352,19 +386,58 @@ export function viewModel() {
 		}
 	}
 
+	// Transcribe what an aborted transcription left out, the new segments are added after the current ones
+	async function resumeTranscribe() {
+		setLoading(true)
+		abortRef.current = false
+		const jobId = crypto.randomUUID()
+		jobIdRef.current = jobId
+		try {
+			// Remote servers don't need the local model
+			if (preference.modelOptions.backend?.type !== 'remote') {
+				await invoke('load_model', {
+					modelPath: preference.modelPath,
+					gpuDevice: preference.gpuDevice,
+					idleTimeoutMins: preference.modelIdleTimeout,
+					draftModelPath: preference.twoPass ? preference.draftModelPath : null,
+				})
+			}
+			const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, min_speakers: preference.minSpeakers, enabled: preference.recognizeSpeakers, mode: preference.diarizeMode }
+			const res = await invoke<transcript.Transcript>('resume_transcribe', {
+				fileName: files[0].name,
+				options: { path: files[0].path, ...preference.modelOptions },
+				diarizeOptions,
+				jobId,
+			})
+			setSegments(res.segments)
+			setResumeFrom(res.resume_from ?? null)
+		} catch (error) {
+			if (!abortRef.current) {
+				console.error('error: ', error)
+				setErrorModal?.({ log: String(error), open: true })
+			}
+		} finally {
+			setLoading(false)
+			setIsAborting(false)
+			setProgress(null)
+			setJobsAhead(null)
+		}
+	}
+
 	async function handleRecordingClick(recording: Recording) {
 		setTabIndex(0)
 		setFiles([{ name: recording.name, path: recording.file_path }])
 		setAudio(new Audio(convertFileSrc(recording.file_path)))
 	
 		const dbManager = getDbManager()
-		const [insights] = await dbManager.select<{ transcription: string, summary: string, summary_prompt: string }>(
-		  'SELECT transcription, summary, summary_prompt FROM recording_insights WHERE file_name = :fileName',
+		const [insights] = await dbManager.select<{ transcription: string, summary: string, summary_prompt: string, resume_from: number | null }>(
+		  'SELECT transcription, summary, summary_prompt, resume_from FROM recording_insights WHERE file_name = :fileName',
 		  { fileName: recording.name }
 		)
 	
 		if (insights && insights.transcription) {
 		  setSegments(JSON.parse(insights.transcription))
+		  setResumeFrom(insights.resume_from ?? null)
 		  setSummary(insights.summary)
 		  if (!insights.summary_prompt) {
 			setSummaryPrompt('')
@@ -374,6 +447,7 @@ export function viewModel() {
 		  }
 		} else {
 		  setSegments(null)
+		  setResumeFrom(null)
 		}
 	}
 
@@ -407,6 +481,7 @@ export function viewModel() {
 		setSettingsVisible,
 		loading,
 		progress,
+		jobsAhead,
 		audio,
 		setAudio,
 		files,
@@ -416,6 +491,8 @@ export function viewModel() {
 		segments,
 		setSegments,
 		transcribe,
+		resumeFrom,
+		resumeTranscribe,
 		onAbort,
 		tabIndex,
 		setTabIndex,
@@ -11,7 +11,7 @@ import { ReactComponent as CopyIcon } from '~/icons/copy.svg'
 import * as config from '~/lib/config'
 import { supportedLanguages } from '~/lib/i18n'
 import { supportedChatStrategies, supportedGeminiModels } from '~/lib/config'
-import { ModifyState, cx } from '~/lib/utils'
+import { ModifyState, cx, formatBytes } from '~/lib/utils'
 import { viewModel } from './viewModel'
 import * as os from '@tauri-apps/plugin-os'
 import { useEffect, useState } from 'react'
@@ -279,6 +279,75 @@ export default function SettingsPage({ setVisible }: SettingsPageProps) {
 				</button>
 			</div>
 
+			{vm.modelInfo && (
+				<div className="mt-2 text-sm opacity-80">
+					{t('common.model-info', {
+						type: vm.modelInfo.model_type,
+						languages: vm.modelInfo.multilingual ? t('common.multilingual') : t('common.english-only'),
+						ftype: vm.modelInfo.ftype,
+						size: formatBytes(vm.modelInfo.file_size),
+					})}
+				</div>
+			)}
+			{vm.modelError && <div className="mt-2 text-sm text-error">{vm.modelError}</div>}
+			{vm.modelStatus && (
+				<div className="flex items-center gap-2 mt-2 text-sm opacity-80">
+					<span className="flex-1">
+						{vm.modelStatus.loaded
+							? t('common.model-loaded', { size: formatBytes(vm.modelStatus.model_bytes ?? 0), states: vm.modelStatus.states })
+							: vm.modelStatus.loading
+							? t('common.model-loading')
+							: t('common.model-unloaded')}
+					</span>
+					{vm.modelStatus.loaded && (
+						<button onMouseDown={vm.unloadModel} className="btn btn-sm bg-base-300 text-base-content">
+							{t('common.unload-model')}
+						</button>
+					)}
+				</div>
+			)}
+			<label className="form-control w-full py-2">
+				<span className="label-text flex items-center gap-1 cursor-default">
+					<InfoTooltip text={t('common.info-model-idle-timeout')} />
+					{t('common.model-idle-timeout')}
+				</span>
+				<input
+					value={vm.preference.modelIdleTimeout}
+					onChange={(e) => vm.preference.setModelIdleTimeout(Math.max(parseInt(e.target.value) || 0, 0))}
+					className="input input-bordered"
+					type="number"
+					min={0}
+				/>
+			</label>
+			<div className="form-control w-full">
+				<label className="label cursor-pointer">
+					<span className="label-text flex items-center gap-1 cursor-default">
+						<InfoTooltip text={t('common.info-two-pass')} />
+						{t('common.two-pass')}
+					</span>
+					<input
+						type="checkbox"
+						className="toggle toggle-primary"
+						checked={vm.preference.twoPass}
+						onChange={() => vm.preference.setTwoPass(!vm.preference.twoPass)}
+					/>
+				</label>
+			</div>
+			{vm.preference.twoPass && (
+				<select
+					onFocus={vm.loadModels}
+					onChange={(e) => vm.preference.setDraftModelPath(e.target.value || null)}
+					value={vm.preference.draftModelPath ?? ''}
+					className="select select-bordered w-full">
+					<option value="">{t('common.select-draft-model')}</option>
+					{vm.models.map((model, index) => (
+						<option key={index} value={model.path}>
+							{model.name}
+						</option>
+					))}
+				</select>
+			)}
+
 			<div className="label mt-10">
 				<span className="label-text">{t('common.advanced')}</span>
 			</div>
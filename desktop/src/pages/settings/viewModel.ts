@@ -14,6 +14,28 @@ import * as clipboard from '@tauri-apps/plugin-clipboard-manager'
 
 const store = new Store(config.storeFilename)
 
+export interface ModelInfo {
+	model_type: string
+	multilingual: boolean
+	ftype: string
+	n_vocab: number
+	n_mels: number
+	n_tensors: number
+	file_size: number
+}
+
+export interface ModelStatus {
+	path: string
+	gpu_device?: number
+	loaded: boolean
+	loading: boolean
+	info: ModelInfo
+	model_bytes?: number
+	states: number
+	idle_secs?: number
+	idle_timeout_secs?: number
+}
+
 async function openModelPath() {
 	let dst = await invoke<string>('get_models_folder')
 	invoke('open_path', { path: dst })
@@ -61,6 +83,9 @@ export function viewModel() {
 	const listenersRef = useRef<UnlistenFn[]>([])
 	const [downloadURL, setDownloadURL] = useState('')
 	const navigate = useNavigate()
+	const [modelStatus, setModelStatus] = useState<ModelStatus | null>(null)
+	const [modelInfo, setModelInfo] = useState<ModelInfo | null>(null)
+	const [modelError, setModelError] = useState<string | null>(null)
 
 	async function askAndReset() {
 		const yes = await ask(t('common.reset-ask-dialog'), { kind: 'info' })
@@ -92,6 +117,15 @@ export function viewModel() {
 		setModels(found)
 	}
 
+	async function loadModelStatus() {
+		setModelStatus(await invoke<ModelStatus | null>('get_model_status'))
+	}
+
+	async function unloadModel() {
+		await invoke('unload_model')
+		await loadModelStatus()
+	}
+
 	async function getDefaultModel() {
 		if (!preference.modelPath) {
 			const modelsFolder = await invoke<string>('get_models_folder')
@@ -116,12 +150,18 @@ export function viewModel() {
 	}
 
 	async function onWindowFocus() {
-		listenersRef.current.push(await listen('tauri://focus', loadModels))
+		listenersRef.current.push(
+			await listen('tauri://focus', () => {
+				loadModels()
+				loadModelStatus()
+			})
+		)
 	}
 
 	useEffect(() => {
 		loadMeta()
 		loadModels()
+		loadModelStatus()
 		getDefaultModel()
 		onWindowFocus()
 		return () => {
@@ -129,7 +169,16 @@ export function viewModel() {
 		}
 	}, [])
 
-	useEffect(() => {}, [preference])
+	// Check the chosen model before it's loaded
+	useEffect(() => {
+		setModelInfo(null)
+		setModelError(null)
+		if (preference.modelPath) {
+			invoke<ModelInfo>('get_model_info', { modelPath: preference.modelPath })
+				.then(setModelInfo)
+				.catch((error) => setModelError(String(error)))
+		}
+	}, [preference.modelPath])
 
 	return {
 		copyLogs,
@@ -148,5 +197,9 @@ export function viewModel() {
 		reportIssue,
 		loadModels,
 		changeModelsFolder,
+		modelStatus,
+		unloadModel,
+		modelInfo,
+		modelError,
 	}
 }
@@ -34,6 +34,12 @@ export interface Preference {
 	setStoreRecordInDocuments: ModifyState<boolean>
 	gpuDevice: number
 	setGpuDevice: ModifyState<number>
+	modelIdleTimeout: number
+	setModelIdleTimeout: ModifyState<number>
+	twoPass: boolean
+	setTwoPass: ModifyState<boolean>
+	draftModelPath: string | null
+	setDraftModelPath: ModifyState<string | null>
 
 	highGraphicsPreference: boolean
 	setHighGraphicsPreference: ModifyState<boolean>
@@ -42,8 +48,12 @@ export interface Preference {
 	setRecognizeSpeakers: ModifyState<boolean>
 	maxSpeakers: number
 	setMaxSpeakers: ModifyState<number>
+	minSpeakers: number
+	setMinSpeakers: ModifyState<number>
 	diarizeThreshold: number
 	setDiarizeThreshold: ModifyState<number>
+	diarizeMode: DiarizeMode
+	setDiarizeMode: ModifyState<DiarizeMode>
 	setLanguageDirections: () => void
 
 	chatModelOptions: ChatModelOptions
@@ -68,8 +78,21 @@ export interface ModelOptions {
 	max_text_ctx?: number
 	word_timestamps?: boolean
 	max_sentence_len?: number
+	vad?: boolean
+	hallucination_filter?: HallucinationFilter
+	temperature_fallback?: boolean
+	backend?: Backend
 }
 
+/** local transcribes with the loaded model, remote on a server with the OpenAI audio API */
+export type Backend = { type: 'local' } | { type: 'remote'; url: string; model: string; api_key?: string; max_upload_mb?: number }
+
+/** flag keeps suspect segments marked, drop removes them */
+export type HallucinationFilter = 'flag' | 'drop'
+
+/** per_segment transcribes every speech segment on its own, word and sentence assign speakers by time overlap */
+export type DiarizeMode = 'per_segment' | 'word' | 'sentence'
+
 export interface ChatModelOptions {
 	strategy: string
 	ollama_base_url?: string
@@ -77,6 +100,9 @@ export interface ChatModelOptions {
 	ollama_api_key?: string
 	gemini_api_key?: string
 	gemini_model?: string
+	temperature?: number
+	top_k?: number
+	top_p?: number
 }
 
 const systemIsDark = window.matchMedia && window.matchMedia('(prefers-color-scheme: dark)').matches
@@ -89,6 +115,11 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 	const [isFirstRun, setIsFirstRun] = useLocalStorage('prefs_first_localstorage_read', true)
 
 	const [gpuDevice, setGpuDevice] = useLocalStorage<number>('prefs_gpu_device', 0)
+	// Minutes, 0 keeps the model loaded
+	const [modelIdleTimeout, setModelIdleTimeout] = useLocalStorage<number>('prefs_model_idle_timeout', 10)
+	// Quick draft with a small model before the selected one
+	const [twoPass, setTwoPass] = useLocalStorage<boolean>('prefs_two_pass', false)
+	const [draftModelPath, setDraftModelPath] = useLocalStorage<string | null>('prefs_draft_model_path', null)
 	const [soundOnFinish, setSoundOnFinish] = useLocalStorage('prefs_sound_on_finish', true)
 	const [focusOnFinish, setFocusOnFinish] = useLocalStorage('prefs_focus_on_finish', true)
 	const [modelPath, setModelPath] = useLocalStorage<string | null>('prefs_model_path', null)
@@ -107,8 +138,11 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		max_sentence_len: 92,
 	})
 	const [recognizeSpeakers, setRecognizeSpeakers] = useLocalStorage<boolean>('prefs_recognize_speakers', false)
-	const [maxSpeakers, setMaxSpeakers] = useLocalStorage<number>('prefs_max_speakers', 5)
+	// 0 lets the number of speakers be estimated
+	const [maxSpeakers, setMaxSpeakers] = useLocalStorage<number>('prefs_max_speakers', 0)
+	const [minSpeakers, setMinSpeakers] = useLocalStorage<number>('prefs_min_speakers', 0)
 	const [diarizeThreshold, setDiarizeThreshold] = useLocalStorage<number>('prefs_diarize_threshold', 0.5)
+	const [diarizeMode, setDiarizeMode] = useLocalStorage<DiarizeMode>('prefs_diarize_mode', 'per_segment')
 	const [storeRecordInDocuments, setStoreRecordInDocuments] = useLocalStorage('prefs_store_record_in_documents', true)
 	const [theme, setTheme] = useLocalStorage<'dark' | 'light'>('prefs_theme', systemIsDark ? 'dark' : 'light')
 	const [highGraphicsPreference, setHighGraphicsPreference] = useLocalStorage<boolean>('prefs_high_graphics_performance', false)
@@ -164,8 +198,12 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		setLanguageDirections: setLanguageDefaults,
 		diarizeThreshold,
 		setDiarizeThreshold,
+		diarizeMode,
+		setDiarizeMode,
 		maxSpeakers,
 		setMaxSpeakers,
+		minSpeakers,
+		setMinSpeakers,
 		highGraphicsPreference,
 		setHighGraphicsPreference,
 		recognizeSpeakers,
@@ -192,6 +230,12 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		setTheme,
 		gpuDevice,
 		setGpuDevice,
+		modelIdleTimeout,
+		setModelIdleTimeout,
+		twoPass,
+		setTwoPass,
+		draftModelPath,
+		setDraftModelPath,
 		chatModelOptions,
 		setChatModelOptions,
 	}
@@ -0,0 +1,415 @@
+// src/chapters.rs
+
+use crate::embeddings::generate_embeddings;
+use crate::text_generation::{generate_text, TextGenerationOptions, TextGenerationStrategy};
+use crate::translation::{one_line, parse_numbered_lines};
+use eyre::Result;
+use samwise_vector::cosine_similarity;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+/// Characters of a chapter sent to the model to title it
+const TITLE_EXCERPT_CHARS: usize = 1500;
+
+/// Transcript text with its time range, in centiseconds like the transcript segments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedText {
+    pub start: i64,
+    pub stop: i64,
+    pub text: String,
+}
+
+/// A titled part of a recording, times in centiseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: i64,
+    pub stop: i64,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterMethod {
+    /// Topic shifts where neighbouring windows of the transcript stop being similar, titled by the model afterwards
+    Embeddings,
+    /// The model reads the whole transcript and picks the chapters and their titles
+    Llm,
+}
+
+/// How to split a transcript into chapters
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChapterOptions {
+    #[serde(default = "default_method")]
+    pub method: ChapterMethod,
+    /// Shortest chapter, in seconds
+    #[serde(default = "default_min_chapter_secs")]
+    pub min_chapter_secs: u32,
+    #[serde(default = "default_max_chapters")]
+    pub max_chapters: usize,
+    /// Characters of transcript in a window, the unit chapters start at
+    #[serde(default = "default_window_chars")]
+    pub window_chars: usize,
+}
+
+fn default_method() -> ChapterMethod {
+    ChapterMethod::Embeddings
+}
+
+fn default_min_chapter_secs() -> u32 {
+    120
+}
+
+fn default_max_chapters() -> usize {
+    20
+}
+
+fn default_window_chars() -> usize {
+    600
+}
+
+impl Default for ChapterOptions {
+    fn default() -> Self {
+        ChapterOptions {
+            method: default_method(),
+            min_chapter_secs: default_min_chapter_secs(),
+            max_chapters: default_max_chapters(),
+            window_chars: default_window_chars(),
+        }
+    }
+}
+
+/// Split the segments of a transcript, sorted by time, into titled chapters.
+/// The first chapter starts with the first segment and every chapter ends where the next one starts.
+pub async fn detect_chapters(
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+    chapter_options: &ChapterOptions,
+    segments: &[TimedText],
+) -> Result<Vec<Chapter>> {
+    let windows = group_windows(segments, chapter_options.window_chars.max(1));
+    if windows.is_empty() {
+        return Ok(Vec::new());
+    }
+    let min_gap = chapter_options.min_chapter_secs as i64 * 100;
+    let max_chapters = chapter_options.max_chapters.max(1);
+
+    let (starts, titles) = match chapter_options.method {
+        ChapterMethod::Embeddings => {
+            let texts: Vec<String> = windows.iter().map(|window| window.text.clone()).collect();
+            let embeddings = generate_embeddings(strategy, options, &texts).await?;
+            let similarities = gap_similarities(&embeddings, 2);
+            let mut starts = vec![0];
+            starts.extend(pick_boundaries(&windows, &similarities, min_gap, max_chapters));
+            let titles = title_chapters(strategy, options, &windows, &starts).await?;
+            (starts, titles)
+        }
+        ChapterMethod::Llm => {
+            let response = generate_text(strategy, options, outline_messages(&windows, max_chapters)).await?;
+            parse_outline(&response, &windows, min_gap)
+        }
+    };
+    debug!("found {} chapters in {} windows", starts.len(), windows.len());
+
+    let end = windows.last().map(|window| window.stop).unwrap_or_default();
+    Ok(starts
+        .iter()
+        .zip(titles)
+        .enumerate()
+        .map(|(position, (&start, title))| Chapter {
+            start: windows[start].start,
+            stop: starts.get(position + 1).map(|&next| windows[next].start).unwrap_or(end),
+            title,
+        })
+        .collect())
+}
+
+/// Consecutive segments joined until they reach `window_chars`, a segment is never split
+fn group_windows(segments: &[TimedText], window_chars: usize) -> Vec<TimedText> {
+    let mut windows: Vec<TimedText> = Vec::new();
+    let mut current: Option<TimedText> = None;
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let window = current.get_or_insert_with(|| TimedText {
+            start: segment.start,
+            stop: segment.stop,
+            text: String::new(),
+        });
+        if !window.text.is_empty() {
+            window.text.push(' ');
+        }
+        window.text.push_str(text);
+        window.stop = segment.stop;
+        if window.text.len() >= window_chars {
+            windows.extend(current.take());
+        }
+    }
+    windows.extend(current);
+    windows
+}
+
+/// Similarity across each gap between windows, comparing the average of up to `depth` windows on each side.
+/// Item `i` is the gap before window `i + 1`.
+fn gap_similarities(embeddings: &[Vec<f32>], depth: usize) -> Vec<f32> {
+    (1..embeddings.len())
+        .map(|gap| {
+            let before = mean(&embeddings[gap.saturating_sub(depth)..gap]);
+            let after = mean(&embeddings[gap..(gap + depth).min(embeddings.len())]);
+            cosine_similarity(&before, &after)
+        })
+        .collect()
+}
+
+fn mean(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let mut sum = vec![0.0; vectors.first().map(Vec::len).unwrap_or_default()];
+    for vector in vectors {
+        for (total, value) in sum.iter_mut().zip(vector) {
+            *total += value;
+        }
+    }
+    sum.iter().map(|total| total / vectors.len() as f32).collect()
+}
+
+/// Windows where a chapter starts, by how deep the similarity dips at each gap compared to the peaks around it.
+/// Only dips deeper than the average by half a standard deviation count, and chapters keep `min_gap` apart.
+fn pick_boundaries(windows: &[TimedText], similarities: &[f32], min_gap: i64, max_chapters: usize) -> Vec<usize> {
+    let depths: Vec<f32> = (0..similarities.len())
+        .map(|gap| {
+            let value = similarities[gap];
+            let mut left = value;
+            for &previous in similarities[..gap].iter().rev() {
+                if previous < left {
+                    break;
+                }
+                left = previous;
+            }
+            let mut right = value;
+            for &next in &similarities[gap + 1..] {
+                if next < right {
+                    break;
+                }
+                right = next;
+            }
+            (left - value) + (right - value)
+        })
+        .collect();
+    if depths.is_empty() {
+        return Vec::new();
+    }
+    let average = depths.iter().sum::<f32>() / depths.len() as f32;
+    let deviation = (depths.iter().map(|depth| (depth - average).powi(2)).sum::<f32>() / depths.len() as f32).sqrt();
+    let cutoff = average + deviation / 2.0;
+
+    let mut candidates: Vec<usize> = (0..depths.len())
+        .filter(|&gap| depths[gap] > 0.0 && depths[gap] >= cutoff)
+        .collect();
+    candidates.sort_by(|a, b| depths[*b].total_cmp(&depths[*a]));
+
+    let first = windows.first().map(|window| window.start).unwrap_or_default();
+    let end = windows.last().map(|window| window.stop).unwrap_or_default();
+    let mut starts: Vec<i64> = vec![first];
+    let mut picked = Vec::new();
+    for gap in candidates {
+        if picked.len() + 1 >= max_chapters {
+            break;
+        }
+        let window = gap + 1;
+        let start = windows[window].start;
+        if end - start < min_gap || starts.iter().any(|other| (start - other).abs() < min_gap) {
+            continue;
+        }
+        starts.push(start);
+        picked.push(window);
+    }
+    picked.sort_unstable();
+    picked
+}
+
+/// A short title for each chapter from the beginning of its text, "Chapter n" when the answer can't be read
+async fn title_chapters(
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+    windows: &[TimedText],
+    starts: &[usize],
+) -> Result<Vec<String>> {
+    let excerpts: Vec<String> = starts
+        .iter()
+        .enumerate()
+        .map(|(position, &start)| {
+            let end = starts.get(position + 1).copied().unwrap_or(windows.len());
+            let text: String = windows[start..end]
+                .iter()
+                .map(|window| window.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let excerpt: String = one_line(&text).chars().take(TITLE_EXCERPT_CHARS).collect();
+            format!("[{}] {}", position + 1, excerpt)
+        })
+        .collect();
+    let messages = vec![
+        json!({"role": "system", "content": "You title the chapters of a transcript. \
+Every input line is the beginning of one chapter, prefixed with its number in square brackets. \
+Answer with exactly one line per chapter, prefixed with the same number: a title of at most six words \
+that names its topic, in the language of the transcript. Answer with the titled lines only."}),
+        json!({"role": "user", "content": excerpts.join("\n")}),
+    ];
+    let response = generate_text(strategy, options, messages).await?;
+    Ok(match parse_numbered_lines(&response, starts.len()) {
+        Some(titles) => titles.into_iter().map(|title| clean_title(&title)).collect(),
+        None => {
+            warn!("chapter titles lost lines, numbering them instead");
+            (1..=starts.len()).map(|number| format!("Chapter {}", number)).collect()
+        }
+    })
+}
+
+fn outline_messages(windows: &[TimedText], max_chapters: usize) -> Vec<Value> {
+    let system = format!(
+        "You split a transcript into chapters where the topic changes. \
+Every input line is a numbered part of the transcript with its start time. \
+Answer with one line per chapter: the number of the part where it starts in square brackets, \
+then a title of at most six words that names its topic, in the language of the transcript. \
+The first chapter starts at [1]. Make at most {} chapters. Answer with the chapter lines only.",
+        max_chapters
+    );
+    let lines: Vec<String> = windows
+        .iter()
+        .enumerate()
+        .map(|(index, window)| format!("[{}] {} {}", index + 1, short_timestamp(window.start), one_line(&window.text)))
+        .collect();
+    vec![
+        json!({"role": "system", "content": system}),
+        json!({"role": "user", "content": lines.join("\n")}),
+    ]
+}
+
+/// Chapter starts and titles of the model's outline, in order, chapters closer than `min_gap` merged into the previous one.
+/// The first chapter always starts with the first window.
+fn parse_outline(response: &str, windows: &[TimedText], min_gap: i64) -> (Vec<usize>, Vec<String>) {
+    let mut outline: Vec<(usize, String)> = response
+        .lines()
+        .filter_map(|line| {
+            let (number, title) = line.trim().strip_prefix('[')?.split_once(']')?;
+            let index = number.trim().parse::<usize>().ok()?.checked_sub(1)?;
+            (index < windows.len()).then(|| (index, clean_title(title)))
+        })
+        .collect();
+    outline.sort_by_key(|(index, _)| *index);
+    outline.dedup_by_key(|(index, _)| *index);
+
+    let mut starts: Vec<usize> = Vec::new();
+    let mut titles: Vec<String> = Vec::new();
+    for (index, title) in outline {
+        match starts.last() {
+            None => starts.push(0),
+            Some(&previous) if windows[index].start - windows[previous].start < min_gap => continue,
+            Some(_) => starts.push(index),
+        }
+        titles.push(title);
+    }
+    if starts.is_empty() {
+        warn!("the chapter outline has no chapter lines");
+        starts.push(0);
+        titles.push("Chapter 1".to_string());
+    }
+    (starts, titles)
+}
+
+/// Title without the timestamp or markdown the model may repeat
+fn clean_title(title: &str) -> String {
+    let title = title.trim().trim_matches(|c: char| c == '*' || c == '#' || c == '"').trim();
+    let title = match title.split_once(' ') {
+        Some((time, rest)) if !time.is_empty() && time.chars().all(|c| c.is_ascii_digit() || c == ':') => rest.trim(),
+        _ => title,
+    };
+    title.trim_start_matches(['-', ':', '–']).trim().to_string()
+}
+
+/// WebVTT chapters track, one cue per chapter
+pub fn to_webvtt(chapters: &[Chapter]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for (index, chapter) in chapters.iter().enumerate() {
+        vtt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            vtt_timestamp(chapter.start),
+            vtt_timestamp(chapter.stop),
+            one_line(&chapter.title)
+        ));
+    }
+    vtt
+}
+
+/// The transcript under a heading per chapter, with the time it starts at
+pub fn to_markdown(chapters: &[Chapter], segments: &[TimedText]) -> String {
+    let mut markdown = String::new();
+    for chapter in chapters {
+        let text: Vec<&str> = segments
+            .iter()
+            .filter(|segment| segment.start >= chapter.start && segment.start < chapter.stop)
+            .map(|segment| segment.text.trim())
+            .filter(|text| !text.is_empty())
+            .collect();
+        markdown.push_str(&format!(
+            "## {} ({})\n\n",
+            one_line(&chapter.title),
+            short_timestamp(chapter.start)
+        ));
+        if !text.is_empty() {
+            markdown.push_str(&format!("{}\n\n", text.join(" ")));
+        }
+    }
+    markdown
+}
+
+/// ffmpeg metadata file with chapter markers, applied to the audio with
+/// `ffmpeg -i audio -i chapters.txt -map_metadata 1 -codec copy output`
+pub fn to_ffmetadata(chapters: &[Chapter]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        metadata.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.start * 10,
+            chapter.stop * 10,
+            escape_ffmetadata(&one_line(&chapter.title))
+        ));
+    }
+    metadata
+}
+
+/// '=', ';', '#' and '\' are special in ffmetadata values
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// HH:MM:SS.mmm from centiseconds
+fn vtt_timestamp(centiseconds: i64) -> String {
+    let millis = centiseconds.max(0) * 10;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// MM:SS, or H:MM:SS past an hour, from centiseconds
+fn short_timestamp(centiseconds: i64) -> String {
+    let seconds = centiseconds.max(0) / 100;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -0,0 +1,129 @@
+// src/embeddings.rs
+
+use crate::error::TextGenerationError;
+use crate::http::{read_json, send_with_retry, shared_client};
+use crate::text_generation::{TextGenerationOptions, TextGenerationStrategy};
+use eyre::{eyre, Result};
+pub use samwise_vector::cosine_similarity;
+use serde_json::{json, Value};
+use tracing::{debug, error};
+
+/// Maximum number of inputs sent in a single embedding request
+const EMBEDDING_BATCH_SIZE: usize = 64;
+
+pub async fn generate_embeddings(
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let mut embeddings = Vec::with_capacity(inputs.len());
+    for batch in inputs.chunks(EMBEDDING_BATCH_SIZE) {
+        let batch_embeddings = match strategy {
+            TextGenerationStrategy::Ollama => generate_embeddings_ollama(options, batch).await?,
+            TextGenerationStrategy::GoogleGemini => generate_embeddings_gemini(options, batch).await?,
+        };
+        if batch_embeddings.len() != batch.len() {
+            return Err(eyre!(
+                "Expected {} embeddings but received {}",
+                batch.len(),
+                batch_embeddings.len()
+            ));
+        }
+        embeddings.extend(batch_embeddings);
+    }
+    Ok(embeddings)
+}
+
+/// Name of the embedding model used by the given strategy, stored next to each vector
+pub fn embedding_model_name(strategy: TextGenerationStrategy, options: &TextGenerationOptions) -> String {
+    match strategy {
+        TextGenerationStrategy::Ollama => options.ollama_embedding_model.clone(),
+        TextGenerationStrategy::GoogleGemini => options.gemini_embedding_model.clone(),
+    }
+}
+
+async fn generate_embeddings_ollama(options: &TextGenerationOptions, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let client = shared_client(&options.http)?;
+    let url = format!("{}/v1/embeddings", options.ollama_base_url);
+
+    debug!("Sending embedding request to Ollama API: {}", url);
+
+    let request = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", options.ollama_api_key))
+        .json(&json!({
+            "model": options.ollama_embedding_model,
+            "input": inputs,
+        }));
+    let response = send_with_retry(request, &options.http, "Ollama API").await?;
+    let response_body = read_json(response, "Ollama API").await?;
+
+    let data = response_body["data"].as_array().ok_or_else(|| {
+        error!("Failed to extract embeddings from Ollama API: {}", response_body);
+        TextGenerationError::InvalidResponse {
+            message: "Failed to extract embeddings from Ollama API".to_string(),
+        }
+    })?;
+
+    // The OpenAI format carries an index per item, don't rely on the order
+    let mut embeddings = vec![Vec::new(); data.len()];
+    for (position, item) in data.iter().enumerate() {
+        let index = item["index"].as_u64().map(|i| i as usize).unwrap_or(position);
+        let slot = embeddings
+            .get_mut(index)
+            .ok_or_else(|| eyre!("Embedding index {} out of range", index))?;
+        *slot = parse_vector(&item["embedding"])?;
+    }
+    Ok(embeddings)
+}
+
+async fn generate_embeddings_gemini(options: &TextGenerationOptions, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let client = shared_client(&options.http)?;
+    let url = format!(
+        "{}/v1beta/models/{}:batchEmbedContents?key={}",
+        options.gemini_base_url, options.gemini_embedding_model, options.google_api_key
+    );
+
+    debug!("Sending embedding request to Google Gemini API");
+
+    let model = format!("models/{}", options.gemini_embedding_model);
+    let requests: Vec<Value> = inputs
+        .iter()
+        .map(|input| {
+            json!({
+                "model": model,
+                "content": { "parts": [{ "text": input }] }
+            })
+        })
+        .collect();
+
+    let request = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .json(&json!({ "requests": requests }));
+    let response = send_with_retry(request, &options.http, "Google Gemini API").await?;
+    let response_body = read_json(response, "Google Gemini API").await?;
+
+    response_body["embeddings"]
+        .as_array()
+        .ok_or_else(|| TextGenerationError::InvalidResponse {
+            message: "The response format was not as expected".to_string(),
+        })?
+        .iter()
+        .map(|embedding| parse_vector(&embedding["values"]))
+        .collect()
+}
+
+fn parse_vector(value: &Value) -> Result<Vec<f32>> {
+    value
+        .as_array()
+        .ok_or_else(|| eyre!("Embedding is not an array"))?
+        .iter()
+        .map(|v| {
+            v.as_f64()
+                .map(|v| v as f32)
+                .ok_or_else(|| eyre!("Embedding value is not a number"))
+        })
+        .collect()
+}
@@ -0,0 +1,70 @@
+// src/error.rs
+
+use serde::Serialize;
+use std::fmt;
+
+/// Failure of a text generation or embedding request, grouped by what the user can do about it
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TextGenerationError {
+    /// Missing, invalid or unauthorized API key
+    Auth { message: String },
+    /// Rate limited or out of quota, still failing after retries
+    Quota { message: String, retry_after_secs: Option<u64> },
+    /// The server couldn't be reached or didn't answer in time
+    Network { message: String },
+    /// The provider refused to answer because of its safety filters
+    ContentFilter { message: String },
+    /// Any other error status returned by the provider
+    Api { status: u16, message: String },
+    /// The provider answered with something we couldn't understand
+    InvalidResponse { message: String },
+}
+
+impl TextGenerationError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TextGenerationError::Auth { .. } => "auth",
+            TextGenerationError::Quota { .. } => "quota",
+            TextGenerationError::Network { .. } => "network",
+            TextGenerationError::ContentFilter { .. } => "content_filter",
+            TextGenerationError::Api { .. } => "api",
+            TextGenerationError::InvalidResponse { .. } => "invalid_response",
+        }
+    }
+}
+
+impl fmt::Display for TextGenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextGenerationError::Auth { message } => {
+                write!(f, "Authentication failed: {}. Please check your API key.", message)
+            }
+            TextGenerationError::Quota {
+                message,
+                retry_after_secs: Some(secs),
+            } => write!(
+                f,
+                "Rate limit or quota exceeded: {}. Please try again in {} seconds.",
+                message, secs
+            ),
+            TextGenerationError::Quota { message, .. } => {
+                write!(f, "Rate limit or quota exceeded: {}. Please try again later.", message)
+            }
+            TextGenerationError::Network { message } => write!(
+                f,
+                "Network error: {}. Please check your internet connection and try again.",
+                message
+            ),
+            TextGenerationError::ContentFilter { message } => {
+                write!(f, "The response was blocked by the provider's content filter: {}", message)
+            }
+            TextGenerationError::Api { status, message } => write!(f, "API Error (Code: {}): {}", status, message),
+            TextGenerationError::InvalidResponse { message } => {
+                write!(f, "Unexpected API response: {}. Please try again later.", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextGenerationError {}
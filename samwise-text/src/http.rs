@@ -0,0 +1,175 @@
+// src/http.rs
+
+use crate::error::TextGenerationError;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{debug, error, warn};
+
+/// Longest we're willing to wait between two attempts, even if the server asks for more
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpOptions {
+    pub connect_timeout_secs: u64,
+    /// Time allowed for the whole response to arrive once connected
+    pub read_timeout_secs: u64,
+    /// Retries on 429 and 5xx responses, on top of the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every following one
+    pub retry_base_delay_ms: u64,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 300,
+            max_retries: 3,
+            retry_base_delay_ms: 1000,
+        }
+    }
+}
+
+/// Clients are expensive to create and keep a connection pool, so share one per connect timeout
+pub fn shared_client(options: &HttpOptions) -> Result<Client, TextGenerationError> {
+    static CLIENTS: OnceLock<Mutex<HashMap<u64, Client>>> = OnceLock::new();
+    let mut clients = CLIENTS
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|e| TextGenerationError::Network { message: e.to_string() })?;
+    if let Some(client) = clients.get(&options.connect_timeout_secs) {
+        return Ok(client.clone());
+    }
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(options.connect_timeout_secs))
+        .build()
+        .map_err(|e| TextGenerationError::Network { message: e.to_string() })?;
+    clients.insert(options.connect_timeout_secs, client.clone());
+    Ok(client)
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Send a request, retrying with exponential backoff on 429 and 5xx responses.
+/// Error statuses are returned as a response once retries are exhausted, see [`read_json`].
+pub async fn send_with_retry(
+    request: RequestBuilder,
+    options: &HttpOptions,
+    provider: &str,
+) -> Result<Response, TextGenerationError> {
+    let request = request.timeout(Duration::from_secs(options.read_timeout_secs));
+    let mut attempt = 0;
+    loop {
+        let current = request.try_clone().ok_or_else(|| TextGenerationError::InvalidResponse {
+            message: "request body can't be retried".to_string(),
+        })?;
+        let response = current.send().await.map_err(|e| {
+            error!("Failed to send request to {}: {}", provider, e);
+            TextGenerationError::Network {
+                message: if e.is_timeout() {
+                    format!("{} did not respond in time", provider)
+                } else {
+                    format!("failed to reach {}", provider)
+                },
+            }
+        })?;
+
+        let status = response.status();
+        if !is_retryable(status) || attempt >= options.max_retries {
+            return Ok(response);
+        }
+
+        let backoff = Duration::from_millis(options.retry_base_delay_ms.saturating_mul(1 << attempt.min(16)));
+        let delay = retry_after(&response).unwrap_or(backoff).min(MAX_RETRY_DELAY);
+        warn!(
+            "{} responded with {}, retrying in {:?} (attempt {}/{})",
+            provider,
+            status,
+            delay,
+            attempt + 1,
+            options.max_retries
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|body| body["error"]["message"].as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| {
+            let body = body.trim();
+            if body.is_empty() {
+                "Unknown error occurred".to_string()
+            } else {
+                body.chars().take(200).collect()
+            }
+        })
+}
+
+fn classify_error(status: StatusCode, retry_after_secs: Option<u64>, body: &str) -> TextGenerationError {
+    let message = error_message(body);
+    let lowercase = message.to_lowercase();
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => TextGenerationError::Auth { message },
+        StatusCode::TOO_MANY_REQUESTS => TextGenerationError::Quota {
+            message,
+            retry_after_secs,
+        },
+        // Gemini answers a bad key with 400 INVALID_ARGUMENT
+        StatusCode::BAD_REQUEST if lowercase.contains("api key") => TextGenerationError::Auth { message },
+        StatusCode::BAD_REQUEST if lowercase.contains("content") && lowercase.contains("polic") => {
+            TextGenerationError::ContentFilter { message }
+        }
+        _ => TextGenerationError::Api {
+            status: status.as_u16(),
+            message,
+        },
+    }
+}
+
+/// Read a JSON response body, turning error statuses into typed errors
+pub async fn read_json(response: Response, provider: &str) -> Result<Value, TextGenerationError> {
+    let status = response.status();
+    let retry_after_secs = retry_after(&response).map(|d| d.as_secs());
+    let body = response.text().await.map_err(|e| {
+        error!("Failed to read {} response: {}", provider, e);
+        TextGenerationError::Network {
+            message: format!("connection to {} was interrupted", provider),
+        }
+    })?;
+
+    if !status.is_success() {
+        let error = classify_error(status, retry_after_secs, &body);
+        error!("{} API error: {} ({})", provider, error, error.kind());
+        return Err(error);
+    }
+
+    debug!("{} responded with {} bytes", provider, body.len());
+    serde_json::from_str(&body).map_err(|e| {
+        error!("Failed to parse {} response: {}", provider, e);
+        TextGenerationError::InvalidResponse {
+            message: "Unable to parse the response".to_string(),
+        }
+    })
+}
@@ -1,4 +1,10 @@
+pub mod chapters;
+pub mod embeddings;
+pub mod error;
+pub mod http;
+pub mod prompt_templates;
 pub mod text_generation;
+pub mod translation;
 
 #[cfg(test)]
 mod test;
@@ -0,0 +1,153 @@
+// src/prompt_templates.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Variables every template can use. Unknown variables are left untouched.
+pub const TEMPLATE_VARIABLES: [&str; 5] = ["transcript", "speakers", "date", "duration", "title"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub body: String,
+    pub builtin: bool,
+}
+
+fn builtin(id: &str, name: &str, description: &str, body: &str) -> PromptTemplate {
+    PromptTemplate {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+        body: body.to_string(),
+        builtin: true,
+    }
+}
+
+pub fn builtin_templates() -> Vec<PromptTemplate> {
+    vec![
+        builtin(
+            "standup",
+            "Standup",
+            "Per person updates, plans and blockers",
+            "The following is the transcript of a standup meeting held on {{date}} ({{duration}}) with {{speakers}}.
+
+For every participant list:
+- What they completed
+- What they are working on next
+- Any blockers, and who can help with them
+
+Finish with a short list of follow ups that need an owner.
+
+Transcript:
+{{transcript}}",
+        ),
+        builtin(
+            "one-on-one",
+            "1:1",
+            "Topics, feedback and agreed next steps of a 1:1",
+            "The following is the transcript of a 1:1 meeting held on {{date}} ({{duration}}) between {{speakers}}.
+
+Summarize it with:
+1. Topics discussed, one line each
+2. Feedback given in either direction
+3. Concerns or risks raised
+4. Agreed next steps with owners and due dates if mentioned
+
+Keep the tone neutral and leave out small talk.
+
+Transcript:
+{{transcript}}",
+        ),
+        builtin(
+            "sales-call",
+            "Sales call",
+            "Customer needs, objections and deal next steps",
+            "The following is the transcript of a sales call held on {{date}} ({{duration}}) with {{speakers}}.
+
+Provide:
+- Customer profile: company, role of the participants, current solution
+- Pain points and requirements in the customer's own words
+- Objections raised and how they were handled
+- Budget, timeline and decision process if mentioned
+- Agreed next steps and open questions
+
+Transcript:
+{{transcript}}",
+        ),
+        builtin(
+            "interview",
+            "Interview",
+            "Candidate answers, strengths and concerns",
+            "The following is the transcript of an interview held on {{date}} ({{duration}}) with {{speakers}}.
+
+Provide:
+- The questions asked, each with a short summary of the answer
+- Strengths demonstrated, with supporting quotes
+- Concerns or gaps, with supporting quotes
+- Questions the candidate asked
+- An overall assessment in two or three sentences
+
+Transcript:
+{{transcript}}",
+        ),
+        builtin(
+            "lecture-notes",
+            "Lecture notes",
+            "Structured study notes with key concepts",
+            "The following is the transcript of a lecture recorded on {{date}} ({{duration}}).
+
+Write study notes in Markdown:
+- A heading per topic, in the order they were covered
+- Key concepts and definitions in bold
+- Examples and formulas mentioned
+- A short recap and a list of review questions at the end
+
+Transcript:
+{{transcript}}",
+        ),
+    ]
+}
+
+pub fn find_builtin_template(id: &str) -> Option<PromptTemplate> {
+    builtin_templates().into_iter().find(|template| template.id == id)
+}
+
+/// Replace `{{variable}}` placeholders with their values.
+/// If the template doesn't reference the transcript, it's appended at the end so the model always receives it.
+pub fn render_template(body: &str, variables: &HashMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(body.len());
+    let mut rest = body;
+    let mut has_transcript = false;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        match after_open.find("}}") {
+            Some(end) => {
+                let name = after_open[..end].trim();
+                match variables.get(name) {
+                    Some(value) => {
+                        has_transcript |= name == "transcript";
+                        rendered.push_str(value);
+                    }
+                    None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after_open[end + 2..];
+            }
+            None => {
+                rendered.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    if !has_transcript {
+        if let Some(transcript) = variables.get("transcript") {
+            rendered.push_str("\n\n");
+            rendered.push_str(transcript);
+        }
+    }
+    rendered
+}
@@ -12,6 +12,9 @@ async fn test_text_generation() {
         max_output_tokens: 1024,
         gemini_model: "gemini-1.5-flash".to_string(),
         ollama_api_key: "openai_api_key".to_string(),
+        ollama_embedding_model: "text-embedding-3-small".to_string(),
+        gemini_embedding_model: "text-embedding-004".to_string(),
+        ..Default::default()
     };
 
     let messages = vec![json!({"role": "user", "content": "Hello! tell me a joke"})];
@@ -30,3 +33,337 @@ async fn test_text_generation() {
         Err(e) => println!("Google Gemini error: {}", e),
     }
 }
+
+#[test]
+fn test_render_prompt_template() {
+    use crate::prompt_templates::{builtin_templates, render_template};
+    use std::collections::HashMap;
+
+    let variables = HashMap::from([
+        ("transcript".to_string(), "[00:00:01] Alice: hello".to_string()),
+        ("speakers".to_string(), "Alice, Bob".to_string()),
+    ]);
+
+    let rendered = render_template("Meeting with {{ speakers }} on {{date}}:\n{{transcript}}", &variables);
+    assert_eq!(rendered, "Meeting with Alice, Bob on {{date}}:\n[00:00:01] Alice: hello");
+
+    // Transcript is appended when the template doesn't reference it
+    let rendered = render_template("Summarize", &variables);
+    assert_eq!(rendered, "Summarize\n\n[00:00:01] Alice: hello");
+
+    for template in builtin_templates() {
+        assert!(
+            template.body.contains("{{transcript}}"),
+            "{} misses the transcript",
+            template.id
+        );
+    }
+}
+
+/// Minimal HTTP server answering each connection with the next canned response.
+/// Returns its base URL and the bodies of the requests it received.
+async fn mock_server(
+    responses: Vec<(u16, &'static str, &'static str)>,
+) -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        for (status, headers, body) in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            // Read headers, then as much body as Content-Length announces
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().to_string())
+                        })
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || read == 0 {
+                        let _ = sender.send(String::from_utf8_lossy(&request[end + 4..]).to_string());
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+                status,
+                body.len(),
+                headers,
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.shutdown().await.ok();
+        }
+    });
+    (url, receiver)
+}
+
+fn mock_options(base_url: &str) -> TextGenerationOptions {
+    TextGenerationOptions {
+        ollama_base_url: base_url.to_string(),
+        gemini_base_url: base_url.to_string(),
+        ollama_model: "mock".to_string(),
+        gemini_model: "mock".to_string(),
+        max_output_tokens: 16,
+        http: crate::http::HttpOptions {
+            retry_base_delay_ms: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_retry_and_typed_errors() {
+    use crate::error::TextGenerationError;
+
+    let ok = r#"{"model": "mock", "choices": [{"message": {"content": "hi"}, "finish_reason": "stop"}]}"#;
+    let (url, _) = mock_server(vec![(503, "", "{}"), (429, "Retry-After: 0\r\n", "{}"), (200, "", ok)]).await;
+    let messages = vec![json!({"role": "user", "content": "hello"})];
+    let text = generate_text(TextGenerationStrategy::Ollama, &mock_options(&url), messages.clone())
+        .await
+        .unwrap();
+    assert_eq!(text, "hi");
+
+    let (url, _) = mock_server(vec![(401, "", r#"{"error": {"message": "bad key"}}"#)]).await;
+    let error = generate_text(TextGenerationStrategy::Ollama, &mock_options(&url), messages.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(error.downcast_ref::<TextGenerationError>().map(|e| e.kind()), Some("auth"));
+
+    let mut options = mock_options(&url);
+    options.http.max_retries = 0;
+    let (url, _) = mock_server(vec![(429, "Retry-After: 7\r\n", "{}")]).await;
+    options.ollama_base_url = url;
+    let error = generate_text(TextGenerationStrategy::Ollama, &options, messages)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TextGenerationError>(),
+        Some(TextGenerationError::Quota {
+            retry_after_secs: Some(7),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_convert_messages_to_gemini_format() {
+    use crate::text_generation::convert_messages_to_gemini_format;
+
+    let messages = vec![
+        json!({"role": "system", "content": "You summarize meetings"}),
+        json!({"role": "user", "content": "Context"}),
+        json!({"role": "user", "content": "Transcript"}),
+        json!({"role": "assistant", "content": "Summary"}),
+        json!({"role": "user", "content": "Shorter"}),
+    ];
+    let (system_instruction, contents) = convert_messages_to_gemini_format(&messages).unwrap();
+    assert_eq!(
+        system_instruction,
+        Some(json!({"parts": [{"text": "You summarize meetings"}]}))
+    );
+    assert_eq!(
+        contents,
+        vec![
+            json!({"role": "user", "parts": [{"text": "Context"}, {"text": "Transcript"}]}),
+            json!({"role": "model", "parts": [{"text": "Summary"}]}),
+            json!({"role": "user", "parts": [{"text": "Shorter"}]}),
+        ]
+    );
+
+    assert!(convert_messages_to_gemini_format(&[json!({"role": "tool", "content": "x"})]).is_err());
+}
+
+#[tokio::test]
+async fn test_gemini_request_body() {
+    let response = r#"{"candidates": [{"content": {"parts": [{"text": "done"}]}, "finishReason": "STOP"}],
+        "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 3}}"#;
+    let (url, mut requests) = mock_server(vec![(200, "", response)]).await;
+    let mut options = mock_options(&url);
+    options.temperature = Some(0.2);
+    options.top_k = Some(10);
+    options.top_p = Some(0.5);
+
+    let messages = vec![
+        json!({"role": "system", "content": "Be brief"}),
+        json!({"role": "user", "content": "hello"}),
+    ];
+    let response = crate::text_generation::generate_text_with_usage(TextGenerationStrategy::GoogleGemini, &options, messages)
+        .await
+        .unwrap();
+    assert_eq!(response.text, "done");
+    assert_eq!(response.prompt_tokens, Some(12));
+
+    let body: serde_json::Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+    assert_eq!(body["systemInstruction"], json!({"parts": [{"text": "Be brief"}]}));
+    assert_eq!(body["contents"], json!([{"role": "user", "parts": [{"text": "hello"}]}]));
+    assert_eq!(body["generationConfig"]["temperature"], json!(0.2f32));
+    assert_eq!(body["generationConfig"]["topK"], json!(10));
+    assert_eq!(body["generationConfig"]["topP"], json!(0.5));
+
+    // Blocked prompts surface as content filter errors
+    let (url, _) = mock_server(vec![(200, "", r#"{"promptFeedback": {"blockReason": "SAFETY"}}"#)]).await;
+    let error = generate_text(
+        TextGenerationStrategy::GoogleGemini,
+        &mock_options(&url),
+        vec![json!({"role": "user", "content": "hello"})],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<crate::error::TextGenerationError>().map(|e| e.kind()),
+        Some("content_filter")
+    );
+
+    // Unset sampling falls back to fixed values for Gemini and is left to the model on OpenAI compatible APIs
+    let gemini = r#"{"candidates": [{"content": {"parts": [{"text": "hi"}]}}]}"#;
+    let ollama = r#"{"choices": [{"message": {"content": "hi"}}]}"#;
+    let (url, mut requests) = mock_server(vec![(200, "", gemini), (200, "", ollama)]).await;
+    let messages = vec![json!({"role": "user", "content": "hello"})];
+    for strategy in [TextGenerationStrategy::GoogleGemini, TextGenerationStrategy::Ollama] {
+        generate_text(strategy, &mock_options(&url), messages.clone()).await.unwrap();
+    }
+    let body: serde_json::Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+    assert_eq!(body["generationConfig"]["temperature"], json!(1.0));
+    assert_eq!(body["generationConfig"]["topK"], json!(64));
+    let body: serde_json::Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+    assert!(body.get("temperature").is_none() && body.get("top_p").is_none(), "{}", body);
+}
+
+#[tokio::test]
+async fn test_translate_segments() {
+    use crate::translation::{translate_segments, TranslationOptions};
+
+    // The first answer merges two segments, so that batch is sent again one segment at a time
+    let (url, mut requests) = mock_server(vec![
+        (200, "", r#"{"choices": [{"message": {"content": "[1] Hallo Welt"}}]}"#),
+        (200, "", r#"{"choices": [{"message": {"content": "Hallo"}}]}"#),
+        (200, "", r#"{"choices": [{"message": {"content": "[1] Welt"}}]}"#),
+        (200, "", r#"{"choices": [{"message": {"content": "Sure!\n[1] Tschüss"}}]}"#),
+    ])
+    .await;
+    let texts: Vec<String> = vec![" Hello".into(), " world.".into(), " Bye\nnow".into()];
+    let translation = TranslationOptions {
+        batch_size: 2,
+        ..TranslationOptions::new("German")
+    };
+    let progress = std::cell::RefCell::new(Vec::new());
+    let translated = translate_segments(
+        TextGenerationStrategy::Ollama,
+        &mock_options(&url),
+        &translation,
+        &texts,
+        |done| progress.borrow_mut().push(done),
+    )
+    .await
+    .unwrap();
+    assert_eq!(translated, vec!["Hallo", "Welt", "Tschüss"]);
+    assert_eq!(*progress.borrow(), vec![1, 2, 3]);
+
+    let first = requests.recv().await.unwrap();
+    assert!(first.contains("[1] Hello\\n[2] world."), "{}", first);
+    for _ in 0..2 {
+        requests.recv().await.unwrap();
+    }
+    // The last batch carries the previous segments as context, on one line each
+    let last = requests.recv().await.unwrap();
+    assert!(last.contains("world.\\n=> Welt"), "{}", last);
+    assert!(last.contains("[1] Bye now"), "{}", last);
+}
+
+#[tokio::test]
+async fn test_detect_chapters() {
+    use crate::chapters::{detect_chapters, to_ffmetadata, to_webvtt, ChapterMethod, ChapterOptions, TimedText};
+
+    // Two topics of three windows each, the embeddings turn at the fourth window
+    let (url, mut requests) = mock_server(vec![
+        (
+            200,
+            "",
+            r#"{"data": [{"embedding": [1, 0]}, {"embedding": [1, 0.1]}, {"embedding": [1, 0]},
+                {"embedding": [0, 1]}, {"embedding": [0.1, 1]}, {"embedding": [0, 1]}]}"#,
+        ),
+        (
+            200,
+            "",
+            r#"{"choices": [{"message": {"content": "[1] **Cooking**\n[2] 03:00 Gardening"}}]}"#,
+        ),
+    ])
+    .await;
+    let segments: Vec<TimedText> = (0..6)
+        .map(|index| TimedText {
+            start: index * 6000,
+            stop: (index + 1) * 6000,
+            text: format!("Part {}", index),
+        })
+        .collect();
+    let chapter_options = ChapterOptions {
+        window_chars: 1,
+        ..Default::default()
+    };
+    let chapters = detect_chapters(
+        TextGenerationStrategy::Ollama,
+        &mock_options(&url),
+        &chapter_options,
+        &segments,
+    )
+    .await
+    .unwrap();
+    assert_eq!(chapters.len(), 2);
+    assert_eq!(
+        (chapters[0].start, chapters[0].stop, chapters[0].title.as_str()),
+        (0, 18000, "Cooking")
+    );
+    assert_eq!(
+        (chapters[1].start, chapters[1].stop, chapters[1].title.as_str()),
+        (18000, 36000, "Gardening")
+    );
+    requests.recv().await.unwrap();
+    let titles = requests.recv().await.unwrap();
+    assert!(titles.contains("[2] Part 3 Part 4 Part 5"), "{}", titles);
+
+    assert!(to_webvtt(&chapters).contains("2\n00:03:00.000 --> 00:06:00.000\nGardening\n"));
+    assert!(
+        to_ffmetadata(&chapters).starts_with(";FFMETADATA1\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=180000\ntitle=Cooking\n")
+    );
+
+    // The model's outline, with a chapter too close to the previous one merged into it
+    let (url, _) = mock_server(vec![(
+        200,
+        "",
+        r#"{"choices": [{"message": {"content": "[2] Intro\n[3] Too soon\n[5] The end"}}]}"#,
+    )])
+    .await;
+    let chapter_options = ChapterOptions {
+        method: ChapterMethod::Llm,
+        min_chapter_secs: 150,
+        window_chars: 1,
+        ..Default::default()
+    };
+    let chapters = detect_chapters(
+        TextGenerationStrategy::Ollama,
+        &mock_options(&url),
+        &chapter_options,
+        &segments,
+    )
+    .await
+    .unwrap();
+    let outline: Vec<(i64, &str)> = chapters
+        .iter()
+        .map(|chapter| (chapter.start, chapter.title.as_str()))
+        .collect();
+    assert_eq!(outline, vec![(0, "Intro"), (24000, "The end")]);
+}
@@ -1,11 +1,17 @@
 // src/text_generation.rs
 
+use crate::error::TextGenerationError;
+use crate::http::{read_json, send_with_retry, shared_client, HttpOptions};
 use eyre::{eyre, Result};
-use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::{debug, error};
 
+/// Sampling sent to Gemini when the options leave it unset
+const GEMINI_TEMPERATURE: f32 = 1.0;
+const GEMINI_TOP_K: u32 = 64;
+const GEMINI_TOP_P: f32 = 0.95;
+
 #[derive(Deserialize)]
 pub struct TextGenerationOptions {
     pub ollama_base_url: String,
@@ -14,145 +20,245 @@ pub struct TextGenerationOptions {
     pub google_api_key: String,
     pub gemini_model: String,
     pub max_output_tokens: i32,
+    #[serde(default = "default_ollama_embedding_model")]
+    pub ollama_embedding_model: String,
+    #[serde(default = "default_gemini_embedding_model")]
+    pub gemini_embedding_model: String,
+    #[serde(default = "default_gemini_base_url")]
+    pub gemini_base_url: String,
+    /// Sampling parameters, the model's own defaults are used when missing
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Only used by Gemini, OpenAI compatible APIs don't support it
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub http: HttpOptions,
 }
-#[derive(Deserialize)]
+
+impl Default for TextGenerationOptions {
+    fn default() -> Self {
+        TextGenerationOptions {
+            ollama_base_url: String::new(),
+            ollama_model: String::new(),
+            ollama_api_key: String::new(),
+            google_api_key: String::new(),
+            gemini_model: String::new(),
+            max_output_tokens: 4096,
+            ollama_embedding_model: default_ollama_embedding_model(),
+            gemini_embedding_model: default_gemini_embedding_model(),
+            gemini_base_url: default_gemini_base_url(),
+            temperature: None,
+            top_k: None,
+            top_p: None,
+            http: HttpOptions::default(),
+        }
+    }
+}
+
+fn default_gemini_base_url() -> String {
+    "https://generativelanguage.googleapis.com".to_string()
+}
+
+fn default_ollama_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_gemini_embedding_model() -> String {
+    "text-embedding-004".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
 pub enum TextGenerationStrategy {
     Ollama,
     GoogleGemini,
 }
 
+/// Generated text along with the model which produced it and the token usage reported by the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextGenerationResponse {
+    pub text: String,
+    pub model: String,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+}
+
 pub async fn generate_text(
     strategy: TextGenerationStrategy,
     options: &TextGenerationOptions,
     messages: Vec<Value>,
 ) -> Result<String> {
+    Ok(generate_text_with_usage(strategy, options, messages).await?.text)
+}
+
+pub async fn generate_text_with_usage(
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+    messages: Vec<Value>,
+) -> Result<TextGenerationResponse> {
     match strategy {
         TextGenerationStrategy::Ollama => generate_text_ollama(options, messages).await,
         TextGenerationStrategy::GoogleGemini => generate_text_gemini(options, messages).await,
     }
 }
 
-async fn generate_text_ollama(options: &TextGenerationOptions, messages: Vec<Value>) -> Result<String> {
-    let client = Client::new();
+async fn generate_text_ollama(options: &TextGenerationOptions, messages: Vec<Value>) -> Result<TextGenerationResponse> {
+    let client = shared_client(&options.http)?;
     let url = format!("{}/v1/chat/completions", options.ollama_base_url);
 
     debug!("Sending request to Ollama API: {}", url);
 
-    let response = client
+    let mut request_body = json!({
+        "model": options.ollama_model,
+        "messages": messages,
+    });
+    if let Some(temperature) = options.temperature {
+        request_body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = options.top_p {
+        request_body["top_p"] = json!(top_p);
+    }
+
+    let request = client
         .post(&url)
         .header("Content-Type", "application/json")
         .header("Authorization", format!("Bearer {}", options.ollama_api_key))
-        .json(&json!({
-            "model": options.ollama_model,
-            "messages": messages,
-        }))
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Failed to send request to Ollama API: {}", e);
-            eyre!("Failed to send request to Ollama API: {}", e)
-        })?;
+        .json(&request_body);
+    let response = send_with_retry(request, &options.http, "Ollama API").await?;
+    let response_body = read_json(response, "Ollama API").await?;
 
-    let response_body: Value = response.json().await.map_err(|e| {
-        error!("Failed to parse Ollama API response: {}", e);
-        eyre!("Failed to parse Ollama API response: {}", e)
-    })?;
+    if response_body["choices"][0]["finish_reason"] == "content_filter" {
+        error!("Ollama API response was blocked by content filter");
+        return Err(TextGenerationError::ContentFilter {
+            message: "the model stopped because of its content policy".to_string(),
+        }
+        .into());
+    }
 
-    response_body["choices"][0]["message"]["content"]
+    let text = response_body["choices"][0]["message"]["content"]
         .as_str()
         .map(|s| s.to_string())
         .ok_or_else(|| {
             error!("Failed to extract response text from Ollama API");
-            eyre!("Failed to extract response text from Ollama API")
-        })
+            TextGenerationError::InvalidResponse {
+                message: "Failed to extract response text from Ollama API".to_string(),
+            }
+        })?;
+
+    Ok(TextGenerationResponse {
+        text,
+        model: response_body["model"].as_str().unwrap_or(&options.ollama_model).to_string(),
+        prompt_tokens: response_body["usage"]["prompt_tokens"].as_i64(),
+        completion_tokens: response_body["usage"]["completion_tokens"].as_i64(),
+    })
 }
 
-async fn generate_text_gemini(options: &TextGenerationOptions, messages: Vec<Value>) -> Result<String> {
-    let client = Client::new();
+async fn generate_text_gemini(options: &TextGenerationOptions, messages: Vec<Value>) -> Result<TextGenerationResponse> {
+    let client = shared_client(&options.http)?;
     let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-        options.gemini_model, options.google_api_key
+        "{}/v1beta/models/{}:generateContent?key={}",
+        options.gemini_base_url, options.gemini_model, options.google_api_key
     );
 
     debug!("Sending request to Google Gemini API");
 
-    let gemini_messages = convert_messages_to_gemini_format(&messages)?;
+    let (system_instruction, contents) = convert_messages_to_gemini_format(&messages)?;
 
-    let request_body = json!({
-        "contents": gemini_messages,
+    let mut request_body = json!({
+        "contents": contents,
         "generationConfig": {
-            "temperature": 1,
-            "topK": 64,
-            "topP": 0.95,
+            "temperature": options.temperature.unwrap_or(GEMINI_TEMPERATURE),
+            "topK": options.top_k.unwrap_or(GEMINI_TOP_K),
+            "topP": options.top_p.unwrap_or(GEMINI_TOP_P),
             "maxOutputTokens": options.max_output_tokens,
             "responseMimeType": "text/plain"
         }
     });
+    if let Some(system_instruction) = system_instruction {
+        request_body["systemInstruction"] = system_instruction;
+    }
 
-    let response = client
+    let request = client
         .post(&url)
         .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Failed to send request to Google Gemini API: {}", e);
-            eyre!("Network error: Failed to send request to Google Gemini API. Please check your internet connection and try again.")
-        })?;
-
-    if !response.status().is_success() {
-        let error_body: Value = response.json().await.map_err(|e| {
-            error!("Failed to parse error response from Google Gemini API: {}", e);
-            eyre!("Unexpected API response: Unable to parse error details. Please try again later.")
-        })?;
-
-        let error_message = error_body["error"]["message"].as_str().unwrap_or("Unknown error occurred");
-        let error_code = error_body["error"]["code"]
-            .as_u64()
-            .map(|code| code.to_string())
-            .unwrap_or_else(|| "Unknown".to_string());
+        .json(&request_body);
+    let response = send_with_retry(request, &options.http, "Google Gemini API").await?;
+    let response_body = read_json(response, "Google Gemini API").await?;
 
-        error!("Google Gemini API error: {} (Code: {})", error_message, error_code);
-        return Err(eyre!("API Error (Code: {}): {}", error_code, error_message));
+    if let Some(block_reason) = response_body["promptFeedback"]["blockReason"].as_str() {
+        error!("Google Gemini API blocked the prompt: {}", block_reason);
+        return Err(TextGenerationError::ContentFilter {
+            message: format!("the prompt was blocked ({})", block_reason),
+        }
+        .into());
+    }
+    let finish_reason = response_body["candidates"][0]["finishReason"].as_str().unwrap_or_default();
+    if matches!(finish_reason, "SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII") {
+        error!("Google Gemini API blocked the response: {}", finish_reason);
+        return Err(TextGenerationError::ContentFilter {
+            message: format!("the response was blocked ({})", finish_reason),
+        }
+        .into());
     }
 
-    let response_body: Value = response.json().await.map_err(|e| {
-        error!("Failed to parse Google Gemini API response: {}", e);
-        eyre!("Unexpected API response: Unable to parse the response. Please try again later.")
-    })?;
-
-    response_body["candidates"][0]["content"]["parts"][0]["text"]
+    let text = response_body["candidates"][0]["content"]["parts"][0]["text"]
         .as_str()
         .map(|s| s.to_string())
         .ok_or_else(|| {
             error!("Failed to extract response text from Google Gemini API");
-            eyre!("Unexpected API response: The response format was not as expected. Please try again later.")
-        })
+            TextGenerationError::InvalidResponse {
+                message: "The response format was not as expected".to_string(),
+            }
+        })?;
+
+    Ok(TextGenerationResponse {
+        text,
+        model: options.gemini_model.clone(),
+        prompt_tokens: response_body["usageMetadata"]["promptTokenCount"].as_i64(),
+        completion_tokens: response_body["usageMetadata"]["candidatesTokenCount"].as_i64(),
+    })
 }
 
-fn convert_messages_to_gemini_format(messages: &[Value]) -> Result<Vec<Value>> {
-    messages
-        .iter()
-        .map(|message| {
-            let role = message["role"].as_str().ok_or_else(|| eyre!("Missing role in message"))?;
-            let content = message["content"]
-                .as_str()
-                .ok_or_else(|| eyre!("Missing content in message"))?;
-
-            let mapped_role = match role {
-                "user" => "user",
-                "assistant" => "model",
-                _ => role, // Keep other roles as they are
-            };
-
-            Ok(json!({
-                "role": mapped_role,
-                "parts": [
-                    {
-                        "text": content
-                    }
-                ]
-            }))
-        })
-        .collect()
+/// Split OpenAI style messages into Gemini's `systemInstruction` and `contents`.
+/// Gemini only knows the user and model roles and expects them to alternate,
+/// so system messages are moved to the instruction and consecutive turns of the same role are merged.
+pub fn convert_messages_to_gemini_format(messages: &[Value]) -> Result<(Option<Value>, Vec<Value>)> {
+    let mut system_parts: Vec<Value> = Vec::new();
+    let mut contents: Vec<Value> = Vec::new();
+
+    for message in messages {
+        let role = message["role"].as_str().ok_or_else(|| eyre!("Missing role in message"))?;
+        let content = message["content"]
+            .as_str()
+            .ok_or_else(|| eyre!("Missing content in message"))?;
+        let part = json!({ "text": content });
+
+        let mapped_role = match role {
+            "system" => {
+                system_parts.push(part);
+                continue;
+            }
+            "user" => "user",
+            "assistant" | "model" => "model",
+            _ => return Err(eyre!("Unsupported role {} in message", role)),
+        };
+
+        match contents.last_mut() {
+            Some(last) if last["role"] == mapped_role => {
+                if let Some(parts) = last["parts"].as_array_mut() {
+                    parts.push(part);
+                }
+            }
+            _ => contents.push(json!({ "role": mapped_role, "parts": [part] })),
+        }
+    }
+
+    let system_instruction = if system_parts.is_empty() {
+        None
+    } else {
+        Some(json!({ "parts": system_parts }))
+    };
+    Ok((system_instruction, contents))
 }
@@ -0,0 +1,159 @@
+// src/translation.rs
+
+use crate::text_generation::{generate_text, TextGenerationOptions, TextGenerationStrategy};
+use eyre::{bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+/// How to translate the segments of a transcript
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranslationOptions {
+    /// Language to translate to, by name such as "German" or "Brazilian Portuguese"
+    pub target_language: String,
+    /// Detected by the model when missing
+    #[serde(default)]
+    pub source_language: Option<String>,
+    /// Segments sent in one request
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Segments of the previous batch sent again with their translation, so names and terms stay consistent
+    #[serde(default = "default_context_size")]
+    pub context_size: usize,
+}
+
+fn default_batch_size() -> usize {
+    25
+}
+
+fn default_context_size() -> usize {
+    5
+}
+
+impl TranslationOptions {
+    pub fn new(target_language: &str) -> Self {
+        TranslationOptions {
+            target_language: target_language.to_string(),
+            source_language: None,
+            batch_size: default_batch_size(),
+            context_size: default_context_size(),
+        }
+    }
+}
+
+/// Translate the text of every segment, returning one translation per segment in the same order.
+/// Segments are numbered in the prompt so the model keeps their boundaries. A batch answered with
+/// missing or merged lines is split in halves and sent again, down to single segments.
+/// `on_progress` is called with the number of segments translated so far.
+pub async fn translate_segments(
+    strategy: TextGenerationStrategy,
+    options: &TextGenerationOptions,
+    translation: &TranslationOptions,
+    texts: &[String],
+    on_progress: impl Fn(usize),
+) -> Result<Vec<String>> {
+    if translation.target_language.trim().is_empty() {
+        bail!("target language is required")
+    }
+    let batch_size = translation.batch_size.max(1);
+    let mut translated: Vec<String> = Vec::with_capacity(texts.len());
+    // Ranges still to translate, in order
+    let mut pending: Vec<(usize, usize)> = (0..texts.len())
+        .step_by(batch_size)
+        .map(|start| (start, (start + batch_size).min(texts.len())))
+        .rev()
+        .collect();
+
+    while let Some((start, end)) = pending.pop() {
+        let context_start = start.saturating_sub(translation.context_size);
+        let context: Vec<(&str, &str)> = texts[context_start..start]
+            .iter()
+            .zip(&translated[context_start..start])
+            .map(|(source, target)| (source.as_str(), target.as_str()))
+            .collect();
+        let messages = translation_messages(translation, &context, &texts[start..end]);
+        let response = generate_text(strategy, options, messages).await?;
+
+        match parse_numbered_lines(&response, end - start) {
+            Some(lines) => translated.extend(lines),
+            None if end - start == 1 => {
+                // A single segment can't be misnumbered, the whole answer is its translation
+                translated.push(strip_number(response.trim()).to_string());
+            }
+            None => {
+                let middle = start + (end - start) / 2;
+                warn!("translation of segments {}..{} lost lines, sending it in halves", start, end);
+                pending.push((middle, end));
+                pending.push((start, middle));
+                continue;
+            }
+        }
+        debug!("translated {} of {} segments", translated.len(), texts.len());
+        on_progress(translated.len());
+    }
+    Ok(translated)
+}
+
+fn translation_messages(translation: &TranslationOptions, context: &[(&str, &str)], batch: &[String]) -> Vec<Value> {
+    let source = translation
+        .source_language
+        .as_deref()
+        .filter(|language| !language.trim().is_empty())
+        .map(|language| format!(" from {}", language))
+        .unwrap_or_default();
+    let system = format!(
+        "You translate transcript segments{} into {}. \
+Every input line is one segment, prefixed with its number in square brackets. \
+Answer with exactly one line per segment, prefixed with the same number, in the same order. \
+Never merge, split, skip or add lines, even when a sentence continues in the next segment. \
+Keep names, numbers and the speaking style. Answer with the translated lines only.",
+        source, translation.target_language
+    );
+
+    let mut messages = vec![json!({"role": "system", "content": system})];
+    if !context.is_empty() {
+        let mut previous = String::from("Previous segments and their translation, for context only:\n");
+        for (source, target) in context {
+            previous.push_str(&format!("{}\n=> {}\n", one_line(source), one_line(target)));
+        }
+        messages.push(json!({"role": "user", "content": previous}));
+    }
+    let lines: Vec<String> = batch
+        .iter()
+        .enumerate()
+        .map(|(index, text)| format!("[{}] {}", index + 1, one_line(text)))
+        .collect();
+    messages.push(json!({"role": "user", "content": lines.join("\n")}));
+    messages
+}
+
+/// The numbered lines 1 to `count` of a response, None when any is missing or repeated
+pub(crate) fn parse_numbered_lines(response: &str, count: usize) -> Option<Vec<String>> {
+    let mut lines: Vec<Option<String>> = vec![None; count];
+    for line in response.lines() {
+        let Some((number, text)) = line.trim().strip_prefix('[').and_then(|rest| rest.split_once(']')) else {
+            continue;
+        };
+        let Ok(number) = number.trim().parse::<usize>() else {
+            continue;
+        };
+        let slot = lines.get_mut(number.checked_sub(1)?)?;
+        if slot.is_some() {
+            return None;
+        }
+        *slot = Some(text.trim().to_string());
+    }
+    lines.into_iter().collect()
+}
+
+fn strip_number(text: &str) -> &str {
+    match text.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((number, rest)) if number.trim().parse::<usize>().is_ok() => rest.trim(),
+        _ => text,
+    }
+}
+
+/// Segment text on one line, the line breaks would be taken for segment boundaries
+pub(crate) fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}